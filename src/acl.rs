@@ -0,0 +1,301 @@
+extern crate sqlite;
+
+use std::fmt;
+use std::net::IpAddr;
+use std::str::FromStr;
+
+use self::sqlite::{State,Value};
+
+use netspace::{NetspaceIo,Netspace,NodeRole,StorageFailure,step};
+
+/*
+ * Access control for protocol commands
+ *
+ * Rules are checked in the order they were added and the first
+ * one that matches the command, netservice module and caller
+ * decides; when none match `acl_default` in node.conf does
+ * (`deny`, otherwise allow). Callers are matched by address
+ * range, by being a registered node or by the role of the node
+ * registered at their address. Rules are held in the netspace
+ * database so they can be edited from the management socket
+ * while the primary is running.
+ */
+
+#[derive(Debug,Clone,PartialEq)]
+pub enum AclSubject {
+	Any,
+	Cidr(IpAddr, u8),
+	Node,
+	Role(NodeRole),
+}
+
+impl AclSubject {
+	pub fn from_str(s: &str) -> Option<AclSubject> {
+		let mut parts = s.splitn(2, ":");
+
+		match (parts.next(), parts.next()) {
+			(Some("any"), None) => Some(AclSubject::Any),
+			(Some("node"), None) => Some(AclSubject::Node),
+			(Some("role"), Some(r)) => NodeRole::from_str(r).map(AclSubject::Role),
+			(Some("cidr"), Some(c)) => AclSubject::cidr(c),
+			_ => None
+		}
+	}
+
+	/// `10.0.0.0/8`, or a bare address for a single host
+	pub fn cidr(s: &str) -> Option<AclSubject> {
+		let mut parts = s.splitn(2, "/");
+
+		let net = match parts.next().map(IpAddr::from_str) {
+			Some(Ok(a)) => a,
+			_ => return None
+		};
+
+		let max = match net { IpAddr::V4(_) => 32, IpAddr::V6(_) => 128 };
+		let bits = match parts.next() {
+			Some(b) => match b.parse::<u8>() {
+				Ok(n) if n <= max => n,
+				_ => return None
+			},
+			None => max
+		};
+
+		Some(AclSubject::Cidr(net, bits))
+	}
+}
+
+impl fmt::Display for AclSubject {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			AclSubject::Any => write!(f, "any"),
+			AclSubject::Node => write!(f, "node"),
+			AclSubject::Role(ref r) => write!(f, "role:{}", r),
+			AclSubject::Cidr(ref a, b) => write!(f, "cidr:{}/{}", a, b),
+		}
+	}
+}
+
+#[derive(Debug,Clone,PartialEq)]
+pub struct AclRule {
+	pub id: i64,
+	pub allow: bool,
+	pub command: String,
+	pub module: Option<String>,
+	pub subject: AclSubject,
+}
+
+impl AclRule {
+	pub fn new(allow: bool, command: &str, module: Option<&str>, subject: AclSubject) -> AclRule {
+		AclRule {
+			id: 0,
+			allow: allow,
+			command: command.to_string(),
+			module: module.map(|m| m.to_string()),
+			subject: subject,
+		}
+	}
+
+	fn covers(&self, command: &str, module: Option<&str>) -> bool {
+		(self.command == "*" || self.command == command)
+			&& match self.module {
+				Some(ref m) => module == Some(m.as_str()),
+				None => true
+			}
+	}
+}
+
+impl fmt::Display for AclRule {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{} {}{} {}",
+				match self.allow { true => "allow", false => "deny" },
+				self.command,
+				self.module.as_ref().map_or(String::new(), |m| format!("/{}", m)),
+				self.subject)
+	}
+}
+
+fn cidr_contains(net: &IpAddr, bits: u8, addr: &IpAddr) -> bool {
+	let (n, a) : (Vec<u8>, Vec<u8>) = match (*net, *addr) {
+		(IpAddr::V4(n), IpAddr::V4(a)) => (n.octets().to_vec(), a.octets().to_vec()),
+		(IpAddr::V6(n), IpAddr::V6(a)) => (n.octets().to_vec(), a.octets().to_vec()),
+		_ => return false
+	};
+
+	let whole = (bits / 8) as usize;
+	if n[..whole] != a[..whole] { return false }
+
+	let rest = bits % 8;
+	if rest == 0 { return true }
+
+	let mask = 0xffu8 << (8 - rest);
+	n[whole] & mask == a[whole] & mask
+}
+
+impl NetspaceIo {
+
+	pub fn acl_rules(&self) -> Result<Vec<AclRule>,StorageFailure> {
+		let mut statement = try!(self.db().prepare("SELECT id,allow,command,module,subject FROM `acl_rules` ORDER BY id ASC"));
+
+		let mut v = Vec::new();
+		while let State::Row = try!(step(&mut statement)) {
+			let subject = try!(statement.read::<String>(4));
+			let module = try!(statement.read::<String>(3));
+
+			v.push(AclRule {
+				id: try!(statement.read::<i64>(0)),
+				allow: try!(statement.read::<i64>(1)) == 1,
+				command: try!(statement.read::<String>(2)),
+				module: match module.is_empty() { true => None, false => Some(module) },
+				subject: match AclSubject::from_str(&subject) {
+					Some(s) => s,
+					None => return Err(StorageFailure::Query(format!("Bad ACL subject `{}`", subject)))
+				},
+			});
+		}
+
+		Ok(v)
+	}
+
+	pub fn acl_add(&self, rule: &AclRule) -> Result<i64,StorageFailure> {
+		let t = try!(self.transaction());
+		let mut statement = try!(self.db().prepare("INSERT INTO `acl_rules` (allow,command,module,subject) VALUES (?,?,?,?)"));
+		try!(statement.bind(1, &Value::Integer( rule.allow as i64 )));
+		try!(statement.bind(2, &Value::String( rule.command.clone() )));
+		try!(statement.bind(3, &Value::String( rule.module.clone().unwrap_or(String::new()) )));
+		try!(statement.bind(4, &Value::String( format!("{}", rule.subject) )));
+		try!(step(&mut statement));
+
+		let mut statement = try!(self.db().prepare("SELECT last_insert_rowid()"));
+		try!(step(&mut statement));
+		let id = try!(statement.read::<i64>(0));
+
+		try!(self.audit_record("acl_add", "", "", &format!("{} {}", id, rule)));
+		try!(t.commit());
+		Ok(id)
+	}
+
+	/// Remove a rule, false if there was no rule with that id
+	pub fn acl_remove(&self, id: i64) -> Result<bool,StorageFailure> {
+		let rule = match try!(self.acl_rules()).into_iter().find(|r| r.id == id) {
+			Some(r) => r,
+			None => return Ok(false)
+		};
+
+		let t = try!(self.transaction());
+		let mut statement = try!(self.db().prepare("DELETE FROM `acl_rules` WHERE id = ?"));
+		try!(statement.bind(1, &Value::Integer( id )));
+		try!(step(&mut statement));
+
+		try!(self.audit_record("acl_remove", "", &format!("{} {}", id, rule), ""));
+		try!(t.commit());
+		Ok(true)
+	}
+
+	/// Whether a caller at `addr` may run `command`, on netservice
+	/// `module` for service requests
+	pub fn acl_permits(&self, command: &str, module: Option<&str>, addr: &IpAddr, default: bool) -> Result<bool,StorageFailure> {
+		let rules = try!(self.acl_rules());
+
+		// Only look the caller up when a rule cares who it is
+		let mut registered = None;
+
+		for rule in rules.iter().filter(|r| r.covers(command, module)) {
+			let matched = match rule.subject {
+				AclSubject::Any => true,
+				AclSubject::Cidr(ref net, bits) => cidr_contains(net, bits, addr),
+				AclSubject::Node | AclSubject::Role(_) => {
+					// Nodes still awaiting approval are not registered yet
+					if registered.is_none() {
+						let mut nodes = Vec::new();
+						for n in try!(self.try_gsn_nodes_by_address(&addr.to_string())) {
+							if !try!(self.pending_check(n.springname())) { nodes.push(n) }
+						}
+						registered = Some(nodes);
+					}
+
+					let nodes = registered.as_ref().unwrap();
+					match rule.subject {
+						AclSubject::Role(ref r) => nodes.iter().any(|n| n.role() == *r),
+						_ => !nodes.is_empty()
+					}
+				}
+			};
+
+			if matched { return Ok(rule.allow) }
+		}
+
+		Ok(default)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use netspace::*;
+
+	fn addr(s: &str) -> IpAddr {
+		IpAddr::from_str(s).unwrap()
+	}
+
+	#[test]
+	fn ts_acl_subject_p() {
+		assert_eq!(AclSubject::from_str("cidr:10.0.0.0/8"), Some(AclSubject::Cidr(addr("10.0.0.0"), 8)));
+		assert_eq!(AclSubject::from_str("cidr:10.1.2.3"), Some(AclSubject::Cidr(addr("10.1.2.3"), 32)));
+		assert_eq!(AclSubject::from_str("role:org"), Some(AclSubject::Role(NodeRole::Org)));
+		assert_eq!(AclSubject::from_str("cidr:10.0.0.0/33"), None);
+		assert_eq!(AclSubject::from_str("everyone"), None);
+	}
+
+	#[test]
+	fn ts_acl_cidr_contains_p() {
+		assert!(cidr_contains(&addr("192.168.0.0"), 16, &addr("192.168.4.2")));
+		assert!(cidr_contains(&addr("192.168.0.0"), 23, &addr("192.168.1.200")));
+		assert!(!cidr_contains(&addr("192.168.0.0"), 23, &addr("192.168.2.1")));
+		assert!(!cidr_contains(&addr("192.168.0.0"), 16, &addr("::1")));
+	}
+
+	#[test]
+	fn ts_acl_permits_p() {
+		let nsio = NetspaceIo::new(":memory:");
+		nsio.gsn_node_register(&Node::from_str("spring:foo,host:bar,address:192.168.1.2,role:org,service:http").unwrap()).unwrap();
+
+		nsio.acl_add(&AclRule::new(true, "service", Some("sync"), AclSubject::cidr("10.0.0.0/8").unwrap())).unwrap();
+		nsio.acl_add(&AclRule::new(false, "service", Some("sync"), AclSubject::Any)).unwrap();
+		nsio.acl_add(&AclRule::new(true, "info", None, AclSubject::Role(NodeRole::Org))).unwrap();
+		nsio.acl_add(&AclRule::new(false, "info", None, AclSubject::Any)).unwrap();
+
+		assert!(nsio.acl_permits("service", Some("sync"), &addr("10.1.1.1"), true).unwrap());
+		assert!(!nsio.acl_permits("service", Some("sync"), &addr("192.168.1.2"), true).unwrap());
+		assert!(!nsio.acl_permits("service", Some("cert"), &addr("192.168.1.2"), false).unwrap());
+		assert!(nsio.acl_permits("info", None, &addr("192.168.1.2"), true).unwrap());
+		assert!(!nsio.acl_permits("info", None, &addr("192.168.1.3"), true).unwrap());
+	}
+
+	#[test]
+	fn ts_acl_permits_f() {
+		let nsio = NetspaceIo::new(":memory:");
+		nsio.gsn_node_register(&Node::from_str("spring:foo,host:bar,address:192.168.1.2,role:org,service:http").unwrap()).unwrap();
+		nsio.pending_add("foo", "192.168.1.2").unwrap();
+
+		nsio.acl_add(&AclRule::new(true, "info", None, AclSubject::Node)).unwrap();
+		nsio.acl_add(&AclRule::new(false, "info", None, AclSubject::Any)).unwrap();
+
+		// Awaiting approval is not registered
+		assert!(!nsio.acl_permits("info", None, &addr("192.168.1.2"), true).unwrap());
+
+		// Nor is a source that cannot be looked up
+		nsio.db().execute("DROP TABLE `geosub_netspace`").unwrap();
+		assert!(nsio.acl_permits("info", None, &addr("192.168.1.2"), true).is_err());
+	}
+
+	#[test]
+	fn ts_acl_remove_p() {
+		let nsio = NetspaceIo::new(":memory:");
+		let id = nsio.acl_add(&AclRule::new(false, "*", None, AclSubject::Any)).unwrap();
+		assert!(!nsio.acl_permits("resolve", None, &addr("192.168.1.2"), true).unwrap());
+
+		assert!(nsio.acl_remove(id).unwrap());
+		assert!(!nsio.acl_remove(id).unwrap());
+		assert!(nsio.acl_permits("resolve", None, &addr("192.168.1.2"), true).unwrap());
+	}
+}
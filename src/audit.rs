@@ -0,0 +1,233 @@
+extern crate sqlite;
+
+use self::sqlite::{State,Value};
+
+use netspace::{NetspaceIo,Node,StorageFailure,step};
+
+/*
+ * Audit log of netspace changes
+ *
+ * Every mutating `Netspace` call on a `NetspaceIo` writes an
+ * entry here with the actor set on the connection -- the source
+ * address of a protocol request or `management` for the
+ * management socket.
+ */
+
+#[derive(Debug,Clone,PartialEq)]
+pub struct AuditEntry {
+	pub timestamp: String,
+	pub actor: String,
+	pub action: String,
+	pub springname: String,
+	pub before: String,
+	pub after: String,
+}
+
+#[derive(Debug,Clone,PartialEq)]
+pub struct AuditFilter {
+	pub springname: Option<String>,
+	pub action: Option<String>,
+	pub since: Option<String>,
+	pub until: Option<String>,
+	pub limit: i64,
+}
+
+impl AuditFilter {
+	pub fn new() -> AuditFilter {
+		AuditFilter {
+			springname: None,
+			action: None,
+			since: None,
+			until: None,
+			limit: 100,
+		}
+	}
+}
+
+/// Flatten a node into the same `key:value` form `Node::from_str` reads
+pub fn node_record(node: &Node) -> String {
+	format!("spring:{},host:{},address:{},service:{},state:{},role:{}",
+			node.springname(), node.hostfield(), node.address(),
+			node.service(), node.state(), node.role())
+}
+
+/// Enough of a token to tell entries apart without the log giving it away
+pub fn token_record(token: &str) -> String {
+	match token.len() > 8 {
+		true => format!("{}...", token.chars().take(4).collect::<String>()),
+		false => "...".to_string(),
+	}
+}
+
+// Times are either unix timestamps or anything sqlite's date functions accept
+fn time_bound(column: &str, op: &str, value: &str, values: &mut Vec<Value>) -> String {
+	match value.parse::<i64>() {
+		Ok(t) => {
+			values.push(Value::Integer(t));
+			format!("{} {} ?", column, op)
+		},
+		Err(_) => {
+			values.push(Value::String(value.to_string()));
+			format!("{} {} CAST(strftime('%s', ?) AS INTEGER)", column, op)
+		}
+	}
+}
+
+impl NetspaceIo {
+
+	pub fn audit_record(&self, action: &str, springname: &str, before: &str, after: &str) -> Result<(),StorageFailure> {
+		let mut statement = try!(self.db().prepare("INSERT INTO `netspace_audit`
+							(timestamp,actor,action,springname,before,after)
+							VALUES (strftime('%s','now'),?,?,?,?,?)"));
+
+		try!(statement.bind(1, &Value::String( self.actor() )));
+		try!(statement.bind(2, &Value::String( action.to_string() )));
+		try!(statement.bind(3, &Value::String( springname.to_string() )));
+		try!(statement.bind(4, &Value::String( before.to_string() )));
+		try!(statement.bind(5, &Value::String( after.to_string() )));
+
+		try!(step(&mut statement));
+		Ok(())
+	}
+
+	/// Id of the latest entry, to count the entries after it
+	pub fn audit_mark(&self) -> Result<i64,StorageFailure> {
+		let mut statement = try!(self.db().prepare("SELECT IFNULL(MAX(id),0) FROM `netspace_audit`"));
+		try!(step(&mut statement));
+		Ok(try!(statement.read::<i64>(0)))
+	}
+
+	/// Entries recorded after `mark`; a change records one for
+	/// each node, token or rule it touches
+	pub fn audit_since(&self, mark: i64) -> Result<i64,StorageFailure> {
+		let mut statement = try!(self.db().prepare("SELECT COUNT(*) FROM `netspace_audit` WHERE id > ?"));
+		try!(statement.bind(1, &Value::Integer( mark )));
+		try!(step(&mut statement));
+		Ok(try!(statement.read::<i64>(0)))
+	}
+
+	/// Entries matching every set field of the filter, most recent first
+	pub fn audit_entries(&self, filter: &AuditFilter) -> Result<Vec<AuditEntry>,StorageFailure> {
+		let mut clauses : Vec<String> = Vec::new();
+		let mut values : Vec<Value> = Vec::new();
+
+		if let Some(ref s) = filter.springname {
+			clauses.push("springname = ?".to_string());
+			values.push(Value::String(s.clone()));
+		}
+
+		if let Some(ref a) = filter.action {
+			clauses.push("action = ?".to_string());
+			values.push(Value::String(a.clone()));
+		}
+
+		if let Some(ref t) = filter.since {
+			clauses.push(time_bound("timestamp", ">=", t, &mut values));
+		}
+
+		if let Some(ref t) = filter.until {
+			clauses.push(time_bound("timestamp", "<=", t, &mut values));
+		}
+
+		let condition = match clauses.is_empty() {
+			true => String::new(),
+			false => format!("WHERE {}", clauses.join(" AND "))
+		};
+
+		let query = format!("SELECT datetime(timestamp,'unixepoch'),actor,action,springname,before,after
+							FROM `netspace_audit` {} ORDER BY id DESC LIMIT {}", condition, filter.limit);
+
+		let mut statement = try!(self.db().prepare(query));
+		for (i, value) in values.iter().enumerate() {
+			try!(statement.bind(i+1, value));
+		}
+
+		let mut v = Vec::new();
+		while let State::Row = try!(step(&mut statement)) {
+			v.push(AuditEntry {
+				timestamp: try!(statement.read::<String>(0)),
+				actor: try!(statement.read::<String>(1)),
+				action: try!(statement.read::<String>(2)),
+				springname: try!(statement.read::<String>(3)),
+				before: try!(statement.read::<String>(4)),
+				after: try!(statement.read::<String>(5)),
+			});
+		}
+
+		Ok(v)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use netspace::*;
+
+	#[test]
+	fn ts_audit_register_update_p() {
+		let nsio = NetspaceIo::new(":memory:");
+		nsio.set_actor("192.168.1.2:55400");
+
+		let mut node = Node::from_str("spring:foo,host:bar,address:192.168.1.2,role:org,service:http").unwrap();
+		nsio.gsn_node_register(&node).unwrap();
+		node.update_state(NodeState::Enabled);
+		nsio.gsn_node_update_state(&node).unwrap();
+
+		let v = nsio.audit_entries(&AuditFilter::new()).unwrap();
+		assert_eq!(v.len(), 2);
+		assert_eq!(v[0].action, "update_state");
+		assert_eq!(v[0].before, format!("{}", NodeState::Disabled));
+		assert_eq!(v[0].after, format!("{}", NodeState::Enabled));
+		assert_eq!(v[1].action, "register");
+		assert_eq!(v[1].actor, "192.168.1.2:55400");
+	}
+
+	#[test]
+	fn ts_audit_filter_p() {
+		let nsio = NetspaceIo::new(":memory:");
+
+		nsio.gsn_node_register(&Node::from_str("spring:foo,host:bar,address:192.168.1.2").unwrap()).unwrap();
+		nsio.gsn_node_register(&Node::from_str("spring:baz,host:bar,address:192.168.1.3").unwrap()).unwrap();
+		nsio.gsn_add_token("3858f62230ac3c915f300c664312c63f", "baz");
+
+		let mut filter = AuditFilter::new();
+		filter.springname = Some("baz".to_string());
+		assert_eq!(nsio.audit_entries(&filter).unwrap().len(), 2);
+
+		filter.action = Some("token_add".to_string());
+		assert_eq!(nsio.audit_entries(&filter).unwrap().len(), 1);
+
+		filter.since = Some("2999-01-01".to_string());
+		assert_eq!(nsio.audit_entries(&filter).unwrap().len(), 0);
+	}
+
+	#[test]
+	fn ts_audit_failed_change_f() {
+		let nsio = NetspaceIo::new(":memory:");
+		assert!(nsio.gsn_node_unregister(&Node::from_str("void").unwrap()).is_err());
+		assert_eq!(nsio.audit_entries(&AuditFilter::new()).unwrap().len(), 0);
+	}
+
+	#[test]
+	fn ts_audit_token_redacted_p() {
+		let nsio = NetspaceIo::new(":memory:");
+		nsio.gsn_add_token("3858f62230ac3c915f300c664312c63f", "baz");
+		nsio.gsn_remove_token("3858f62230ac3c915f300c664312c63f");
+
+		let v = nsio.audit_entries(&AuditFilter::new()).unwrap();
+		assert_eq!(v.len(), 2);
+		assert_eq!(v[1].after, "3858...");
+		assert_eq!(v[0].before, "3858...");
+		assert!(v.iter().all(|e| !e.before.contains("c63f") && !e.after.contains("c63f")));
+	}
+
+	#[test]
+	fn ts_audit_atomic_f() {
+		let nsio = NetspaceIo::new(":memory:");
+		nsio.db().execute("DROP TABLE `netspace_audit`").unwrap();
+
+		// Without an audit log the change itself is not kept
+		assert!(nsio.gsn_node_register(&Node::from_str("spring:foo,host:bar,address:192.168.1.2").unwrap()).is_err());
+		assert!(nsio.gsn_node_by_springname("foo").is_err());
+	}
+}
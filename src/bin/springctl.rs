@@ -0,0 +1,410 @@
+/* Notice:  Copyright 2016, The Care Connections Initiative c.i.c.
+ * Author:  Charlie Fyvie-Gauld (cfg@zunautica.org)
+ * License: GPLv3 (http://www.gnu.org/licenses/gpl-3.0.txt)
+ */
+
+/*
+ * springctl -- management client for the primary
+ *
+ *   springctl <command>          run one command and exit
+ *   springctl                    interactive shell
+ *   springctl -f <file>          run each line of a file ('-' for stdin)
+ *
+ * `--socket <path>` talks to a primary on another socket and
+ * `--remote <host:port>` to one's TLS management listener, checking
+ * its certificate against `--ca <pem>` and authenticating with
+ * `--cert <pem> --key <pem>` or a token. The token is read from
+ * `--token-file <path>` or the SPRINGCTL_TOKEN environment variable
+ * so it never shows in the process list. Commands go over
+ * one framed session (see management/wire.rs) which is opened again
+ * if the primary drops it.
+ */
+
+extern crate openssl;
+extern crate rustyline;
+extern crate unix_socket;
+
+use std::env;
+use std::fs::File;
+use std::io::{self,BufRead,BufReader};
+use std::io::prelude::*;
+use std::net::TcpStream;
+use std::process;
+
+use openssl::ssl::{SslMethod,SslConnectorBuilder};
+use openssl::x509::X509_FILETYPE_PEM;
+
+use rustyline::Editor;
+use rustyline::completion::Completer;
+use rustyline::error::ReadlineError;
+use unix_socket::UnixStream;
+
+#[path = "../management/wire.rs"]
+mod wire;
+
+use wire::Status;
+
+const MANAGEMENT_SOCKET : &'static str = "/var/run/springdvs/primary.sock";
+const HISTORY_FILE : &'static str = ".springctl_history";
+
+/// Zone names with their aliases, actions and operands for completion
+const ZONES : &'static [(&'static [&'static str], &'static [&'static str], &'static [&'static str])] = &[
+	(&["network", "net"], &["view", "remove", "update"],
+		&["all", "node", "springname", "hostname", "address", "role", "state", "service"]),
+	(&["validation", "val"], &["view", "add", "remove"],
+		&["all", "node", "springname", "token"]),
+	(&["service", "ser"], &["init", "manage"],
+		&["all", "module", "cert", "sync"]),
+	(&["log"], &["view"],
+		&["all", "node", "springname", "action", "since", "until", "limit"]),
+	(&["snapshot", "snap"], &["export", "import"],
+		&["file", "merge", "replace", "dryrun"]),
+	(&["geotop", "geo"], &["view", "add", "remove", "priority", "exchange"],
+		&["all", "node", "springname", "geosub", "hostname", "address", "service"]),
+	(&["registration", "reg"], &["view", "approve", "reject"],
+		&["all", "node", "springname"]),
+	(&["throttle", "thr"], &["view", "lift"],
+		&["all", "address"]),
+	(&["acl"], &["view", "add", "remove"],
+		&["all", "allow", "deny", "command", "module", "any", "node", "role", "cidr", "id"]),
+	(&["config", "cfg"], &["view", "reload"],
+		&["all"]),
+	(&["stats"], &["view"],
+		&["all", "requests", "responses", "chain", "multicast"]),
+	(&["status"], &["view"],
+		&["all", "services", "databases", "errors"]),
+];
+
+/// Words that may come before the zone
+const DIRECTIVES : &'static [&'static str] = &["--json", "format", "batch"];
+
+trait Conn : Read + Write { }
+
+impl<T: Read + Write> Conn for T { }
+
+enum Target {
+	Local(String),
+	Remote {
+		address: String,
+		ca: Option<String>,
+		cert: Option<(String,String)>,
+		token: Option<String>,
+	},
+}
+
+struct Client {
+	target: Target,
+	stream: Option<Box<Conn>>,
+}
+
+impl Client {
+	fn new(target: Target) -> Client {
+		Client {
+			target: target,
+			stream: None,
+		}
+	}
+
+	fn open(&self) -> Result<Box<Conn>,String> {
+		match self.target {
+			Target::Local(ref socket) =>
+				UnixStream::connect(socket)
+					.map(|s| Box::new(s) as Box<Conn>)
+					.map_err(|e| format!("unable to connect to {} ({})", socket, e)),
+
+			Target::Remote { ref address, ref ca, ref cert, .. } => {
+				let mut builder = try!(SslConnectorBuilder::new(SslMethod::tls()).map_err(|e| format!("unable to set up TLS ({})", e)));
+
+				{
+					let ctx = builder.builder_mut();
+					if let Some(ref ca) = *ca {
+						try!(ctx.set_ca_file(ca).map_err(|e| format!("unable to read {} ({})", ca, e)));
+					}
+					if let Some((ref cert, ref key)) = *cert {
+						try!(ctx.set_certificate_file(cert, X509_FILETYPE_PEM).map_err(|e| format!("unable to read {} ({})", cert, e)));
+						try!(ctx.set_private_key_file(key, X509_FILETYPE_PEM).map_err(|e| format!("unable to read {} ({})", key, e)));
+					}
+				}
+
+				let host = address.rsplitn(2, ':').last().unwrap_or(address);
+				let tcp = try!(TcpStream::connect(address.as_str()).map_err(|e| format!("unable to connect to {} ({})", address, e)));
+
+				builder.build().connect(host, tcp)
+					.map(|s| Box::new(s) as Box<Conn>)
+					.map_err(|e| format!("TLS handshake with {} failed ({:?})", address, e))
+			}
+		}
+	}
+
+	fn connect(&self) -> Result<Box<Conn>,String> {
+		let mut stream = try!(self.open());
+
+		try!(wire::write_hello(&mut stream, wire::VERSION).map_err(|e| format!("unable to open a session ({})", e)));
+		try!(wire::read_hello(&mut stream).map_err(|e| format!("the primary did not answer the hello ({:?})", e)));
+
+		if let Target::Remote { token: Some(ref token), .. } = self.target {
+			try!(wire::write_command(&mut stream, &format!("auth {}", token)).map_err(|e| format!("unable to authenticate ({})", e)));
+			match wire::read_reply(&mut stream) {
+				Ok((Status::Ok, _)) => { },
+				Ok((_, reply)) => return Err(reply.trim().trim_left_matches("Error: ").to_string()),
+				Err(e) => return Err(format!("unable to authenticate ({:?})", e)),
+			}
+		}
+
+		Ok(stream)
+	}
+
+	fn send(&mut self, command: &str) -> Result<(Status,String),String> {
+		let mut stream = match self.stream.take() {
+			Some(s) => s,
+			None => try!(self.connect())
+		};
+
+		try!(wire::write_command(&mut stream, command).map_err(|e| format!("unable to send command ({})", e)));
+		let reply = try!(wire::read_reply(&mut stream).map_err(|e| format!("unable to read reply ({:?})", e)));
+
+		// Kept for the next command unless the primary ended the session
+		match reply.0 {
+			Status::BadFrame | Status::Unsupported => { },
+			_ => self.stream = Some(stream)
+		}
+
+		Ok(reply)
+	}
+
+	/// Print the reply to a command, false if it failed
+	fn run(&mut self, command: &str) -> bool {
+		match self.send(command) {
+			Ok((status, reply)) => {
+				print!("{}", reply);
+				if !reply.ends_with("\n") { println!("") }
+				status == Status::Ok
+			},
+			Err(e) => {
+				println!("Error: {}", e);
+				false
+			}
+		}
+	}
+
+	fn script<R: BufRead>(&mut self, input: R) -> bool {
+		let mut ok = true;
+
+		for line in input.lines() {
+			let line = match line {
+				Ok(l) => l,
+				Err(e) => { println!("Error: unable to read script ({})", e); return false }
+			};
+
+			let command = line.trim();
+			if command.is_empty() || command.starts_with("#") { continue }
+
+			ok = self.run(command) && ok;
+		}
+
+		ok
+	}
+
+	fn shell(&mut self) {
+		let mut rl = Editor::<ZoneCompleter>::new();
+		rl.set_completer(Some(ZoneCompleter));
+
+		let history = env::var("HOME").map(|h| format!("{}/{}", h, HISTORY_FILE)).unwrap_or(HISTORY_FILE.to_string());
+		let _ = rl.load_history(&history);
+
+		loop {
+			match rl.readline("springctl> ") {
+				Ok(line) => {
+					let command = line.trim();
+					if command.is_empty() { continue }
+					rl.add_history_entry(command);
+
+					match command {
+						"quit" | "exit" => break,
+						"help" => ZoneCompleter::help(),
+						_ => { self.run(command); }
+					}
+				},
+				Err(ReadlineError::Interrupted) => continue,
+				Err(ReadlineError::Eof) => break,
+				Err(e) => {
+					println!("Error: {:?}", e);
+					break
+				}
+			}
+		}
+
+		let _ = rl.save_history(&history);
+	}
+}
+
+struct ZoneCompleter;
+
+impl ZoneCompleter {
+	fn zone(name: &str) -> Option<&'static (&'static [&'static str], &'static [&'static str], &'static [&'static str])> {
+		ZONES.iter().find(|z| z.0.iter().any(|n| *n == name))
+	}
+
+	/// Candidates for the next word given the words before it
+	fn candidates(words: &[&str]) -> Vec<&'static str> {
+		let words : Vec<&str> = match words.first() {
+			Some(&"--json") => words[1..].to_vec(),
+			Some(&"format") if words.len() == 1 => return vec!["json"],
+			Some(&"format") => words[2..].to_vec(),
+			_ => words.to_vec()
+		};
+
+		let words : Vec<&str> = match words.first() {
+			Some(&"batch") if words.len() == 1 => return ZONES.iter().map(|z| z.0[0]).chain(Some("dryrun")).collect(),
+			Some(&"batch") if words[1] == "dryrun" => words[2..].to_vec(),
+			Some(&"batch") => words[1..].to_vec(),
+			_ => words.clone()
+		};
+
+		match words.len() {
+			0 => ZONES.iter().map(|z| z.0[0]).chain(DIRECTIVES.iter().cloned()).collect(),
+			1 => Self::zone(words[0]).map_or(Vec::new(), |z| z.1.to_vec()),
+			_ => Self::zone(words[0]).map_or(Vec::new(), |z| z.2.to_vec()),
+		}
+	}
+
+	fn help() {
+		println!("Commands are <zone> <action> [operands]; prefix with --json for JSON output");
+		for z in ZONES {
+			println!("  {:<14} {}", z.0.join("|"), z.1.join(", "));
+		}
+		println!("  batch [dryrun] <command>; <command>; ...   all or nothing in one transaction");
+		println!("  quit");
+	}
+}
+
+impl Completer for ZoneCompleter {
+	fn complete(&self, line: &str, pos: usize) -> rustyline::Result<(usize, Vec<String>)> {
+		let line = &line[..pos];
+		let start = line.rfind(' ').map_or(0, |i| i + 1);
+		// Each command of a batch is completed on its own
+		let words : Vec<&str> = match line[..start].rfind(';') {
+			Some(i) => line[i+1..start].split_whitespace().collect(),
+			None => line[..start].split_whitespace().collect(),
+		};
+		let partial = &line[start..];
+
+		Ok((start, Self::candidates(&words).into_iter()
+								.filter(|c| c.starts_with(partial))
+								.map(|c| c.to_string())
+								.collect()))
+	}
+}
+
+/// Environment variable a remote token may be given in
+const TOKEN_VAR : &'static str = "SPRINGCTL_TOKEN";
+
+fn usage() -> ! {
+	println!("Usage: springctl [--socket <path> | --remote <host:port> [--ca <pem>] [--cert <pem> --key <pem> | --token-file <path>]]");
+	println!("                 [-f <file> | <command>]");
+	process::exit(2)
+}
+
+/// The first line of a token file
+fn read_token(path: &str) -> String {
+	let mut s = String::new();
+	match File::open(path).and_then(|mut f| f.read_to_string(&mut s)) {
+		Ok(_) => s.lines().next().unwrap_or("").trim().to_string(),
+		Err(e) => {
+			println!("Error: unable to read token from {} ({})", path, e);
+			process::exit(2)
+		}
+	}
+}
+
+fn main() {
+	let mut socket = MANAGEMENT_SOCKET.to_string();
+	let mut remote = None;
+	let mut ca = None;
+	let mut cert = None;
+	let mut key = None;
+	let mut token = env::var(TOKEN_VAR).ok();
+	let mut script = None;
+	let mut command = Vec::new();
+
+	let mut args = env::args().skip(1);
+	while let Some(a) = args.next() {
+		match a.as_ref() {
+			"--socket" => socket = args.next().unwrap_or_else(|| usage()),
+			"--remote" => remote = Some(args.next().unwrap_or_else(|| usage())),
+			"--ca" => ca = Some(args.next().unwrap_or_else(|| usage())),
+			"--cert" => cert = Some(args.next().unwrap_or_else(|| usage())),
+			"--key" => key = Some(args.next().unwrap_or_else(|| usage())),
+			"--token-file" => token = Some(read_token(&args.next().unwrap_or_else(|| usage()))),
+			"-f" => script = Some(args.next().unwrap_or_else(|| usage())),
+			"-h" | "--help" => usage(),
+			_ => { command.push(a.clone()); command.extend(args.by_ref()); }
+		}
+	}
+
+	let target = match remote {
+		Some(address) => Target::Remote {
+			address: address,
+			ca: ca,
+			cert: match (cert, key) {
+				(Some(c), Some(k)) => Some((c, k)),
+				(None, None) => None,
+				_ => usage()
+			},
+			token: token,
+		},
+		None => Target::Local(socket)
+	};
+
+	let mut client = Client::new(target);
+
+	let ok = match (script, command.is_empty()) {
+		(Some(_), false) => usage(),
+		(Some(ref f), true) if f == "-" => {
+			let stdin = io::stdin();
+			let ok = client.script(stdin.lock());
+			ok
+		},
+		(Some(f), true) => match File::open(&f) {
+			Ok(file) => client.script(BufReader::new(file)),
+			Err(e) => { println!("Error: unable to open {} ({})", f, e); false }
+		},
+		(None, false) => client.run(&command.join(" ")),
+		(None, true) => { client.shell(); true }
+	};
+
+	process::exit(match ok { true => 0, false => 1 })
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn ts_springctl_complete_zone_p() {
+		let (start, c) = ZoneCompleter.complete("ne", 2).unwrap();
+		assert_eq!(start, 0);
+		assert_eq!(c, vec!["network".to_string()]);
+	}
+
+	#[test]
+	fn ts_springctl_complete_operand_p() {
+		let (start, c) = ZoneCompleter.complete("--json acl add de", 17).unwrap();
+		assert_eq!(start, 15);
+		assert_eq!(c, vec!["deny".to_string()]);
+	}
+
+	#[test]
+	fn ts_springctl_complete_batch_p() {
+		let line = "batch dryrun network remove node foo; val";
+		let (start, c) = ZoneCompleter.complete(line, line.len()).unwrap();
+		assert_eq!(start, 38);
+		assert_eq!(c, vec!["validation".to_string()]);
+	}
+
+	#[test]
+	fn ts_springctl_complete_f() {
+		let (_, c) = ZoneCompleter.complete("bogus vi", 8).unwrap();
+		assert!(c.is_empty());
+	}
+}
@@ -1,12 +1,16 @@
 use std::net::UdpSocket;
 use std::io::{ErrorKind};
-use std::time::Duration;
+use std::time::{Duration,Instant};
 
 use spring_dvs::enums::{NodeService};
 use spring_dvs::node::Node;
-use spring_dvs::protocol::Port;
+use spring_dvs::protocol::{Port,ProtocolObject,Message};
 pub use network::NetworkFailure;
 
+use protocol::is_truncated;
+use service::Tcp;
+use metrics;
+
 pub trait Chain {
 	fn request(&self, bytes: &Vec<u8>, target: &Node) -> Result<Vec<u8>, NetworkFailure> ;
 }
@@ -19,6 +23,7 @@ pub struct ChainService;
 impl ChainService {
 	fn dvsp(&self, bytes: &Vec<u8>, target: &Node) -> Result<Vec<u8>, NetworkFailure> {
 		let address = format!("{}:{}", target.address(), Port::Dvsp);
+		log_debug!("chain", "Requesting {} at {}", target.springname(), address);
 		
 		let socket = match UdpSocket::bind("0.0.0.0:0") {
 				Ok(s) => s,
@@ -35,29 +40,58 @@ impl ChainService {
 			_ => return Err(NetworkFailure::SocketWrite),
 		}
 		
-		let mut buf = [0;768];
+		let mut buf = [0;4096];
 		let (sz, _) = match socket.recv_from(&mut buf) {
 			Ok(t) => t,
 			Err(e) => {
 				match e.kind() { 
-					ErrorKind::TimedOut => return Err(NetworkFailure::TimedOut),
+					ErrorKind::TimedOut => {
+						log_warn!("chain", "{} timed out", target.springname());
+						return Err(NetworkFailure::TimedOut)
+					},
 					_ => return Err(NetworkFailure::SocketRead) 
 				}
 			} 
 
 		};
 		
-		Ok(Vec::from(&buf[0..sz]))		
+		// Too large for a datagram; the target wants us on the stream
+		match Message::from_bytes(&buf[0..sz]) {
+			Ok(ref m) if is_truncated(m) => {
+				log_debug!("chain", "{} referred us to the stream", target.springname());
+				self.stream(bytes, target)
+			},
+			_ => Ok(Vec::from(&buf[0..sz]))
+		}
+	}
+	
+	fn stream(&self, bytes: &Vec<u8>, target: &Node) -> Result<Vec<u8>, NetworkFailure> {
+		let msg = match Message::from_bytes(bytes) {
+			Ok(m) => m,
+			Err(_) => return Err(NetworkFailure::SocketWrite)
+		};
+		
+		match Tcp::make_request(&msg, &target.address(), target.hostname(), NodeService::Dvsp) {
+			Ok(m) => Ok(m.to_bytes()),
+			Err(_) => Err(NetworkFailure::SocketRead)
+		}
 	}
 }
 
 impl Chain for ChainService {
 	fn request(&self, bytes: &Vec<u8>, target: &Node) -> Result<Vec<u8>, NetworkFailure> {
 		// ToDo: Handle HTTP service layers
-		match target.service() {
+		let start = Instant::now();
+		let result = match target.service() {
 			NodeService::Dvsp => self.dvsp(bytes,target),
 			_ => Err(NetworkFailure::UnsupportedAction)
-		}
+		};
+		
+		let outcome = match result { Ok(_) => "ok", Err(_) => "failed" };
+		metrics::count("dvsp_chain_requests_total", &[("target", target.springname()), ("result", outcome)]);
+		metrics::observe("dvsp_chain_seconds", &[("target", target.springname())], metrics::since(start));
+		
+		result
 	}
 }
 
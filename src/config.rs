@@ -1,6 +1,21 @@
 use std::io::prelude::*;
-use std::collections::HashMap;
+use std::collections::{HashMap,BTreeSet};
 use std::fs::{File};
+use std::sync::{Arc,RwLock};
+
+use logging;
+
+/// Keys a running primary cannot take on from a reload; they fix its
+/// place in the network and every peer and record that refers to it
+const FIXED_KEYS : &'static [&'static str] = &["springname", "geosub"];
+
+/// What is shown in place of a secret setting
+const HIDDEN : &'static str = "(hidden)";
+
+/// Keys holding tokens or key material are never shown back
+fn secret(key: &str) -> bool {
+	key.contains("token") || key.contains("key")
+}
 
 
 
@@ -10,6 +25,9 @@ pub trait NodeConfig {
 	fn geosub(&self) -> String;
 	fn address(&self) -> String;
 	fn uri(&self) -> String;
+	
+	/// Any other key set in node.conf, such as policy switches
+	fn setting(&self, key: &str) -> Option<String>;
 }
 
 #[derive(Clone)]
@@ -23,14 +41,55 @@ pub struct Config {
 impl Config {
 	#[allow(dead_code)]
 	pub fn new() -> Config {
+		Config::with_kvs(Config::load_kvs())
+	}
+	
+	pub fn with_kvs(kvs: HashMap<String,String>) -> Config {
 		Config {
-			node: Config::load_kvs(),
+			node: kvs,
 			live_test: false,
 			toggle_man: true,
 			toggle_offline: false,
 		}
 	}
 	
+	/// Every key in node.conf, in key order, with secrets hidden
+	pub fn settings(&self) -> Vec<(String,String)> {
+		let mut v : Vec<(String,String)> = self.node.iter().map(|(k, v)| (k.clone(), Config::shown(k, v))).collect();
+		v.sort();
+		v
+	}
+	
+	fn shown(key: &str, value: &str) -> String {
+		match secret(key) {
+			true => HIDDEN.to_string(),
+			false => value.to_string()
+		}
+	}
+	
+	/// Check that `next` can take over from this configuration,
+	/// describing each key that differs
+	pub fn reload_changes(&self, next: &Config) -> Result<Vec<String>,String> {
+		for key in FIXED_KEYS {
+			let (now, then) = (self.get_key(key), next.get_key(key));
+			
+			if now != then {
+				return Err(format!("{} cannot change from {} to {} without a restart", key, now, then))
+			}
+		}
+		
+		let keys : BTreeSet<&String> = self.node.keys().chain(next.node.keys()).collect();
+		
+		Ok(keys.into_iter().filter_map(|k| {
+			match (self.node.get(k), next.node.get(k)) {
+				(Some(a), Some(b)) if a != b => Some(format!("{}: {} -> {}", k, Config::shown(k, a), Config::shown(k, b))),
+				(Some(_), None) => Some(format!("{}: removed", k)),
+				(None, Some(b)) => Some(format!("{}: set to {}", k, Config::shown(k, b))),
+				_ => None
+			}
+		}).collect())
+	}
+	
 	#[allow(dead_code)]
 	fn load_kvs() -> HashMap<String,String> {
 		
@@ -86,16 +145,62 @@ impl NodeConfig for Config {
 	fn uri(&self) -> String {
 		format!("{}.{}.uk", self.springname(),self.geosub())
 	}
+	
+	fn setting(&self, key: &str) -> Option<String> {
+		self.node.get(key).cloned()
+	}
+}
+
+/// The configuration every thread reads through, so a reload
+/// reaches them all without a restart
+#[derive(Clone)]
+pub struct SharedConfig {
+	inner: Arc<RwLock<Config>>,
+}
+
+impl SharedConfig {
+	pub fn new(config: Config) -> SharedConfig {
+		SharedConfig {
+			inner: Arc::new(RwLock::new(config))
+		}
+	}
+	
+	/// A copy of the configuration as it stands; take one per request
+	pub fn current(&self) -> Config {
+		self.inner.read().unwrap().clone()
+	}
+	
+	/// Read node.conf again, keeping the command line toggles, and
+	/// pick up its logging settings
+	pub fn reload(&self) -> Result<Vec<String>,String> {
+		let mut next = self.current();
+		next.node = Config::load_kvs();
+		
+		let changes = try!(self.replace(next));
+		logging::reconfigure(&self.current());
+		Ok(changes)
+	}
+	
+	pub fn replace(&self, next: Config) -> Result<Vec<String>,String> {
+		let mut config = self.inner.write().unwrap();
+		let changes = try!(config.reload_changes(&next));
+		
+		*config = next;
+		Ok(changes)
+	}
 }
 
 #[cfg(test)]
 pub mod mocks {
+	use std::collections::HashMap;
+	
 	pub struct MockConfig {
 		spring: String,
 		host: String,
 		geosub: String,
 		address: String,
-		uri: String
+		uri: String,
+		settings: HashMap<String,String>,
 	}
 	
 	impl ::config::NodeConfig for MockConfig {
@@ -115,6 +220,9 @@ pub mod mocks {
 		fn uri(&self) -> String {
 			self.uri.clone()
 		}
+		fn setting(&self, key: &str) -> Option<String> {
+			self.settings.get(key).cloned()
+		}
 	}
 	
 	impl MockConfig {
@@ -125,8 +233,57 @@ pub mod mocks {
 				geosub: String::from("esusx"),
 				address: String::from("127.0.0.1"),
 				uri: String::from("foohub.esusx.uk"),
+				settings: HashMap::new(),
 			}
 		}
+		
+		pub fn with_setting(mut self, key: &str, value: &str) -> MockConfig {
+			self.settings.insert(key.to_string(), value.to_string());
+			self
+		}
 	}
 	
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	
+	fn config(pairs: &[(&str, &str)]) -> Config {
+		Config::with_kvs(pairs.iter().map(|&(k, v)| (k.to_string(), v.to_string())).collect())
+	}
+	
+	#[test]
+	fn ts_config_replace_p() {
+		let shared = SharedConfig::new(config(&[("springname", "foo"), ("geosub", "esusx"), ("hostname", "a.lan")]));
+		
+		let changes = shared.replace(config(&[("springname", "foo"), ("geosub", "esusx"), ("hostname", "b.lan"), ("registration", "moderated")])).unwrap();
+		assert_eq!(changes, vec!["hostname: a.lan -> b.lan".to_string(), "registration: set to moderated".to_string()]);
+		assert_eq!(shared.current().hostname(), "b.lan");
+	}
+	
+	#[test]
+	fn ts_config_replace_f() {
+		let shared = SharedConfig::new(config(&[("springname", "foo"), ("geosub", "esusx")]));
+		
+		assert!(shared.replace(config(&[("springname", "bar"), ("geosub", "esusx")])).is_err());
+		assert!(shared.replace(config(&[("springname", "foo")])).is_err());
+		assert_eq!(shared.current().springname(), "foo");
+	}
+	
+	#[test]
+	fn ts_config_secret_hidden_p() {
+		let shared = SharedConfig::new(config(&[("springname", "foo"), ("remote_man_token_admin", "s3cret")]));
+		assert!(shared.current().settings().contains(&("remote_man_token_admin".to_string(), HIDDEN.to_string())));
+		
+		let changes = shared.replace(config(&[("springname", "foo"), ("remote_man_token_admin", "n3w")])).unwrap();
+		assert_eq!(changes, vec!["remote_man_token_admin: (hidden) -> (hidden)".to_string()]);
+	}
+	
+	#[test]
+	fn ts_config_replace_unset_p() {
+		// A key that was never set may stay unset
+		let shared = SharedConfig::new(config(&[("springname", "foo")]));
+		assert!(shared.replace(config(&[("springname", "foo"), ("hostname", "b.lan")])).is_ok());
+	}
+}
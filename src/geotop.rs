@@ -0,0 +1,286 @@
+extern crate sqlite;
+
+use self::sqlite::{State,Value};
+
+use netspace::{NetspaceIo,Netspace,NetspaceFailure,StorageFailure,Success,step};
+use netspace::{Node,NodeRole,NodeService,NodeState};
+
+/*
+ * Geotop registry
+ *
+ * The roots of every geosub this primary knows of, with the
+ * priority they are tried in. Roots of our own geosub come from
+ * `netspace_add_self` and replication; roots of other geosubs are
+ * added by an operator or learnt from announcements.
+ */
+
+#[derive(Debug,Clone,PartialEq)]
+pub struct GeotopRoot {
+	pub geosub: String,
+	pub springname: String,
+	pub hostname: String,
+	pub address: String,
+	pub service: NodeService,
+	pub priority: i64,
+}
+
+impl GeotopRoot {
+	pub fn new(geosub: &str, springname: &str, hostname: &str, address: &str, service: NodeService, priority: i64) -> GeotopRoot {
+		GeotopRoot {
+			geosub: geosub.to_string(),
+			springname: springname.to_string(),
+			hostname: hostname.to_string(),
+			address: address.to_string(),
+			service: service,
+			priority: priority,
+		}
+	}
+
+	pub fn to_node(&self) -> Node {
+		Node::new(&self.springname, &self.hostname, &self.address, self.service, NodeState::Enabled, NodeRole::Hub, "")
+	}
+
+	/// Same root, reachable the same way
+	pub fn same_endpoint(&self, other: &GeotopRoot) -> bool {
+		self.springname == other.springname && self.hostname == other.hostname
+			&& self.address == other.address && self.service == other.service
+	}
+}
+
+impl NetspaceIo {
+
+	/// Roots of one geosub, or of every geosub, in priority order
+	pub fn gtn_roots(&self, geosub: Option<&str>) -> Result<Vec<GeotopRoot>,StorageFailure> {
+		let condition = match geosub {
+			Some(_) => "WHERE geosub = ?",
+			None => ""
+		};
+
+		let mut statement = try!(self.db().prepare(format!("SELECT geosub,springname,hostname,address,service,priority
+									FROM `geotop_netspace` {} ORDER BY geosub, priority ASC", condition)));
+
+		if let Some(g) = geosub {
+			try!(statement.bind(1, &Value::String( g.to_string() )));
+		}
+
+		let mut v = Vec::new();
+		while let State::Row = try!(step(&mut statement)) {
+			v.push(GeotopRoot {
+				geosub: try!(statement.read::<String>(0)),
+				springname: try!(statement.read::<String>(1)),
+				hostname: try!(statement.read::<String>(2)),
+				address: try!(statement.read::<String>(3)),
+				service: NodeService::from_i64(try!(statement.read::<i64>(4))),
+				priority: try!(statement.read::<i64>(5)),
+			});
+		}
+
+		Ok(v)
+	}
+
+	pub fn gtn_root(&self, springname: &str, geosub: &str) -> Result<Option<GeotopRoot>,StorageFailure> {
+		let roots = try!(self.gtn_roots(Some(geosub)));
+		Ok(roots.into_iter().find(|r| r.springname == springname))
+	}
+
+	pub fn gtn_geosub_update_priority(&self, springname: &str, geosub: &str, priority: i64) -> Result<Success,NetspaceFailure> {
+		let old = match self.gtn_root(springname, geosub) {
+			Ok(Some(r)) => r,
+			Ok(None) => return Err(NetspaceFailure::NodeNotFound),
+			Err(e) => return Err(e.to_netspace_failure())
+		};
+
+		self.audited(|| {
+			let mut statement = try!(self.db().prepare("UPDATE `geotop_netspace` SET priority = ? WHERE springname = ? AND geosub = ?"));
+			try!(statement.bind(1, &Value::Integer( priority )));
+			try!(statement.bind(2, &Value::String( springname.to_string() )));
+			try!(statement.bind(3, &Value::String( geosub.to_string() )));
+			try!(step(&mut statement));
+
+			self.audit_record("geotop_priority", springname,
+						&format!("geosub:{},priority:{}", geosub, old.priority),
+						&format!("geosub:{},priority:{}", geosub, priority))
+		})
+	}
+
+	/// Add a root, or replace it if it has moved; its priority is kept
+	pub fn gtn_root_store(&self, root: &GeotopRoot) -> Result<Success,NetspaceFailure> {
+		let existing = match self.gtn_root(&root.springname, &root.geosub) {
+			Ok(r) => r,
+			Err(e) => return Err(e.to_netspace_failure())
+		};
+
+		if let Some(ref old) = existing {
+			if old.same_endpoint(root) { return Ok(Success::Ok) }
+		}
+
+		// A moved root is never left half replaced
+		let transaction = match self.transaction() {
+			Ok(t) => t,
+			Err(e) => return Err(e.to_netspace_failure())
+		};
+
+		let priority = match existing {
+			Some(ref old) => {
+				try!(self.gtn_geosub_unregister_node(&old.to_node(), &old.geosub));
+				old.priority
+			},
+			None => root.priority
+		};
+
+		try!(self.gtn_geosub_register_node(&root.to_node(), &root.geosub));
+
+		if priority != 1 {
+			try!(self.gtn_geosub_update_priority(&root.springname, &root.geosub, priority));
+		}
+
+		match transaction.commit() {
+			Ok(_) => Ok(Success::Ok),
+			Err(e) => Err(e.to_netspace_failure())
+		}
+	}
+
+	/// Priority after every root a geosub has now, for roots we have
+	/// only been told about
+	fn gtn_root_last_priority(&self, geosub: &str) -> Result<i64,StorageFailure> {
+		let roots = try!(self.gtn_roots(Some(geosub)));
+		Ok(roots.iter().map(|r| r.priority).max().unwrap_or(0) + 1)
+	}
+
+	/// Store a root we have only been told about. It is tried after
+	/// every root already known so it can never displace one.
+	fn gtn_root_store_untrusted(&self, root: &GeotopRoot) -> Result<Success,NetspaceFailure> {
+		let mut root = root.clone();
+		root.priority = match self.gtn_root_last_priority(&root.geosub) {
+			Ok(p) => p,
+			Err(e) => return Err(e.to_netspace_failure())
+		};
+
+		self.gtn_root_store(&root)
+	}
+
+	/// Store a root that announced itself. Announcements are not
+	/// authenticated, so a known root is only updated by an announce
+	/// from the address it is registered at and a new one goes last.
+	pub fn gtn_root_announce(&self, root: &GeotopRoot) -> Result<Success,NetspaceFailure> {
+		match self.gtn_root(&root.springname, &root.geosub) {
+			Ok(Some(ref old)) if old.address != root.address => Err(NetspaceFailure::DuplicateNode),
+			Ok(Some(_)) => self.gtn_root_store(root),
+			Ok(None) => self.gtn_root_store_untrusted(root),
+			Err(e) => Err(e.to_netspace_failure())
+		}
+	}
+
+	/// Take in roots learnt from another primary; roots of our own geosub
+	/// and roots already known are left alone, and new ones go after the
+	/// roots we have whatever priority the peer gave them. Returns how
+	/// many were new.
+	pub fn gtn_roots_learn(&self, roots: &Vec<GeotopRoot>, own_geosub: &str) -> Result<usize,NetspaceFailure> {
+		let mut learnt = 0;
+		for root in roots {
+			if root.geosub == own_geosub { continue }
+
+			match self.gtn_root(&root.springname, &root.geosub) {
+				Ok(Some(_)) => continue,
+				Ok(None) => { },
+				Err(e) => return Err(e.to_netspace_failure())
+			}
+
+			try!(self.gtn_root_store_untrusted(root));
+			learnt += 1;
+		}
+
+		Ok(learnt)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use netspace::*;
+
+	fn root(geosub: &str, spring: &str, address: &str) -> GeotopRoot {
+		GeotopRoot::new(geosub, spring, "greenman", address, NodeService::Dvsp, 1)
+	}
+
+	#[test]
+	fn ts_geotop_store_priority_p() {
+		let nsio = NetspaceIo::new(":memory:");
+		let mut r = root("wsusx", "springa", "192.168.1.2");
+		r.priority = 3;
+
+		nsio.gtn_root_store(&r).unwrap();
+		assert_eq!(nsio.gtn_root("springa", "wsusx").unwrap(), Some(r.clone()));
+
+		// A moved root keeps its priority
+		r.address = "192.168.1.9".to_string();
+		r.priority = 1;
+		nsio.gtn_root_store(&r).unwrap();
+
+		let stored = nsio.gtn_root("springa", "wsusx").unwrap().unwrap();
+		assert_eq!(stored.address, "192.168.1.9");
+		assert_eq!(stored.priority, 3);
+	}
+
+	#[test]
+	fn ts_geotop_announce_p() {
+		let nsio = NetspaceIo::new(":memory:");
+		nsio.gtn_root_announce(&root("wsusx", "springa", "192.168.1.2")).unwrap();
+
+		// The registered address may change how the root is reached
+		let mut r = root("wsusx", "springa", "192.168.1.2");
+		r.hostname = "bluewoman".to_string();
+		nsio.gtn_root_announce(&r).unwrap();
+		assert_eq!(nsio.gtn_root("springa", "wsusx").unwrap().unwrap().hostname, "bluewoman");
+	}
+
+	#[test]
+	fn ts_geotop_announce_f() {
+		let nsio = NetspaceIo::new(":memory:");
+		nsio.gtn_root_announce(&root("wsusx", "springa", "192.168.1.2")).unwrap();
+
+		assert_eq!(nsio.gtn_root_announce(&root("wsusx", "springa", "192.168.1.66")), Err(NetspaceFailure::DuplicateNode));
+		assert_eq!(nsio.gtn_root("springa", "wsusx").unwrap().unwrap().address, "192.168.1.2");
+	}
+
+	#[test]
+	fn ts_geotop_announce_new_last_p() {
+		let nsio = NetspaceIo::new(":memory:");
+		nsio.gtn_root_store(&root("wsusx", "springa", "192.168.1.2")).unwrap();
+
+		// A stranger asking for the top spot is put after the known root
+		nsio.gtn_root_announce(&root("wsusx", "springx", "192.168.1.66")).unwrap();
+
+		let roots = nsio.gtn_roots(Some("wsusx")).unwrap();
+		assert_eq!(roots[0].springname, "springa");
+		assert_eq!(roots[1].springname, "springx");
+		assert_eq!(roots[1].priority, 2);
+	}
+
+	#[test]
+	fn ts_geotop_learn_p() {
+		let nsio = NetspaceIo::new(":memory:");
+		nsio.gtn_root_store(&root("wsusx", "springa", "192.168.1.2")).unwrap();
+
+		let learnt = nsio.gtn_roots_learn(&vec![
+			root("wsusx", "springa", "192.168.1.5"),
+			root("wsusx", "springd", "192.168.1.6"),
+			root("kent", "springb", "192.168.1.3"),
+			root("esusx", "springc", "192.168.1.4"),
+		], "esusx").unwrap();
+
+		assert_eq!(learnt, 2);
+		assert_eq!(nsio.gtn_roots(None).unwrap().len(), 3);
+		assert_eq!(nsio.gtn_root("springd", "wsusx").unwrap().unwrap().priority, 2);
+		assert_eq!(nsio.gtn_root("springa", "wsusx").unwrap().unwrap().address, "192.168.1.2");
+	}
+
+	#[test]
+	fn ts_geotop_update_priority_f() {
+		let nsio = NetspaceIo::new(":memory:");
+		assert!(match nsio.gtn_geosub_update_priority("void", "wsusx", 2) {
+			Err(NetspaceFailure::NodeNotFound) => true,
+			_ => false
+		});
+	}
+}
@@ -0,0 +1,23 @@
+/*
+ * Process wide state
+ *
+ * Registries every thread shares for the life of the process, such
+ * as the metrics and the recent errors, are created on first use
+ * and never freed. `process_global!` writes the accessor for one.
+ */
+
+/// `process_global!(name, Type, init)` defines `fn name() -> &'static Type`,
+/// creating the value from `init` the first time it is called
+macro_rules! process_global {
+	($name: ident, $t: ty, $init: expr) => (
+		fn $name() -> &'static $t {
+			static INIT : ::std::sync::Once = ::std::sync::ONCE_INIT;
+			static mut VALUE : *const $t = 0 as *const $t;
+
+			unsafe {
+				INIT.call_once(|| VALUE = Box::into_raw(Box::new($init)));
+				&*VALUE
+			}
+		}
+	)
+}
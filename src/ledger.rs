@@ -0,0 +1,205 @@
+extern crate sqlite;
+
+use std::sync::{Condvar,Mutex};
+use std::time::Duration;
+
+use self::sqlite::{State,Value};
+
+use netspace::{NetspaceIo,StorageFailure,step};
+
+/*
+ * Replication ledger
+ *
+ * Every change to a geosub node is stamped here so that primaries
+ * rooting the same geosub can work out whose copy of a node is
+ * the most recent. Stamps are milliseconds since the epoch and
+ * an unregistered node keeps its row as a tombstone.
+ *
+ * Local changes are recorded with an empty origin; readers are
+ * given the springname of this primary to stand in for it.
+ *
+ * Committing a local change, or hearing a peer has one, bumps a
+ * process wide counter that wakes the replicator straight away.
+ */
+
+static NOW_MS : &'static str = "CAST((julianday('now') - 2440587.5) * 86400000 AS INTEGER)";
+
+process_global!(changes, (Mutex<u64>,Condvar), (Mutex::new(0), Condvar::new()));
+
+/// There is something new to replicate
+pub fn notify() {
+	let &(ref count, ref cvar) = changes();
+	if let Ok(mut c) = count.lock() {
+		*c += 1;
+		cvar.notify_all();
+	}
+}
+
+/// Changes notified so far, to hand to `wait`
+pub fn seen() -> u64 {
+	match changes().0.lock() {
+		Ok(c) => *c,
+		Err(_) => 0
+	}
+}
+
+/// Block until there are changes beyond `seen` or `timeout` passes,
+/// returning the count to wait from next time
+pub fn wait(seen: u64, timeout: Duration) -> u64 {
+	let &(ref count, ref cvar) = changes();
+	let c = match count.lock() {
+		Ok(c) => c,
+		Err(_) => return seen
+	};
+
+	if *c != seen { return *c }
+
+	match cvar.wait_timeout(c, timeout) {
+		Ok((c, _)) => *c,
+		Err(_) => seen
+	}
+}
+
+#[derive(Debug,Clone,PartialEq)]
+pub struct LedgerEntry {
+	pub springname: String,
+	pub stamp: i64,
+	pub origin: String,
+	pub deleted: bool,
+}
+
+impl LedgerEntry {
+	pub fn new(springname: &str, stamp: i64, origin: &str, deleted: bool) -> LedgerEntry {
+		LedgerEntry {
+			springname: springname.to_string(),
+			stamp: stamp,
+			origin: origin.to_string(),
+			deleted: deleted,
+		}
+	}
+
+	/// Last writer wins; equal stamps are settled on the origin
+	/// so every primary picks the same winner
+	pub fn supersedes(&self, other: &LedgerEntry) -> bool {
+		(self.stamp, &self.origin) > (other.stamp, &other.origin)
+	}
+}
+
+impl NetspaceIo {
+
+	/// Stamp a local change to a node
+	pub fn ledger_touch(&self, springname: &str, deleted: bool) -> Result<(),StorageFailure> {
+		// Never step backwards, even if the clock does
+		let query = format!("INSERT OR REPLACE INTO `netspace_ledger` (springname,stamp,origin,deleted)
+							VALUES (?1, MAX({}, COALESCE((SELECT stamp FROM `netspace_ledger` WHERE springname = ?1) + 1, 0)), '', ?2)", NOW_MS);
+
+		let mut statement = try!(self.db().prepare(query));
+		try!(statement.bind(1, &Value::String( springname.to_string() )));
+		try!(statement.bind(2, &Value::Integer( deleted as i64 )));
+		try!(step(&mut statement));
+		self.stamped();
+		Ok(())
+	}
+
+	/// Record a change that came from another primary
+	pub fn ledger_set(&self, entry: &LedgerEntry) -> Result<(),StorageFailure> {
+		let mut statement = try!(self.db().prepare("INSERT OR REPLACE INTO `netspace_ledger`
+									(springname,stamp,origin,deleted) VALUES (?,?,?,?)"));
+
+		try!(statement.bind(1, &Value::String( entry.springname.clone() )));
+		try!(statement.bind(2, &Value::Integer( entry.stamp )));
+		try!(statement.bind(3, &Value::String( entry.origin.clone() )));
+		try!(statement.bind(4, &Value::Integer( entry.deleted as i64 )));
+		try!(step(&mut statement));
+		Ok(())
+	}
+
+	pub fn ledger_entry(&self, springname: &str, local: &str) -> Result<Option<LedgerEntry>,StorageFailure> {
+		let mut v = try!(self.ledger_query("WHERE springname = ?", &[Value::String( springname.to_string() )], local));
+		Ok(v.pop())
+	}
+
+	pub fn ledger_entries(&self, local: &str) -> Result<Vec<LedgerEntry>,StorageFailure> {
+		self.ledger_query("", &[], local)
+	}
+
+	/// Changes made on this primary after `stamp`
+	pub fn ledger_local_since(&self, stamp: i64, local: &str) -> Result<Vec<LedgerEntry>,StorageFailure> {
+		self.ledger_query("WHERE origin = '' AND stamp > ?", &[Value::Integer(stamp)], local)
+	}
+
+	fn ledger_query(&self, condition: &str, values: &[Value], local: &str) -> Result<Vec<LedgerEntry>,StorageFailure> {
+		let mut statement = try!(self.db().prepare(format!("SELECT springname,stamp,origin,deleted
+									FROM `netspace_ledger` {} ORDER BY stamp ASC", condition)));
+
+		for (i, value) in values.iter().enumerate() {
+			try!(statement.bind(i+1, value));
+		}
+
+		let mut v = Vec::new();
+		while let State::Row = try!(step(&mut statement)) {
+			let origin = try!(statement.read::<String>(2));
+			v.push(LedgerEntry {
+				springname: try!(statement.read::<String>(0)),
+				stamp: try!(statement.read::<i64>(1)),
+				origin: if origin.is_empty() { local.to_string() } else { origin },
+				deleted: try!(statement.read::<i64>(3)) != 0,
+			});
+		}
+
+		Ok(v)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use netspace::*;
+	use std::time::Duration;
+
+	#[test]
+	fn ts_ledger_register_unregister_p() {
+		let nsio = NetspaceIo::new(":memory:");
+		let node = Node::from_str("spring:foo,host:bar,address:192.168.1.2").unwrap();
+
+		nsio.gsn_node_register(&node).unwrap();
+		let first = nsio.ledger_entry("foo", "self").unwrap().unwrap();
+		assert_eq!(first.origin, "self");
+		assert_eq!(first.deleted, false);
+
+		nsio.gsn_node_unregister(&node).unwrap();
+		let second = nsio.ledger_entry("foo", "self").unwrap().unwrap();
+		assert!(second.deleted);
+		assert!(second.supersedes(&first));
+	}
+
+	#[test]
+	fn ts_ledger_remote_entry_p() {
+		let nsio = NetspaceIo::new(":memory:");
+		nsio.ledger_set(&LedgerEntry::new("foo", 1000, "peer", false)).unwrap();
+
+		assert_eq!(nsio.ledger_local_since(0, "self").unwrap().len(), 0);
+		assert_eq!(nsio.ledger_entries("self").unwrap()[0].origin, "peer");
+	}
+
+	#[test]
+	fn ts_ledger_notify_on_commit_p() {
+		let nsio = NetspaceIo::new(":memory:");
+		let before = seen();
+
+		let t = nsio.transaction().unwrap();
+		nsio.gsn_node_register(&Node::from_str("spring:foo,host:bar,address:192.168.1.2").unwrap()).unwrap();
+
+		t.commit().unwrap();
+		assert!(wait(before, Duration::from_millis(1)) > before);
+	}
+
+	#[test]
+	fn ts_ledger_supersedes_tie_p() {
+		let a = LedgerEntry::new("foo", 1000, "alpha", false);
+		let b = LedgerEntry::new("foo", 1000, "beta", true);
+		assert!(b.supersedes(&a));
+		assert!(!a.supersedes(&b));
+		assert!(!a.supersedes(&a));
+	}
+}
@@ -0,0 +1,142 @@
+extern crate libc;
+
+use std::sync::atomic::{AtomicBool,AtomicUsize,Ordering,ATOMIC_BOOL_INIT,ATOMIC_USIZE_INIT};
+use std::thread;
+use std::time::{Duration,Instant};
+
+use config::SharedConfig;
+
+/*
+ * Process lifecycle
+ *
+ * SIGTERM and SIGINT ask the primary to stop: services stop taking
+ * new work, whatever is in flight is given until a deadline to
+ * finish and then the process exits. SIGHUP reloads node.conf into
+ * the shared configuration. The handlers only flip atomics;
+ * everything else happens on the threads that poll them.
+ */
+
+static STOPPING : AtomicBool = ATOMIC_BOOL_INIT;
+static RELOADS : AtomicUsize = ATOMIC_USIZE_INIT;
+static IN_FLIGHT : AtomicUsize = ATOMIC_USIZE_INIT;
+
+/// How often the supervisor and draining check on things
+const POLL_MS : u64 = 200;
+
+extern "C" fn on_signal(signal: libc::c_int) {
+	match signal {
+		libc::SIGHUP => { RELOADS.fetch_add(1, Ordering::SeqCst); },
+		_ => STOPPING.store(true, Ordering::SeqCst),
+	}
+}
+
+pub fn install() {
+	unsafe {
+		libc::signal(libc::SIGTERM, on_signal as libc::sighandler_t);
+		libc::signal(libc::SIGINT, on_signal as libc::sighandler_t);
+		libc::signal(libc::SIGHUP, on_signal as libc::sighandler_t);
+	}
+}
+
+pub fn stopping() -> bool {
+	STOPPING.load(Ordering::SeqCst)
+}
+
+/// Block the calling thread until the primary is asked to stop,
+/// reloading `config` whenever there is a SIGHUP
+pub fn wait(config: &SharedConfig) {
+	let mut seen = RELOADS.load(Ordering::SeqCst);
+
+	while !stopping() {
+		thread::sleep(Duration::from_millis(POLL_MS));
+
+		let reloads = RELOADS.load(Ordering::SeqCst);
+		if reloads == seen { continue }
+		seen = reloads;
+
+		match config.reload() {
+			Ok(changes) => log_info!("system", "Configuration reloaded ({} change(s))", changes.len()),
+			Err(e) => log_error!("system", "Configuration reload rejected: {}", e),
+		}
+	}
+}
+
+/// Sleep for `duration` in short steps, false if the primary was
+/// asked to stop before it was up
+pub fn pause(duration: Duration) -> bool {
+	let start = Instant::now();
+
+	while start.elapsed() < duration {
+		if stopping() { return false }
+		thread::sleep(Duration::from_millis(POLL_MS));
+	}
+
+	!stopping()
+}
+
+/// Wait for in-flight work to finish, false if the deadline passed first
+pub fn drain(deadline: Duration) -> bool {
+	let start = Instant::now();
+
+	while IN_FLIGHT.load(Ordering::SeqCst) > 0 {
+		if start.elapsed() >= deadline { return false }
+		thread::sleep(Duration::from_millis(POLL_MS));
+	}
+
+	true
+}
+
+pub fn in_flight() -> usize {
+	IN_FLIGHT.load(Ordering::SeqCst)
+}
+
+/// A unit of work the primary waits on before exiting; counted
+/// from creation until it is dropped
+pub struct Work;
+
+impl Work {
+	/// Start a piece of new work, refused once the primary is stopping
+	pub fn begin() -> Option<Work> {
+		if stopping() { return None }
+		Some(Work::hold())
+	}
+
+	/// Work that is already under way, such as the threads of an
+	/// accepted request or a service loop closing its database
+	pub fn hold() -> Work {
+		IN_FLIGHT.fetch_add(1, Ordering::SeqCst);
+		Work
+	}
+}
+
+impl Drop for Work {
+	fn drop(&mut self) {
+		IN_FLIGHT.fetch_sub(1, Ordering::SeqCst);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn ts_lifecycle_work_drain_p() {
+		let work = Work::begin().unwrap();
+		assert!(in_flight() >= 1);
+		drop(work);
+		assert!(drain(Duration::from_millis(2000)));
+	}
+
+	#[test]
+	fn ts_lifecycle_pause_p() {
+		let start = Instant::now();
+		assert!(pause(Duration::from_millis(300)));
+		assert!(start.elapsed() >= Duration::from_millis(300));
+	}
+
+	#[test]
+	fn ts_lifecycle_drain_f() {
+		let _work = Work::hold();
+		assert!(!drain(Duration::from_millis(1)));
+	}
+}
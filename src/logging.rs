@@ -0,0 +1,253 @@
+use std::collections::{BTreeMap,HashMap};
+use std::fs::{File,OpenOptions};
+use std::io::prelude::*;
+use std::sync::{Arc,Mutex,RwLock};
+use std::time::{SystemTime,UNIX_EPOCH};
+
+use rustc_serialize::json::{self, ToJson, Json};
+
+use config::NodeConfig;
+use status;
+
+/*
+ * Leveled logging
+ *
+ * Every diagnostic goes through the `log_*!` macros with the
+ * subsystem it comes from. node.conf sets the level with
+ * `log_level=<level>` and per subsystem with
+ * `log_level_<target>=<level>`; `log_file=<path>` appends to a file
+ * instead of stdout and `log_format=json` writes one JSON object a
+ * line. `--verbose` and `--quiet` on the command line override the
+ * base level. Until `init` is called lines go to stdout at info.
+ */
+
+#[derive(Debug,Clone,Copy,PartialEq,Eq,PartialOrd,Ord)]
+pub enum Level {
+	Error,
+	Warn,
+	Info,
+	Debug,
+}
+
+impl Level {
+	pub fn from_str(s: &str) -> Option<Level> {
+		match s {
+			"error" => Some(Level::Error),
+			"warn" => Some(Level::Warn),
+			"info" => Some(Level::Info),
+			"debug" => Some(Level::Debug),
+			_ => None
+		}
+	}
+
+	fn name(&self) -> &'static str {
+		match *self {
+			Level::Error => "error",
+			Level::Warn => "warn",
+			Level::Info => "info",
+			Level::Debug => "debug",
+		}
+	}
+}
+
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub enum Format {
+	Text,
+	Json,
+}
+
+pub struct Logger {
+	level: Level,
+	targets: HashMap<String,Level>,
+	format: Format,
+	file: Option<Mutex<File>>,
+	/// Level forced from the command line, kept across reloads
+	forced: Option<Level>,
+}
+
+impl Logger {
+	pub fn new(level: Level) -> Logger {
+		Logger {
+			level: level,
+			targets: HashMap::new(),
+			format: Format::Text,
+			file: None,
+			forced: None,
+		}
+	}
+
+	pub fn from_config(config: &NodeConfig, forced: Option<Level>) -> Logger {
+		let mut logger = Logger::new(forced.unwrap_or(
+			config.setting("log_level").and_then(|l| Level::from_str(&l)).unwrap_or(Level::Info)
+		));
+		logger.forced = forced;
+
+		for target in TARGETS {
+			if let Some(l) = config.setting(&format!("log_level_{}", target)).and_then(|l| Level::from_str(&l)) {
+				logger.targets.insert(target.to_string(), l);
+			}
+		}
+
+		if let Some(ref f) = config.setting("log_format") {
+			if f == "json" { logger.format = Format::Json }
+		}
+
+		if let Some(path) = config.setting("log_file") {
+			match OpenOptions::new().create(true).append(true).open(&path) {
+				Ok(f) => logger.file = Some(Mutex::new(f)),
+				Err(e) => println!("[Error] [System] Unable to open log file {} ({}); logging to stdout", path, e),
+			}
+		}
+
+		logger
+	}
+
+	pub fn enabled(&self, level: Level, target: &str) -> bool {
+		// A forced level wins over anything node.conf says per target
+		let limit = match self.forced {
+			Some(l) => l,
+			None => *self.targets.get(target).unwrap_or(&self.level)
+		};
+
+		level <= limit
+	}
+
+	pub fn line(&self, level: Level, target: &str, msg: &str) -> String {
+		match self.format {
+			Format::Text => match level {
+				Level::Info => format!("[{}] {}", title(target), msg),
+				_ => format!("[{}] [{}] {}", title(level.name()), title(target), msg),
+			},
+			Format::Json => {
+				let stamp = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+
+				let mut d = BTreeMap::new();
+				d.insert("ts".to_string(), stamp.to_json());
+				d.insert("level".to_string(), level.name().to_json());
+				d.insert("target".to_string(), target.to_json());
+				d.insert("msg".to_string(), msg.to_json());
+				json::encode(&Json::Object(d)).unwrap()
+			}
+		}
+	}
+
+	pub fn write(&self, level: Level, target: &str, msg: &str) {
+		if !self.enabled(level, target) { return }
+
+		let line = self.line(level, target, msg);
+
+		match self.file {
+			Some(ref f) => match f.lock() {
+				Ok(mut f) => { let _ = writeln!(f, "{}", line); },
+				Err(_) => println!("{}", line),
+			},
+			None => println!("{}", line),
+		}
+	}
+}
+
+/// Subsystems that can be given their own level
+pub const TARGETS : &'static [&'static str] = &[
+	"system", "node", "service", "protocol", "resolution", "chain",
+	"management", "cert", "netspace", "database", "sync", "geotop", "throttle",
+];
+
+fn title(s: &str) -> String {
+	let mut c = s.chars();
+	match c.next() {
+		Some(f) => f.to_uppercase().chain(c).collect(),
+		None => String::new()
+	}
+}
+
+// A thread part way through a write holds its own reference, so
+// a reload never pulls the logger out from under it
+process_global!(logger, RwLock<Option<Arc<Logger>>>, RwLock::new(None));
+
+pub fn init(l: Logger) {
+	if let Ok(mut current) = logger().write() {
+		*current = Some(Arc::new(l));
+	}
+}
+
+/// Pick up a reloaded node.conf, keeping any command line level
+pub fn reconfigure(config: &NodeConfig) {
+	let forced = current().and_then(|l| l.forced);
+	init(Logger::from_config(config, forced));
+}
+
+fn current() -> Option<Arc<Logger>> {
+	match logger().read() {
+		Ok(l) => l.clone(),
+		Err(_) => None
+	}
+}
+
+pub fn log(level: Level, target: &str, msg: &str) {
+	// Kept for the status zone whatever the level says about writing it
+	if level == Level::Error { status::record_error(target, msg) }
+	
+	match current() {
+		Some(l) => l.write(level, target, msg),
+		None => if level <= Level::Info { Logger::new(Level::Info).write(level, target, msg) },
+	}
+}
+
+#[macro_export]
+macro_rules! log_error {
+	($target: expr, $($arg: tt)*) => ( $crate::logging::log($crate::logging::Level::Error, $target, &format!($($arg)*)) )
+}
+
+#[macro_export]
+macro_rules! log_warn {
+	($target: expr, $($arg: tt)*) => ( $crate::logging::log($crate::logging::Level::Warn, $target, &format!($($arg)*)) )
+}
+
+#[macro_export]
+macro_rules! log_info {
+	($target: expr, $($arg: tt)*) => ( $crate::logging::log($crate::logging::Level::Info, $target, &format!($($arg)*)) )
+}
+
+#[macro_export]
+macro_rules! log_debug {
+	($target: expr, $($arg: tt)*) => ( $crate::logging::log($crate::logging::Level::Debug, $target, &format!($($arg)*)) )
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use config::mocks::MockConfig;
+
+	#[test]
+	fn ts_logging_levels_p() {
+		let config = MockConfig::dflt().with_setting("log_level", "warn").with_setting("log_level_chain", "debug");
+		let logger = Logger::from_config(&config, None);
+
+		assert!(logger.enabled(Level::Error, "service"));
+		assert!(!logger.enabled(Level::Info, "service"));
+		assert!(logger.enabled(Level::Debug, "chain"));
+	}
+
+	#[test]
+	fn ts_logging_forced_p() {
+		let config = MockConfig::dflt().with_setting("log_level_chain", "debug");
+		let logger = Logger::from_config(&config, Some(Level::Error));
+
+		assert!(!logger.enabled(Level::Debug, "chain"));
+		assert!(!logger.enabled(Level::Warn, "service"));
+		assert!(logger.enabled(Level::Error, "service"));
+	}
+
+	#[test]
+	fn ts_logging_line_p() {
+		let mut logger = Logger::new(Level::Info);
+		assert_eq!(logger.line(Level::Info, "service", "Online"), "[Service] Online");
+		assert_eq!(logger.line(Level::Error, "chain", "Timed out"), "[Error] [Chain] Timed out");
+
+		logger.format = Format::Json;
+		let j = Json::from_str(&logger.line(Level::Warn, "protocol", "Odd")).unwrap();
+		assert_eq!(j.find("level").unwrap().as_string(), Some("warn"));
+		assert_eq!(j.find("target").unwrap().as_string(), Some("protocol"));
+		assert_eq!(j.find("msg").unwrap().as_string(), Some("Odd"));
+	}
+}
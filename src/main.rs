@@ -14,10 +14,26 @@ extern crate rustc_serialize;
 static SERVER_VERSION : &'static str = "0.7.0";
 
 use std::env;
+use std::time::Duration;
 
+#[macro_use]
+mod global;
+#[macro_use]
+mod logging;
 mod config;
+mod lifecycle;
 mod management;
 mod netspace;
+mod schema;
+mod audit;
+mod ledger;
+mod geotop;
+mod pending;
+mod throttle;
+mod acl;
+mod metrics;
+mod status;
+mod snapshot;
 mod protocol;
 mod network;
 mod chain;
@@ -31,10 +47,15 @@ mod unit_test_env;
 
 use config::{NodeConfig};
 
+/// Seconds in-flight requests are given to finish on shutdown
+const DRAIN_SECS : u64 = 10;
+
 fn main() {
 	
 	let mut config = config::Config::new();
 	config.live_test = false;
+	
+	let mut verbosity = None;
 
 	for a in env::args() {		
 		match a.as_ref() {
@@ -48,39 +69,90 @@ fn main() {
 								config.toggle_man = true;
 								config.toggle_offline = true;
 							},
+			"--verbose" => { verbosity = Some(logging::Level::Debug) },
+			"--quiet" => { verbosity = Some(logging::Level::Error) },
 			_ => { }
 		}
 	}
 
-    println!("SpringNet Primary Node v{}\n[Node] {}.{}.uk", SERVER_VERSION, config.springname(), config.geosub());
-    println!("[Node] {}/spring/", config.hostname());
+    logging::init(logging::Logger::from_config(&config, verbosity));
+    lifecycle::install();
+    status::mark_started();
+    
+    println!("SpringNet Primary Node v{}", SERVER_VERSION);
+    log_info!("node", "{}.{}.uk", config.springname(), config.geosub());
+    log_info!("node", "{}/spring/", config.hostname());
     
 	if config.toggle_offline {
-	    println!("[Alert] Server running in offline maintenance mode");
+	    log_warn!("system", "Server running in offline maintenance mode");
 	}
  
+    let shared = config::SharedConfig::new(config.clone());
+    
     if config.toggle_man {
-	    match service::Management::start(&config) {
+	    match service::Management::start(&shared) {
 	    	Ok(_) =>{  },
-	    	Err(_) => println!("[Error]"),
+	    	Err(e) => log_error!("management", "Management service failed to start ({:?})", e),
+	    }
+	    
+	    match service::RemoteManagement::start(&shared) {
+	    	Ok(_) =>{  },
+	    	Err(e) => log_error!("management", "Remote management failed to start ({:?})", e),
 	    }
     } else {
-    	println!("[System] Management Service Disabled");
+    	log_info!("system", "Management Service Disabled");
     }
     
     
-    // If we're in offline mode we'll wait for the management
-    // service to thread to end and the exit
-    if config.toggle_offline { return }
-
-	
-    match service::Dvsp::start(&config) {
-    	Ok(_) =>{  },
-    	Err(_) => println!("[Error]"),
+    // In offline mode only the management service runs
+    if !config.toggle_offline {
+	    match service::Dvsp::start(&shared) {
+	    	Ok(_) =>{  },
+	    	Err(e) => log_error!("service", "UDP service failed to start ({:?})", e),
+	    }
+	    
+	    match netservice::sync::replicator::Replicator::start(&shared) {
+	    	Ok(_) => {},
+	    	Err(e) => log_error!("sync", "Replication failed to start ({:?})", e),
+	    }
+	    
+	    match netservice::geotop::discovery::Discovery::start(&shared) {
+	    	Ok(_) => {},
+	    	Err(e) => log_error!("geotop", "Geotop discovery failed to start ({:?})", e),
+	    }
+	    
+	    match service::Tcp::start(&shared) {
+	    	Ok(_) => {},
+	    	Err(e) => log_error!("service", "TCP service failed to start ({:?})", e),
+	    }
+	    
+	    match service::MetricsHttp::start(&shared) {
+	    	Ok(_) => {},
+	    	Err(e) => log_error!("service", "Metrics exporter failed to start ({:?})", e),
+	    }
     }
     
-    match service::Tcp::start(&config) {
-    	Ok(_) => {},
-    	Err(_) => {println!("[Error]")},
+    lifecycle::wait(&shared);
+    log_info!("system", "Shutting down");
+    
+    // Listeners block in accept; poke them so they see we are stopping
+    if !config.toggle_offline {
+    	service::Tcp::wake();
+    	service::MetricsHttp::wake();
     }
+    if config.toggle_man {
+    	service::Management::wake();
+    	service::RemoteManagement::wake();
+    }
+    
+    let deadline = shared.current().setting("shutdown_deadline")
+    					.and_then(|s| s.parse::<u64>().ok())
+    					.unwrap_or(DRAIN_SECS);
+    
+    match lifecycle::drain(Duration::new(deadline, 0)) {
+    	true => log_info!("system", "Stopped"),
+    	false => log_warn!("system", "Stopped with {} request(s) still in flight", lifecycle::in_flight()),
+    }
+    
+    service::Management::cleanup();
 }
@@ -0,0 +1,262 @@
+use std::str::Split;
+
+use netspace::*;
+use acl::{AclRule,AclSubject};
+
+use management::{output,ZoneResult};
+
+use prettytable::Table;
+use prettytable::row::Row;
+use prettytable::cell::Cell;
+
+#[macro_export]
+macro_rules! extract_zone_acl {
+	($e: expr) => (
+		match $e {
+			ManagementZone::Acl(s) => s,
+			e => panic!("extract_zone_acl -- Unexpected value: {:?}", e)
+		}
+	)
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum AclAction {
+	View,
+	Add,
+	Remove,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum AclOperand {
+	None,
+	All,
+	Allow,
+	Deny,
+	Command(String),
+	Module(String),
+	Subject(AclSubject),
+	Id(i64),
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct AclZone {
+	action: AclAction,
+	ops: Vec<AclOperand>,
+}
+
+impl AclZone {
+	pub fn new(action: AclAction, ops: Vec<AclOperand>) -> AclZone {
+		AclZone {
+			action: action,
+			ops: ops,
+		}
+	}
+
+	pub fn action(&self) -> AclAction {
+		self.action
+	}
+
+	pub fn from_str(msg: &str) -> Option<AclZone> {
+		if msg.len() == 0 { return None; }
+
+		let mut atom = msg.split(" ");
+
+		let action = match atom.next() {
+			Some("view") => AclAction::View,
+			Some("add") => AclAction::Add,
+			Some("del") | Some("remove") => AclAction::Remove,
+			_ => return None,
+		};
+
+		let mut ops = Vec::new();
+		loop {
+			match cascade_none_nowrap!(AclZone::extract_operand(&mut atom)) {
+				AclOperand::None => break,
+				op => ops.push(op)
+			}
+		}
+
+		if ops.is_empty() { return None }
+
+		Some(AclZone::new(action, ops))
+	}
+
+	fn extract_operand(atom: &mut Split<&str>) -> Option<AclOperand> {
+
+		Some(match atom.next() {
+			Some("all") =>
+						AclOperand::All,
+
+			Some("allow") =>
+						AclOperand::Allow,
+
+			Some("deny") =>
+						AclOperand::Deny,
+
+			Some("command") =>
+						AclOperand::Command(
+							cascade_none_nowrap!(atom.next()).to_string()
+						),
+
+			Some("module") =>
+						AclOperand::Module(
+							cascade_none_nowrap!(atom.next()).to_string()
+						),
+
+			Some("any") =>
+						AclOperand::Subject(AclSubject::Any),
+
+			Some("node") =>
+						AclOperand::Subject(AclSubject::Node),
+
+			Some("role") =>
+						AclOperand::Subject(
+							AclSubject::Role(
+								cascade_none_nowrap!(
+									NodeRole::from_str(
+										cascade_none_nowrap!(atom.next())
+									)
+								)
+							)
+						),
+
+			Some("cidr") =>
+						AclOperand::Subject(
+							cascade_none_nowrap!(
+								AclSubject::cidr(
+									cascade_none_nowrap!(atom.next())
+								)
+							)
+						),
+
+			Some("id") =>
+						AclOperand::Id(
+							match cascade_none_nowrap!(atom.next()).parse() {
+								Ok(n) => n,
+								Err(_) => return None
+							}
+						),
+
+			_ => AclOperand::None,
+		})
+	}
+
+	pub fn process(az: AclZone, nio: &NetspaceIo) -> Option<ZoneResult> {
+		match az.action {
+			AclAction::View => AclZoneModel::view(nio),
+			AclAction::Add => AclZoneModel::add(az.ops, nio),
+			AclAction::Remove => AclZoneModel::remove(az.ops, nio),
+		}
+	}
+}
+
+struct AclZoneModel;
+
+impl AclZoneModel {
+	pub fn view(nio: &NetspaceIo) -> Option<ZoneResult> {
+		Some(match nio.acl_rules() {
+			Ok(v) => Ok(Self::tabulate_rules(&v)),
+			Err(e) => Err(format!("Error: unable to read access control rules ({:?})\n", e))
+		})
+	}
+
+	pub fn add(ops: Vec<AclOperand>, nio: &NetspaceIo) -> Option<ZoneResult> {
+		let mut allow = None;
+		let mut command = None;
+		let mut module = None;
+		let mut subject = None;
+
+		for op in ops {
+			match op {
+				AclOperand::Allow => allow = Some(true),
+				AclOperand::Deny => allow = Some(false),
+				AclOperand::Command(c) => command = Some(c),
+				AclOperand::Module(m) => module = Some(m),
+				AclOperand::Subject(s) => subject = Some(s),
+				_ => { }
+			}
+		}
+
+		let rule = match (allow, command, subject) {
+			(Some(a), Some(c), Some(s)) => AclRule::new(a, &c, module.as_ref().map(|m| m.as_str()), s),
+			_ => return Some(Err("Error: a rule needs allow or deny, a command and a subject\n".to_string()))
+		};
+
+		Some(match nio.acl_add(&rule) {
+			Ok(id) => Ok(format!("Added rule {}: {}\n", id, rule)),
+			Err(e) => Err(format!("Error: failed to add rule ({:?})\n", e))
+		})
+	}
+
+	pub fn remove(ops: Vec<AclOperand>, nio: &NetspaceIo) -> Option<ZoneResult> {
+		let id = match ops.iter().filter_map(|op| match *op { AclOperand::Id(n) => Some(n), _ => None }).next() {
+			Some(n) => n,
+			None => return Some(Err("Error: removing a rule needs its id\n".to_string()))
+		};
+
+		Some(match nio.acl_remove(id) {
+			Ok(true) => Ok(format!("Removed rule {}\n", id)),
+			Ok(false) => Err(format!("Error: no rule {}\n", id)),
+			Err(e) => Err(format!("Error: failed to remove rule ({:?})\n", e))
+		})
+	}
+
+	fn tabulate_rules(rules: &Vec<AclRule>) -> String {
+		let mut table = Table::new();
+		table.add_row(row!["_id_", "_policy_", "_command_", "_module_", "_subject_"]);
+
+		for rule in rules {
+			table.add_row(Row::new(vec![
+							Cell::new(&format!("{}", rule.id)),
+							Cell::new(match rule.allow { true => "allow", false => "deny" }),
+							Cell::new(&rule.command),
+							Cell::new(rule.module.as_ref().map_or("*", |m| m.as_str())),
+							Cell::new(&format!("{}", rule.subject))
+							]));
+		}
+
+		output::table(table)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use management::ManagementZone;
+
+	macro_rules! unwrap_some {
+		($chk:expr) => (
+			match $chk {
+						Some(s) => s,
+						_ => panic!("Unwrapping a None")
+			})
+	}
+
+	#[test]
+	fn ts_acl_add_p() {
+		let mz = unwrap_some!(ManagementZone::from_str("acl add deny command service module sync cidr 10.0.0.0/8"));
+		let az : AclZone = extract_zone_acl!(mz);
+		assert_eq!(az.action, AclAction::Add);
+		assert_eq!(az.ops, vec![
+			AclOperand::Deny,
+			AclOperand::Command("service".to_string()),
+			AclOperand::Module("sync".to_string()),
+			AclOperand::Subject(AclSubject::cidr("10.0.0.0/8").unwrap()),
+		]);
+	}
+
+	#[test]
+	fn ts_acl_remove_id_p() {
+		let mz = unwrap_some!(ManagementZone::from_str("acl del id 3"));
+		let az : AclZone = extract_zone_acl!(mz);
+		assert_eq!(az.action, AclAction::Remove);
+		assert_eq!(az.ops, vec![AclOperand::Id(3)]);
+	}
+
+	#[test]
+	fn ts_acl_add_f() {
+		assert_eq!(ManagementZone::from_str("acl add allow command info cidr 10.0.0.0/40"), None);
+		assert_eq!(ManagementZone::from_str("acl add allow command info role void"), None);
+		assert_eq!(ManagementZone::from_str("acl view"), None);
+	}
+}
@@ -0,0 +1,288 @@
+extern crate libc;
+
+use std::fmt;
+use std::mem;
+use std::os::unix::io::AsRawFd;
+
+use config::NodeConfig;
+
+use super::ManagementZone;
+use super::network::NetworkAction;
+use super::validation::ValidationAction;
+use super::snapshot::SnapshotAction;
+use super::geotop::GeotopAction;
+use super::registration::RegistrationAction;
+use super::throttle::ThrottleAction;
+use super::acl::AclAction;
+use super::config::ConfigAction;
+
+/*
+ * Management socket authorisation
+ *
+ * The kernel tells us the uid and gid of whoever connected to the
+ * socket. node.conf gives them a role with `man_admin`,
+ * `man_operator` and `man_viewer`, each a comma separated list of
+ * uids or `gid:<n>` entries; `man_default` is the role for anyone
+ * not listed and is unset, so refused, unless given. Root and the
+ * user the primary runs as are always admin.
+ *
+ * Remote callers on the TLS listener get a role from the common
+ * name of their client certificate, listed in `remote_man_cn_<role>`,
+ * or by sending `auth <token>` with a token set as
+ * `remote_man_token_<role>`.
+ */
+
+#[derive(Debug,Clone,Copy,PartialEq,Eq,PartialOrd,Ord)]
+pub enum Role {
+	/// Read only
+	Viewer,
+	/// Day to day changes to nodes, tokens and registrations
+	Operator,
+	/// Access control, configuration and restoring snapshots
+	Admin,
+}
+
+impl Role {
+	pub fn from_str(s: &str) -> Option<Role> {
+		match s {
+			"viewer" => Some(Role::Viewer),
+			"operator" => Some(Role::Operator),
+			"admin" => Some(Role::Admin),
+			_ => None
+		}
+	}
+
+	pub fn name(&self) -> &'static str {
+		match *self {
+			Role::Viewer => "viewer",
+			Role::Operator => "operator",
+			Role::Admin => "admin",
+		}
+	}
+
+	/// The role a request needs
+	pub fn required(request: &ManagementZone) -> Role {
+		match *request {
+			ManagementZone::Network(ref z) => match z.action() {
+				NetworkAction::View => Role::Viewer,
+				_ => Role::Operator,
+			},
+			ManagementZone::Validation(ref z) => match z.action() {
+				ValidationAction::View => Role::Viewer,
+				_ => Role::Operator,
+			},
+			ManagementZone::Service(_) => Role::Operator,
+			ManagementZone::Log(_) => Role::Viewer,
+			ManagementZone::Snapshot(ref z) => match z.action() {
+				// Writing a file anywhere on the primary is an admin's call
+				SnapshotAction::Export if z.writes_file() => Role::Admin,
+				SnapshotAction::Export => Role::Operator,
+				SnapshotAction::Import => Role::Admin,
+			},
+			ManagementZone::Geotop(ref z) => match z.action() {
+				GeotopAction::View => Role::Viewer,
+				_ => Role::Operator,
+			},
+			ManagementZone::Registration(ref z) => match z.action() {
+				RegistrationAction::View => Role::Viewer,
+				_ => Role::Operator,
+			},
+			ManagementZone::Throttle(ref z) => match z.action() {
+				ThrottleAction::View => Role::Viewer,
+				ThrottleAction::Lift => Role::Operator,
+			},
+			ManagementZone::Acl(ref z) => match z.action() {
+				AclAction::View => Role::Viewer,
+				_ => Role::Admin,
+			},
+			ManagementZone::Config(ref z) => match z.action() {
+				ConfigAction::View => Role::Operator,
+				ConfigAction::Reload => Role::Admin,
+			},
+			ManagementZone::Stats(_) => Role::Viewer,
+			ManagementZone::Status(_) => Role::Viewer,
+		}
+	}
+}
+
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub struct Peer {
+	pub uid: u32,
+	pub gid: u32,
+	pub pid: i32,
+}
+
+impl Peer {
+	/// SO_PEERCRED of a connected unix socket
+	pub fn of<S: AsRawFd>(stream: &S) -> Option<Peer> {
+		let mut cred = libc::ucred { pid: 0, uid: 0, gid: 0 };
+		let mut len = mem::size_of::<libc::ucred>() as libc::socklen_t;
+
+		let r = unsafe {
+			libc::getsockopt(stream.as_raw_fd(), libc::SOL_SOCKET, libc::SO_PEERCRED,
+							&mut cred as *mut libc::ucred as *mut libc::c_void, &mut len)
+		};
+
+		match r {
+			0 => Some(Peer { uid: cred.uid, gid: cred.gid, pid: cred.pid }),
+			_ => None
+		}
+	}
+
+	fn listed(&self, list: &str) -> bool {
+		list.split(',').map(|s| s.trim()).any(|id| match id.starts_with("gid:") {
+			true => id[4..].parse::<u32>().ok() == Some(self.gid),
+			false => id.parse::<u32>().ok() == Some(self.uid),
+		})
+	}
+}
+
+const ROLES : [Role;3] = [Role::Admin, Role::Operator, Role::Viewer];
+
+/// Who is on the other end of a management connection
+#[derive(Debug,Clone,PartialEq)]
+pub struct Caller {
+	pub peer: Option<Peer>,
+	pub role: Option<Role>,
+	/// Address and identity of a caller on the TLS listener
+	pub remote: Option<String>,
+}
+
+impl Caller {
+	pub fn new(peer: Option<Peer>, config: &NodeConfig) -> Caller {
+		let own = unsafe { libc::getuid() };
+
+		let role = match peer {
+			None => None,
+			Some(p) if p.uid == 0 || p.uid == own => Some(Role::Admin),
+			Some(p) => ROLES.iter()
+						.find(|r| config.setting(&format!("man_{}", r.name())).map_or(false, |l| p.listed(&l)))
+						.cloned()
+						.or(config.setting("man_default").and_then(|r| Role::from_str(&r))),
+		};
+
+		Caller {
+			peer: peer,
+			role: role,
+			remote: None,
+		}
+	}
+
+	/// A caller on the TLS listener, known by its certificate if it sent one
+	pub fn remote(address: &str, cn: Option<String>, config: &NodeConfig) -> Caller {
+		let role = cn.as_ref().and_then(|cn| ROLES.iter()
+						.find(|r| config.setting(&format!("remote_man_cn_{}", r.name()))
+									.map_or(false, |l| l.split(',').any(|n| n.trim() == cn)))
+						.cloned());
+
+		Caller {
+			peer: None,
+			role: role,
+			remote: Some(match cn {
+				Some(cn) => format!("{} cn {}", address, cn),
+				None => address.to_string(),
+			}),
+		}
+	}
+
+	/// Take the role of a remote management token, false if it is not one
+	pub fn authenticate(&mut self, token: &str, config: &NodeConfig) -> bool {
+		let role = ROLES.iter()
+					.find(|r| config.setting(&format!("remote_man_token_{}", r.name()))
+								.map_or(false, |t| !t.is_empty() && same(t.as_bytes(), token.as_bytes())))
+					.cloned();
+
+		match role {
+			Some(r) => { self.role = Some(r); true },
+			None => false
+		}
+	}
+
+	pub fn uid(&self) -> Option<u32> {
+		self.peer.map(|p| p.uid)
+	}
+
+	pub fn may(&self, needed: Role) -> bool {
+		self.role.map_or(false, |r| r >= needed)
+	}
+}
+
+impl fmt::Display for Caller {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		let role = self.role.map_or("no role", |r| r.name());
+
+		match (self.peer, self.remote.as_ref()) {
+			(Some(p), _) => write!(f, "uid {} ({})", p.uid, role),
+			(None, Some(r)) => write!(f, "remote {} ({})", r, role),
+			(None, None) => write!(f, "unknown peer"),
+		}
+	}
+}
+
+/// Compare tokens without giving away how much of one matched
+fn same(a: &[u8], b: &[u8]) -> bool {
+	a.len() == b.len() && a.iter().zip(b.iter()).fold(0, |d, (x, y)| d | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use config::mocks::MockConfig;
+
+	fn peer(uid: u32, gid: u32) -> Option<Peer> {
+		Some(Peer { uid: uid, gid: gid, pid: 1 })
+	}
+
+	#[test]
+	fn ts_auth_roles_p() {
+		let config = MockConfig::dflt().with_setting("man_operator", "1001, gid:50").with_setting("man_viewer", "1002");
+
+		assert_eq!(Caller::new(peer(0, 0), &config).role, Some(Role::Admin));
+		assert_eq!(Caller::new(peer(1001, 100), &config).role, Some(Role::Operator));
+		assert_eq!(Caller::new(peer(1003, 50), &config).role, Some(Role::Operator));
+		assert_eq!(Caller::new(peer(1002, 100), &config).role, Some(Role::Viewer));
+	}
+
+	#[test]
+	fn ts_auth_required_p() {
+		let viewer = Caller { peer: peer(1002, 100), role: Some(Role::Viewer), remote: None };
+
+		assert!(viewer.may(Role::required(&ManagementZone::from_str("network view all").unwrap())));
+		assert!(!viewer.may(Role::required(&ManagementZone::from_str("validation add token abc springname foo").unwrap())));
+		assert_eq!(Role::required(&ManagementZone::from_str("acl del id 3").unwrap()), Role::Admin);
+		assert_eq!(Role::required(&ManagementZone::from_str("snapshot export file /etc/passwd").unwrap()), Role::Admin);
+	}
+
+	#[test]
+	fn ts_auth_unlisted_f() {
+		let config = MockConfig::dflt().with_setting("man_admin", "1001");
+		assert!(!Caller::new(peer(1009, 100), &config).may(Role::Viewer));
+		assert!(!Caller::new(None, &config).may(Role::Viewer));
+
+		let config = MockConfig::dflt().with_setting("man_default", "viewer");
+		assert!(Caller::new(peer(1009, 100), &config).may(Role::Viewer));
+	}
+
+	#[test]
+	fn ts_auth_remote_p() {
+		let config = MockConfig::dflt().with_setting("remote_man_cn_operator", "ops1, ops2").with_setting("remote_man_token_admin", "s3cret");
+
+		assert_eq!(Caller::remote("192.0.2.1:4000", Some("ops2".to_string()), &config).role, Some(Role::Operator));
+
+		let mut caller = Caller::remote("192.0.2.1:4000", None, &config);
+		assert_eq!(caller.role, None);
+		assert!(caller.authenticate("s3cret", &config));
+		assert_eq!(caller.role, Some(Role::Admin));
+	}
+
+	#[test]
+	fn ts_auth_remote_f() {
+		let config = MockConfig::dflt().with_setting("remote_man_token_admin", "s3cret");
+
+		let mut caller = Caller::remote("192.0.2.1:4000", Some("stranger".to_string()), &config);
+		assert_eq!(caller.role, None);
+		assert!(!caller.authenticate("s3cre", &config));
+		assert!(!caller.authenticate("", &config));
+		assert_eq!(caller.role, None);
+	}
+}
@@ -0,0 +1,124 @@
+use config::SharedConfig;
+
+use management::{output,ZoneResult};
+
+use prettytable::Table;
+use prettytable::row::Row;
+use prettytable::cell::Cell;
+
+#[macro_export]
+macro_rules! extract_zone_config {
+	($e: expr) => (
+		match $e {
+			ManagementZone::Config(s) => s,
+			e => panic!("extract_zone_config -- Unexpected value: {:?}", e)
+		}
+	)
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum ConfigAction {
+	View,
+	Reload,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct ConfigZone {
+	action: ConfigAction,
+}
+
+impl ConfigZone {
+	pub fn new(action: ConfigAction) -> ConfigZone {
+		ConfigZone {
+			action: action,
+		}
+	}
+
+	pub fn action(&self) -> ConfigAction {
+		self.action
+	}
+
+	pub fn from_str(msg: &str) -> Option<ConfigZone> {
+		if msg.len() == 0 { return None; }
+
+		let mut atom = msg.split(" ");
+
+		let action = match atom.next() {
+			Some("view") => ConfigAction::View,
+			Some("reload") => ConfigAction::Reload,
+			_ => return None,
+		};
+
+		match atom.next() {
+			None | Some("all") => Some(ConfigZone::new(action)),
+			_ => None
+		}
+	}
+
+	pub fn process(cz: ConfigZone, config: &SharedConfig) -> Option<ZoneResult> {
+		match cz.action {
+			ConfigAction::View => ConfigZoneModel::view(config),
+			ConfigAction::Reload => ConfigZoneModel::reload(config),
+		}
+	}
+}
+
+struct ConfigZoneModel;
+
+impl ConfigZoneModel {
+	pub fn view(config: &SharedConfig) -> Option<ZoneResult> {
+		let mut table = Table::new();
+		table.add_row(row!["_key_", "_value_"]);
+
+		for (k, v) in config.current().settings() {
+			table.add_row(Row::new(vec![
+							Cell::new(&k),
+							Cell::new(&v)
+							]));
+		}
+
+		Some(Ok(output::table(table)))
+	}
+
+	pub fn reload(config: &SharedConfig) -> Option<ZoneResult> {
+		Some(match config.reload() {
+			Ok(ref changes) if changes.is_empty() => Ok("Reloaded; nothing changed\n".to_string()),
+			Ok(changes) => Ok(format!("Reloaded:\n{}\n", changes.join("\n"))),
+			Err(e) => Err(format!("Error: reload rejected, {}\n", e))
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use management::ManagementZone;
+
+	macro_rules! unwrap_some {
+		($chk:expr) => (
+			match $chk {
+						Some(s) => s,
+						_ => panic!("Unwrapping a None")
+			})
+	}
+
+	#[test]
+	fn ts_config_reload_p() {
+		let mz = unwrap_some!(ManagementZone::from_str("config reload"));
+		let cz : ConfigZone = extract_zone_config!(mz);
+		assert_eq!(cz.action, ConfigAction::Reload);
+	}
+
+	#[test]
+	fn ts_config_view_p() {
+		let mz = unwrap_some!(ManagementZone::from_str("cfg view all"));
+		let cz : ConfigZone = extract_zone_config!(mz);
+		assert_eq!(cz.action, ConfigAction::View);
+	}
+
+	#[test]
+	fn ts_config_f() {
+		assert_eq!(ManagementZone::from_str("config reload now"), None);
+		assert_eq!(ManagementZone::from_str("config edit"), None);
+	}
+}
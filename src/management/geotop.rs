@@ -0,0 +1,314 @@
+use std::str::Split;
+
+use netspace::*;
+use geotop::GeotopRoot;
+use netservice::geotop::discovery::Discovery;
+
+use management::{output,ZoneResult};
+
+use prettytable::Table;
+use prettytable::row::Row;
+use prettytable::cell::Cell;
+
+#[macro_export]
+macro_rules! extract_zone_geotop {
+	($e: expr) => (
+		match $e {
+			ManagementZone::Geotop(s) => s,
+			e => panic!("extract_zone_geotop -- Unexpected value: {:?}", e)
+		}
+	)
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum GeotopAction {
+	View,
+	Add,
+	Remove,
+	Priority,
+	Exchange,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum GeotopOperand {
+	None,
+	All,
+	Geosub(String),
+	Node(String),
+	Host(String),
+	Address(String),
+	Service(NodeService),
+	Priority(i64),
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct GeotopZone {
+	action: GeotopAction,
+	ops: Vec<GeotopOperand>,
+}
+
+impl GeotopZone {
+	pub fn new(action: GeotopAction, ops: Vec<GeotopOperand>) -> GeotopZone {
+		GeotopZone {
+			action: action,
+			ops: ops,
+		}
+	}
+
+	pub fn action(&self) -> GeotopAction {
+		self.action
+	}
+
+	pub fn from_str(msg: &str) -> Option<GeotopZone> {
+		if msg.len() == 0 { return None; }
+
+		let mut atom = msg.split(" ");
+
+		let action = match atom.next() {
+			Some("view") => GeotopAction::View,
+			Some("add") => GeotopAction::Add,
+			Some("del") | Some("remove") => GeotopAction::Remove,
+			Some("pri") | Some("priority") => GeotopAction::Priority,
+			Some("exc") | Some("exchange") => GeotopAction::Exchange,
+			_ => return None,
+		};
+
+		let mut ops = Vec::new();
+		loop {
+			match cascade_none_nowrap!(GeotopZone::extract_operand(&mut atom)) {
+				GeotopOperand::None => break,
+				op => ops.push(op)
+			}
+		}
+
+		if ops.is_empty() { return None }
+
+		Some(GeotopZone::new(action, ops))
+	}
+
+	fn extract_operand(atom: &mut Split<&str>) -> Option<GeotopOperand> {
+
+		Some(match atom.next() {
+			Some("all") =>
+						GeotopOperand::All,
+
+			Some("geosub") =>
+						GeotopOperand::Geosub(
+							cascade_none_nowrap!(atom.next()).to_string()
+						),
+
+			Some("node") | Some("springname") =>
+						GeotopOperand::Node(
+							cascade_none_nowrap!(atom.next()).to_string()
+						),
+
+			Some("hostname") =>
+						GeotopOperand::Host(
+							cascade_none_nowrap!(atom.next()).to_string()
+						),
+
+			Some("address") =>
+						GeotopOperand::Address(
+							cascade_none_nowrap!(atom.next()).to_string()
+						),
+
+			Some("service") =>
+						GeotopOperand::Service(
+							cascade_none_nowrap!(
+								NodeService::from_str(
+									cascade_none_nowrap!(atom.next())
+								)
+							)
+						),
+
+			Some("priority") =>
+						GeotopOperand::Priority(
+							match cascade_none_nowrap!(atom.next()).parse() {
+								Ok(n) => n,
+								Err(_) => return None
+							}
+						),
+
+			_ => GeotopOperand::None,
+		})
+	}
+
+	pub fn process(gz: GeotopZone, nio: &NetspaceIo, config: &NodeConfig) -> Option<ZoneResult> {
+		let target = GeotopTarget::from_ops(gz.ops);
+
+		match gz.action {
+			GeotopAction::View => GeotopZoneModel::view(target, nio),
+			GeotopAction::Add => GeotopZoneModel::add(target, nio, config),
+			GeotopAction::Remove => GeotopZoneModel::remove(target, nio),
+			GeotopAction::Priority => GeotopZoneModel::priority(target, nio),
+			GeotopAction::Exchange => GeotopZoneModel::exchange(target, nio, config),
+		}
+	}
+}
+
+/// Operands collected into the root they describe
+struct GeotopTarget {
+	geosub: Option<String>,
+	springname: Option<String>,
+	hostname: Option<String>,
+	address: Option<String>,
+	service: NodeService,
+	priority: Option<i64>,
+}
+
+impl GeotopTarget {
+	fn from_ops(ops: Vec<GeotopOperand>) -> GeotopTarget {
+		let mut t = GeotopTarget { geosub: None, springname: None, hostname: None, address: None, service: NodeService::Dvsp, priority: None };
+
+		for op in ops {
+			match op {
+				GeotopOperand::Geosub(s) => t.geosub = Some(s),
+				GeotopOperand::Node(s) => t.springname = Some(s),
+				GeotopOperand::Host(s) => t.hostname = Some(s),
+				GeotopOperand::Address(s) => t.address = Some(s),
+				GeotopOperand::Service(s) => t.service = s,
+				GeotopOperand::Priority(n) => t.priority = Some(n),
+				_ => { }
+			}
+		}
+
+		t
+	}
+}
+
+struct GeotopZoneModel;
+
+impl GeotopZoneModel {
+	pub fn view(target: GeotopTarget, nio: &NetspaceIo) -> Option<ZoneResult> {
+		Some(match nio.gtn_roots(target.geosub.as_ref().map(|s| s.as_ref())) {
+			Ok(v) => Ok(Self::tabulate_roots(&v)),
+			Err(e) => Err(format!("Error: unable to read geotop registry ({:?})\n", e))
+		})
+	}
+
+	pub fn add(target: GeotopTarget, nio: &NetspaceIo, config: &NodeConfig) -> Option<ZoneResult> {
+		let root = match (target.geosub, target.springname, target.hostname, target.address) {
+			(Some(g), Some(s), Some(h), Some(a)) => GeotopRoot::new(&g, &s, &h, &a, target.service, target.priority.unwrap_or(1)),
+			_ => return Some(Err("Error: a root needs a geosub, springname, hostname and address\n".to_string()))
+		};
+
+		if root.geosub == config.geosub() {
+			return Some(Err(format!("Error: roots of {} are managed through the network zone\n", root.geosub)))
+		}
+
+		Some(match nio.gtn_root_store(&root) {
+			Ok(_) => Ok(format!("Added {} as a root of {}\n", root.springname, root.geosub)),
+			Err(e) => Err(format!("Error: failed to add root ({:?})\n", e))
+		})
+	}
+
+	pub fn remove(target: GeotopTarget, nio: &NetspaceIo) -> Option<ZoneResult> {
+		let (geosub, springname) = match (target.geosub, target.springname) {
+			(Some(g), Some(s)) => (g, s),
+			_ => return Some(Err("Error: removing a root needs a geosub and springname\n".to_string()))
+		};
+
+		let root = match nio.gtn_root(&springname, &geosub) {
+			Ok(Some(r)) => r,
+			Ok(None) => return Some(Err(format!("Error: {} is not a root of {}\n", springname, geosub))),
+			Err(e) => return Some(Err(format!("Error: unable to read geotop registry ({:?})\n", e)))
+		};
+
+		Some(match nio.gtn_geosub_unregister_node(&root.to_node(), &geosub) {
+			Ok(_) => Ok(format!("Removed {} as a root of {}\n", springname, geosub)),
+			Err(e) => Err(format!("Error: failed to remove root ({:?})\n", e))
+		})
+	}
+
+	pub fn priority(target: GeotopTarget, nio: &NetspaceIo) -> Option<ZoneResult> {
+		let (geosub, springname, priority) = match (target.geosub, target.springname, target.priority) {
+			(Some(g), Some(s), Some(p)) => (g, s, p),
+			_ => return Some(Err("Error: setting a priority needs a geosub, springname and priority\n".to_string()))
+		};
+
+		Some(match nio.gtn_geosub_update_priority(&springname, &geosub, priority) {
+			Ok(_) => Ok(format!("Priority of {} in {} set to {}\n", springname, geosub, priority)),
+			Err(e) => Err(format!("Error: failed to set priority ({:?})\n", e))
+		})
+	}
+
+	pub fn exchange(target: GeotopTarget, nio: &NetspaceIo, config: &NodeConfig) -> Option<ZoneResult> {
+		let mut out = String::new();
+		let mut failed = false;
+
+		for (geosub, result) in Discovery::exchange(nio, config, target.geosub.as_ref().map(|s| s.as_ref())) {
+			out.push_str(&match result {
+				Ok(n) => format!("{}: learnt {} root(s)\n", geosub, n),
+				Err(e) => { failed = true; format!("Error: {}: failed ({})\n", geosub, e) }
+			});
+		}
+
+		if out.is_empty() { out.push_str("No other geosubs to exchange with\n") }
+		Some(match failed {
+			true => Err(out),
+			false => Ok(out)
+		})
+	}
+
+	fn tabulate_roots(roots: &Vec<GeotopRoot>) -> String {
+		let mut table = Table::new();
+		table.add_row(row!["_geosub_", "_spring_", "_host_", "_address_", "_service_", "_priority_"]);
+
+		for root in roots {
+			table.add_row(Row::new(vec![
+							Cell::new(&root.geosub),
+							Cell::new(&root.springname),
+							Cell::new(&root.hostname),
+							Cell::new(&root.address),
+							Cell::new(&format!("{}", root.service)),
+							Cell::new(&format!("{}", root.priority))
+							]));
+		}
+
+		output::table(table)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use management::ManagementZone;
+	use netspace::NodeService;
+
+	macro_rules! unwrap_some {
+		($chk:expr) => (
+			match $chk {
+						Some(s) => s,
+						_ => panic!("Unwrapping a None")
+			})
+	}
+
+	#[test]
+	fn ts_geotop_add_p() {
+		let mz = unwrap_some!(ManagementZone::from_str("geotop add geosub wsusx springname springa hostname greenman address 192.168.1.2 service http priority 2"));
+		let gz : GeotopZone = extract_zone_geotop!(mz);
+		assert_eq!(gz.action, GeotopAction::Add);
+		assert_eq!(gz.ops, vec![
+			GeotopOperand::Geosub("wsusx".to_string()),
+			GeotopOperand::Node("springa".to_string()),
+			GeotopOperand::Host("greenman".to_string()),
+			GeotopOperand::Address("192.168.1.2".to_string()),
+			GeotopOperand::Service(NodeService::Http),
+			GeotopOperand::Priority(2),
+		]);
+	}
+
+	#[test]
+	fn ts_geotop_view_all_p() {
+		let mz = unwrap_some!(ManagementZone::from_str("geotop view all"));
+		let gz : GeotopZone = extract_zone_geotop!(mz);
+		assert_eq!(gz.action, GeotopAction::View);
+		assert_eq!(gz.ops, vec![GeotopOperand::All]);
+	}
+
+	#[test]
+	fn ts_geotop_priority_f() {
+		assert_eq!(ManagementZone::from_str("geotop priority geosub wsusx priority high"), None);
+		assert_eq!(ManagementZone::from_str("geotop view"), None);
+	}
+}
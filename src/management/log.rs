@@ -0,0 +1,206 @@
+use std::str::Split;
+
+use netspace::*;
+use audit::{AuditFilter,AuditEntry};
+
+use management::{output,ZoneResult};
+
+use prettytable::Table;
+use prettytable::row::Row;
+use prettytable::cell::Cell;
+
+#[macro_export]
+macro_rules! extract_zone_log {
+	($e: expr) => (
+		match $e {
+			ManagementZone::Log(s) => s,
+			e => panic!("extract_zone_log -- Unexpected value: {:?}", e)
+		}
+	)
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum LogAction {
+	View,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum LogOperand {
+	None,
+	All,
+	Node(String),
+	Action(String),
+	Since(String),
+	Until(String),
+	Limit(i64),
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct LogZone {
+	action: LogAction,
+	ops: Vec<LogOperand>,
+}
+
+impl LogZone {
+	pub fn new(action: LogAction, ops: Vec<LogOperand>) -> LogZone {
+		LogZone {
+			action: action,
+			ops: ops,
+		}
+	}
+
+	pub fn from_str(msg: &str) -> Option<LogZone> {
+		if msg.len() == 0 { return None; }
+
+		let mut atom = msg.split(" ");
+
+		let action = match atom.next() {
+			Some("view") => LogAction::View,
+			_ => return None,
+		};
+
+		let mut ops = Vec::new();
+		loop {
+			match cascade_none_nowrap!(LogZone::extract_operand(&mut atom)) {
+				LogOperand::None => break,
+				op => ops.push(op)
+			}
+		}
+
+		if ops.is_empty() { return None }
+
+		Some(LogZone::new(action, ops))
+	}
+
+	fn extract_operand(atom: &mut Split<&str>) -> Option<LogOperand> {
+
+		Some(match atom.next() {
+			Some("all") =>
+						LogOperand::All,
+
+			Some("node") | Some("springname") =>
+						LogOperand::Node(
+							cascade_none_nowrap!(atom.next()).to_string()
+						),
+
+			Some("action") =>
+						LogOperand::Action(
+							cascade_none_nowrap!(atom.next()).to_string()
+						),
+
+			Some("since") =>
+						LogOperand::Since(
+							cascade_none_nowrap!(atom.next()).to_string()
+						),
+
+			Some("until") =>
+						LogOperand::Until(
+							cascade_none_nowrap!(atom.next()).to_string()
+						),
+
+			Some("limit") =>
+						LogOperand::Limit(
+							match cascade_none_nowrap!(atom.next()).parse() {
+								Ok(n) => n,
+								Err(_) => return None
+							}
+						),
+
+			_ => LogOperand::None,
+		})
+	}
+
+	pub fn process(lz: LogZone, nio: &NetspaceIo) -> Option<ZoneResult> {
+		match lz.action {
+			LogAction::View => LogZoneModel::view(lz.ops, nio),
+		}
+	}
+}
+
+struct LogZoneModel;
+
+impl LogZoneModel {
+	pub fn view(ops: Vec<LogOperand>, nio: &NetspaceIo) -> Option<ZoneResult> {
+		let mut filter = AuditFilter::new();
+
+		for op in ops {
+			match op {
+				LogOperand::All => { },
+				LogOperand::Node(s) => filter.springname = Some(s),
+				LogOperand::Action(s) => filter.action = Some(s),
+				LogOperand::Since(s) => filter.since = Some(s),
+				LogOperand::Until(s) => filter.until = Some(s),
+				LogOperand::Limit(n) => filter.limit = n,
+				LogOperand::None => return None,
+			}
+		}
+
+		Some(match nio.audit_entries(&filter) {
+			Ok(v) => Ok(Self::tabulate_entries(&v)),
+			Err(e) => Err(format!("Error: unable to read audit log ({:?})\n", e))
+		})
+	}
+
+	fn tabulate_entries(entries: &Vec<AuditEntry>) -> String {
+		let mut table = Table::new();
+		Self::add_headings(&mut table);
+		for entry in entries {
+			table.add_row(Row::new(vec![
+							Cell::new(&entry.timestamp),
+							Cell::new(&entry.actor),
+							Cell::new(&entry.action),
+							Cell::new(&entry.springname),
+							Cell::new(&entry.before),
+							Cell::new(&entry.after)
+							]));
+		}
+
+		output::table(table)
+	}
+
+	fn add_headings(table: &mut Table) {
+		table.add_row(row!["_time_", "_actor_",
+							"_action_", "_spring_",
+							"_before_", "_after_"]);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use management::ManagementZone;
+
+	macro_rules! unwrap_some {
+		($chk:expr) => (
+			match $chk {
+						Some(s) => s,
+						_ => panic!("Unwrapping a None")
+			})
+	}
+
+	#[test]
+	fn ts_log_view_all_p() {
+		let mz = unwrap_some!(ManagementZone::from_str("log view all"));
+		let lz : LogZone = extract_zone_log!(mz);
+		assert_eq!(lz.action, LogAction::View);
+		assert_eq!(lz.ops, vec![LogOperand::All]);
+	}
+
+	#[test]
+	fn ts_log_view_node_range_p() {
+		let mz = unwrap_some!(ManagementZone::from_str("log view node foo action register since 2016-01-01 until 1470000000"));
+		let lz : LogZone = extract_zone_log!(mz);
+		assert_eq!(lz.ops, vec![
+			LogOperand::Node("foo".to_string()),
+			LogOperand::Action("register".to_string()),
+			LogOperand::Since("2016-01-01".to_string()),
+			LogOperand::Until("1470000000".to_string()),
+		]);
+	}
+
+	#[test]
+	fn ts_log_view_limit_f() {
+		assert_eq!(ManagementZone::from_str("log view limit many"), None);
+		assert_eq!(ManagementZone::from_str("log view"), None);
+	}
+}
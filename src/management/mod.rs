@@ -1,13 +1,20 @@
 extern crate unix_socket;
 
+use std::cell::RefCell;
+use std::cmp;
 use std::io::prelude::*;
-use std::mem;
+use std::panic::{self,AssertUnwindSafe};
 use std::str::FromStr;
+use std::time::Duration;
 
-use ::protocol::{SocketAddr,Svr};
-use netspace::{NetspaceIo,Config};
+use ::protocol::{SocketAddr,Svr,Transport};
+use netspace::{self,NetspaceIo};
+use config::{NodeConfig,SharedConfig};
+use throttle::ThrottleLimits;
 
 use self::unix_socket::UnixStream;
+use self::wire::{Status,WireError};
+use self::auth::{Caller,Peer,Role};
 
 
 
@@ -25,77 +32,360 @@ macro_rules! cascade_none_nowrap {
 mod network;
 mod validation;
 mod service;
+mod log;
+mod snapshot;
+mod geotop;
+mod registration;
+mod throttle;
+mod acl;
+mod config;
+mod stats;
+mod status;
+pub mod output;
+pub mod wire;
+mod auth;
+pub mod remote;
 
 use self::validation::ValidationZone;
 use self::network::NetworkZone;
 use self::service::ServiceZone;
+use self::log::LogZone;
+use self::snapshot::SnapshotZone;
+use self::geotop::GeotopZone;
+use self::registration::RegistrationZone;
+use self::geotop::GeotopAction;
+use self::throttle::{ThrottleZone,ThrottleAction};
+use self::acl::AclZone;
+use self::config::ConfigZone;
+use self::stats::StatsZone;
+use self::status::StatusZone;
+
+/// Seconds a management session may sit idle before it is dropped
+const IDLE_SECS : u64 = 300;
 
 fn binary_split(msg: &str) -> Vec<&str> {
 	msg.splitn(2, " ").collect()
 }
 
+/// The reply to a command that ran, or the error it failed with
+pub type ZoneResult = Result<String,String>;
+
 pub trait ManagedService {
-	fn init(&self) -> String;
-	fn hook(&self, atom: &Vec<String>, svr: &Svr) -> String;
+	fn init(&self) -> ZoneResult;
+	fn hook(&self, atom: &Vec<String>, svr: &Svr) -> ZoneResult;
 }
 
 
-pub fn management_handler(mut stream: UnixStream, config: Config) {
+pub fn management_handler(mut stream: UnixStream, shared: SharedConfig) {
 	
-	let nio = match config.live_test {
-		false => {
-			NetspaceIo::new("/var/lib/springdvs/gsn.db") 
-		},
-		true => {
-			NetspaceIo::new("live-testing.db")
+	let config = shared.current();
+	let opened = NetspaceIo::open(netspace::database_path(config.live_test));
+	
+	let nio = match opened {
+		Ok(nio) => nio,
+		Err(e) => {
+			log_error!("management", "Unable to open the netspace ({:?})", e);
+			return
 		}
 	};
 	
-	let svr = Svr::new(SocketAddr::from_str("0.0.0.0:0").unwrap(), Box::new(config.clone()), &nio);
+	// The session holds up a drain for as long as it is open
+	let _ = stream.set_read_timeout(Some(Duration::new(IDLE_SECS, 0)));
 	
-	let mut szin_buf = [0;4];
+	let caller = Caller::new(Peer::of(&stream), &config);
+	log_info!("management", "Connection from {}", caller);
 	
-	stream.read_exact(&mut szin_buf).unwrap();
+	// Audit entries record who made the change
+	nio.set_actor(&caller.uid().map_or("management".to_string(), |u| format!("management uid {}", u)));
 	
-	let szin : u32 = unsafe { mem::transmute(szin_buf) };
+	let mi = ManagementInstance::new(&nio, &shared, caller);
 	
-	let mut bufin : Vec<u8> = Vec::new();
-	bufin.resize(szin as usize, b'\0');
-	stream.read_exact(bufin.as_mut_slice()).unwrap();
-	let command = String::from_utf8(bufin).unwrap();
-	
-	let mi = ManagementInstance::new();
+	// A framed session opens with the magic bytes; anything else
+	// is taken as the length of an old single command
+	let mut lead = [0;4];
+	if let Err(e) = stream.read_exact(&mut lead) {
+		log_debug!("management", "Connection closed before a command ({})", e);
+		return
+	}
 	
-	let out = match mi.run(&command, &svr) {
-		Some(s) => s,
-		None => "Error: Unrecognised or malformed command".to_string() 
+	let result = match &lead == wire::MAGIC {
+		true => mi.session(&mut stream),
+		false => mi.legacy(&mut stream, lead),
 	};
-	stream.write_all(out.as_bytes()).unwrap();
+	
+	if let Err(e) = result {
+		log_warn!("management", "Session ended early ({:?})", e);
+	}
 }
 
-struct ManagementInstance;
+/// Agree a protocol version after the magic bytes, false when
+/// the client's is too old and the session is over
+fn hello<S: Read + Write>(stream: &mut S) -> Result<bool,WireError> {
+	let version = try!(wire::read_version(stream));
+	try!(wire::write_hello(stream, cmp::min(version, wire::VERSION)));
+	
+	if version < wire::MIN_VERSION {
+		try!(wire::write_reply(stream, Status::Unsupported, &format!("Error: protocol version {} is not supported\n", version)));
+		return Ok(false)
+	}
+	
+	Ok(true)
+}
 
-impl ManagementInstance {
-	pub fn new() -> Self {
-		ManagementInstance
+/// Give a remote caller the role of its token
+fn check_token(caller: &mut Caller, token: &str, config: &NodeConfig) -> Result<String,String> {
+	match caller.authenticate(token.trim(), config) {
+		true => {
+			log_info!("management", "Authenticated {}", caller);
+			Ok(format!("Authenticated as {}\n", caller.role.map_or("", |r| r.name())))
+		},
+		false => {
+			log_warn!("management", "Bad token from {}", caller);
+			Err("Error: bad token\n".to_string())
+		}
+	}
+}
+
+struct ManagementInstance<'a> {
+	nio: &'a NetspaceIo,
+	config: &'a SharedConfig,
+	caller: RefCell<Caller>,
+	/// Source address of a remote caller, for throttling bad tokens
+	address: Option<String>,
+}
+
+impl<'a> ManagementInstance<'a> {
+	pub fn new(nio: &'a NetspaceIo, config: &'a SharedConfig, caller: Caller) -> Self {
+		ManagementInstance {
+			nio: nio,
+			config: config,
+			caller: RefCell::new(caller),
+			address: None,
+		}
 	}
-	pub fn run(&self, command: &str, svr: &Svr) -> Option<String> {
-		self.process_request(cascade_none_nowrap!(ManagementZone::from_str(command)), svr)
+	
+	pub fn from_address(mut self, address: &str) -> Self {
+		self.address = Some(address.to_string());
+		self
+	}
+	
+	/// A framed session of any number of commands
+	fn session<S: Read + Write>(&self, stream: &mut S) -> Result<(),WireError> {
+		match try!(hello(stream)) {
+			true => self.serve(stream),
+			false => Ok(())
+		}
+	}
+	
+	/// The commands of a session once the hello is done
+	fn serve<S: Read + Write>(&self, stream: &mut S) -> Result<(),WireError> {
+		loop {
+			let command = match wire::read_command(stream) {
+				Ok(Some(c)) => c,
+				Ok(None) => return Ok(()),
+				Err(WireError::Io(e)) => return Err(WireError::Io(e)),
+				Err(e) => {
+					// The stream is out of step after a bad frame so the session ends
+					try!(wire::write_reply(stream, Status::BadFrame, &format!("Error: bad frame ({:?})\n", e)));
+					return Err(e)
+				}
+			};
+			
+			let (status, out) = match command.starts_with("auth ") {
+				true => self.authenticate(&command[5..]),
+				false => self.execute(&command),
+			};
+			try!(wire::write_reply(stream, status, &out));
+		}
+	}
+	
+	/// Token authentication for callers on the TLS listener
+	fn authenticate(&self, token: &str) -> (Status, String) {
+		let mut caller = self.caller.borrow_mut();
+		
+		if caller.remote.is_none() {
+			return (Status::Denied, "Error: tokens are only for remote management\n".to_string())
+		}
+		
+		let config = self.config.current();
+		match check_token(&mut caller, token, &config) {
+			Ok(s) => (Status::Ok, s),
+			Err(s) => {
+				if let Some(ref a) = self.address {
+					let _ = self.nio.throttle_failure(a, &ThrottleLimits::from_config(&config));
+				}
+				(Status::Denied, s)
+			}
+		}
+	}
+	
+	/// The original exchange of a native-endian length and command
+	/// for a bare reply, kept for older clients
+	fn legacy(&self, stream: &mut UnixStream, mut lead: [u8;4]) -> Result<(),WireError> {
+		if cfg!(target_endian = "big") { lead.reverse() }
+		
+		let command = try!(wire::read_body(stream, wire::u32_le(&lead) as usize));
+		let (_, out) = self.execute(&command);
+		try!(stream.write_all(out.as_bytes()));
+		Ok(())
+	}
+	
+	/// Run one command, reporting a panic in a zone as an error
+	fn execute(&self, command: &str) -> (Status, String) {
+		log_info!("management", "[{}] {}", *self.caller.borrow(), command);
+		
+		let (format, command) = output::directive(command);
+		output::set_format(format);
+		
+		let result = match panic::catch_unwind(AssertUnwindSafe(|| {
+			let svr = Svr::new(SocketAddr::from_str("0.0.0.0:0").unwrap(), Box::new(self.config.current()), self.nio).over(Transport::Local);
+			self.run(command, &svr)
+		})) {
+			Ok(result) => result,
+			Err(_) => {
+				log_error!("management", "Command failed: {}", command);
+				Ok(Some(Err("Error: the command failed on the primary\n".to_string())))
+			}
+		};
+		
+		let (status, out) = match result {
+			Err(needed) => {
+				log_warn!("management", "Denied {} to {}", command, *self.caller.borrow());
+				(Status::Denied, Some(Err(format!("Error: permission denied; this needs the {} role\n", needed.name()))))
+			},
+			Ok(None) => (Status::Unrecognised, None),
+			Ok(Some(Ok(s))) => (Status::Ok, Some(Ok(s))),
+			Ok(Some(Err(s))) => (Status::Error, Some(Err(s))),
+		};
+		
+		(status, output::respond(out, self.caller.borrow().uid()))
+	}
+	
+	/// The reply to a command, or the role it needed if the caller lacks it
+	pub fn run(&self, command: &str, svr: &Svr) -> Result<Option<ZoneResult>,Role> {
+		if command.starts_with("batch ") {
+			return self.batch(&command[6..], svr)
+		}
+		
+		match ManagementZone::from_str(command) {
+			Some(request) => self.process_request(request, svr),
+			None => Ok(None)
+		}
 	}
 
-	pub fn process_request(&self, request: ManagementZone, svr: &Svr) -> Option<String> {
-		match request {
-			ManagementZone::Network(nz) => NetworkZone::process(nz, svr.nio),
-			ManagementZone::Validation(vz) => ValidationZone::process(vz, svr.nio),
-			ManagementZone::Service(sz) => ServiceZone::process(sz, svr)
+	/// Run `;` separated commands in one transaction, applying all of
+	/// them or none; `dryrun` first reports what they would change
+	fn batch(&self, commands: &str, svr: &Svr) -> Result<Option<ZoneResult>,Role> {
+		let (dry, commands) = match commands.starts_with("dryrun ") {
+			true => (true, &commands[7..]),
+			false => (false, commands),
+		};
+		
+		let commands : Vec<&str> = commands.split(';').map(|c| c.trim()).filter(|c| !c.is_empty()).collect();
+		if commands.is_empty() { return Ok(None) }
+		
+		let mut requests = Vec::new();
+		for (n, command) in commands.iter().enumerate() {
+			let request = match ManagementZone::from_str(command) {
+				Some(r) => r,
+				None => return Ok(Some(Err(format!("Error: batch command {} is not understood ({})\n", n+1, command))))
+			};
+			
+			if !request.transactional() {
+				return Ok(Some(Err(format!("Error: batch command {} cannot run in a batch ({})\n", n+1, command))))
+			}
+			
+			// Nothing runs unless the caller may run all of it
+			let needed = Role::required(&request);
+			if !self.caller.borrow().may(needed) { return Err(needed) }
+			
+			requests.push(request);
 		}
+		
+		let transaction = match self.nio.transaction() {
+			Ok(t) => t,
+			Err(e) => return Ok(Some(Err(format!("Error: unable to start a transaction ({:?})\n", e))))
+		};
+		
+		let mut names = Vec::new();
+		let mut results = Vec::new();
+		let mut changed = 0;
+		
+		for (n, request) in requests.into_iter().enumerate() {
+			// Records are counted from the audit log, which has an entry for each
+			let mark = match self.nio.audit_mark() {
+				Ok(m) => m,
+				Err(e) => return Ok(Some(Err(format!("Error: unable to read the audit log ({:?})\n", e))))
+			};
+			
+			let out = match try!(self.process_request(request, svr)) {
+				Some(Ok(s)) => s,
+				failed => {
+					// Dropping the transaction undoes the commands before this one
+					log_warn!("management", "Batch stopped at command {} ({})", n+1, commands[n]);
+					return Ok(Some(Err(format!("Error: batch stopped at command {} ({}); nothing was applied\n{}",
+											n+1, commands[n], failed.and_then(|r| r.err()).unwrap_or(String::new())))))
+				}
+			};
+			
+			let records = match self.nio.audit_since(mark) {
+				Ok(r) => r,
+				Err(e) => return Ok(Some(Err(format!("Error: unable to read the audit log ({:?})\n", e))))
+			};
+			
+			changed += records;
+			names.push(format!("{}. {}", n+1, commands[n]));
+			results.push(match output::format() {
+				output::Format::Text => format!("[{}] {} ({} record(s))\n{}", n+1, commands[n], records, out),
+				output::Format::Json => out,
+			});
+		}
+		
+		let summary = match dry {
+			true => match transaction.rollback() {
+				Ok(_) => format!("Dry run of {} commands would change {} record(s); nothing was applied\n", commands.len(), changed),
+				Err(e) => return Ok(Some(Err(format!("Error: unable to roll back the dry run ({:?})\n", e)))),
+			},
+			false => match transaction.commit() {
+				Ok(_) => format!("Batch of {} commands applied, {} record(s) changed\n", commands.len(), changed),
+				Err(e) => return Ok(Some(Err(format!("Error: unable to commit the batch, nothing was applied ({:?})\n", e)))),
+			},
+		};
+		
+		let mut parts : Vec<(&str,String)> = names.iter().map(|n| n.as_str()).zip(results.into_iter()).collect();
+		parts.push(("summary", summary));
+		Ok(Some(Ok(output::sections(parts))))
+	}
+	
+	pub fn process_request(&self, request: ManagementZone, svr: &Svr) -> Result<Option<ZoneResult>,Role> {
+		let needed = Role::required(&request);
+		if !self.caller.borrow().may(needed) { return Err(needed) }
+		
+		Ok(match request {
+			ManagementZone::Network(nz) => NetworkZone::process(nz, self.nio),
+			ManagementZone::Validation(vz) => ValidationZone::process(vz, self.nio),
+			ManagementZone::Service(sz) => ServiceZone::process(sz, svr),
+			ManagementZone::Log(lz) => LogZone::process(lz, self.nio),
+			ManagementZone::Snapshot(sz) => SnapshotZone::process(sz, self.nio),
+			ManagementZone::Geotop(gz) => GeotopZone::process(gz, self.nio, svr.config.as_ref()),
+			ManagementZone::Registration(rz) => RegistrationZone::process(rz, self.nio),
+			ManagementZone::Throttle(tz) => ThrottleZone::process(tz, self.nio, svr.config.as_ref()),
+			ManagementZone::Acl(az) => AclZone::process(az, self.nio),
+			ManagementZone::Config(cz) => ConfigZone::process(cz, self.config),
+			ManagementZone::Stats(sz) => StatsZone::process(sz),
+			ManagementZone::Status(sz) => StatusZone::process(sz, self.config),
+		})
 	}
 }
 
 #[derive(Clone, PartialEq, Debug)]
 pub enum ManagementZone {
 	Network(network::NetworkZone), Validation(validation::ValidationZone),
-	Service(service::ServiceZone)
+	Service(service::ServiceZone), Log(log::LogZone),
+	Snapshot(snapshot::SnapshotZone), Geotop(geotop::GeotopZone),
+	Registration(registration::RegistrationZone), Throttle(throttle::ThrottleZone),
+	Acl(acl::AclZone), Config(config::ConfigZone),
+	Stats(stats::StatsZone), Status(status::StatusZone)
 }
 
 impl ManagementZone {
@@ -103,6 +393,7 @@ impl ManagementZone {
 		if msg.len() == 0 { return None; }
 		
 		let atom = binary_split(msg);
+		if atom.len() < 2 { return None }
 		
 		Some(match atom[0] {
 			"net" | "network" => {
@@ -114,8 +405,183 @@ impl ManagementZone {
 			"ser" | "service" => {
 				ManagementZone::Service(cascade_none_nowrap!(ServiceZone::from_str(atom[1])))
 			},
+			"log" => {
+				ManagementZone::Log(cascade_none_nowrap!(LogZone::from_str(atom[1])))
+			},
+			"snap" | "snapshot" => {
+				ManagementZone::Snapshot(cascade_none_nowrap!(SnapshotZone::from_str(atom[1])))
+			},
+			"geo" | "geotop" => {
+				ManagementZone::Geotop(cascade_none_nowrap!(GeotopZone::from_str(atom[1])))
+			},
+			"reg" | "registration" => {
+				ManagementZone::Registration(cascade_none_nowrap!(RegistrationZone::from_str(atom[1])))
+			},
+			"thr" | "throttle" => {
+				ManagementZone::Throttle(cascade_none_nowrap!(ThrottleZone::from_str(atom[1])))
+			},
+			"acl" => {
+				ManagementZone::Acl(cascade_none_nowrap!(AclZone::from_str(atom[1])))
+			},
+			"cfg" | "config" => {
+				ManagementZone::Config(cascade_none_nowrap!(ConfigZone::from_str(atom[1])))
+			},
+			"stats" => {
+				ManagementZone::Stats(cascade_none_nowrap!(StatsZone::from_str(atom[1])))
+			},
+			"status" => {
+				ManagementZone::Status(cascade_none_nowrap!(StatusZone::from_str(atom[1])))
+			},
 			_ => return None
 		})
 		
 	}
+	
+	/// Whether a request only touches the netspace database and can
+	/// be undone as part of a batch; a geotop exchange talks to other
+	/// primaries and lifting a throttle clears counters held in memory
+	pub fn transactional(&self) -> bool {
+		match *self {
+			ManagementZone::Service(_) | ManagementZone::Snapshot(_)
+			| ManagementZone::Config(_) => false,
+			ManagementZone::Geotop(ref z) => z.action() != GeotopAction::Exchange,
+			ManagementZone::Throttle(ref z) => z.action() != ThrottleAction::Lift,
+			_ => true
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::collections::HashMap;
+	use std::thread;
+	use config::Config;
+	use netspace::{Netspace,Node};
+	
+	fn serve(mut server: UnixStream) -> thread::JoinHandle<()> {
+		thread::spawn(move|| {
+			let nio = NetspaceIo::new(":memory:");
+			let shared = SharedConfig::new(Config::with_kvs(HashMap::new()));
+			
+			let mut lead = [0;4];
+			server.read_exact(&mut lead).unwrap();
+			assert_eq!(&lead, wire::MAGIC);
+			
+			let caller = Caller::new(Peer::of(&server), &shared.current());
+			ManagementInstance::new(&nio, &shared, caller).session(&mut server).unwrap();
+		})
+	}
+	
+	#[test]
+	fn ts_management_session_p() {
+		let (mut client, server) = UnixStream::pair().unwrap();
+		let handle = serve(server);
+		
+		wire::write_hello(&mut client, wire::VERSION).unwrap();
+		assert_eq!(wire::read_hello(&mut client).unwrap(), wire::VERSION);
+		
+		wire::write_command(&mut client, "status view services").unwrap();
+		assert_eq!(wire::read_reply(&mut client).unwrap().0, Status::Ok);
+		
+		wire::write_command(&mut client, "status").unwrap();
+		assert_eq!(wire::read_reply(&mut client).unwrap().0, Status::Unrecognised);
+		
+		wire::write_command(&mut client, "").unwrap();
+		handle.join().unwrap();
+	}
+	
+	#[test]
+	fn ts_management_session_version_f() {
+		let (mut client, server) = UnixStream::pair().unwrap();
+		let handle = serve(server);
+		
+		wire::write_hello(&mut client, 1).unwrap();
+		assert_eq!(wire::read_hello(&mut client).unwrap(), 1);
+		assert_eq!(wire::read_reply(&mut client).unwrap().0, Status::Unsupported);
+		
+		handle.join().unwrap();
+	}
+	
+	#[test]
+	fn ts_management_denied_f() {
+		let nio = NetspaceIo::new(":memory:");
+		let shared = SharedConfig::new(Config::with_kvs(HashMap::new()));
+		let caller = Caller { peer: Some(Peer { uid: 1009, gid: 100, pid: 1 }), role: Some(Role::Viewer), remote: None };
+		let mi = ManagementInstance::new(&nio, &shared, caller);
+		
+		assert_eq!(mi.execute("status view services").0, Status::Ok);
+		assert_eq!(mi.execute("config reload").0, Status::Denied);
+		assert_eq!(mi.execute("acl add allow command info any").0, Status::Denied);
+	}
+	
+	#[test]
+	fn ts_management_zone_error_f() {
+		let nio = NetspaceIo::new(":memory:");
+		let shared = SharedConfig::new(Config::with_kvs(HashMap::new()));
+		let mi = ManagementInstance::new(&nio, &shared, admin());
+		
+		// Failure comes from the zone's result, not the wording of its reply
+		assert_eq!(mi.execute("service manage module cert : bogus").0, Status::Error);
+		assert_eq!(mi.execute("acl del id 99").0, Status::Error);
+		assert_eq!(mi.execute("acl view all").0, Status::Ok);
+	}
+	
+	fn admin() -> Caller {
+		Caller { peer: Some(Peer { uid: 0, gid: 0, pid: 1 }), role: Some(Role::Admin), remote: None }
+	}
+	
+	#[test]
+	fn ts_management_batch_p() {
+		let nio = NetspaceIo::new(":memory:");
+		let shared = SharedConfig::new(Config::with_kvs(HashMap::new()));
+		let mi = ManagementInstance::new(&nio, &shared, admin());
+		
+		let (status, out) = mi.execute("batch validation add token abc springname foo; validation add token def springname bar");
+		assert_eq!(status, Status::Ok);
+		assert!(out.contains("Batch of 2 commands applied"));
+		assert!(nio.gsn_check_token("abc"));
+		assert!(nio.gsn_check_token("def"));
+		
+		let (status, out) = mi.execute("batch dryrun validation add token ghi springname baz");
+		assert_eq!(status, Status::Ok);
+		assert!(out.contains("Dry run of 1 commands would change 1 record(s)"));
+		assert!(!nio.gsn_check_token("ghi"));
+	}
+	
+	#[test]
+	fn ts_management_batch_f() {
+		let nio = NetspaceIo::new(":memory:");
+		let shared = SharedConfig::new(Config::with_kvs(HashMap::new()));
+		let mi = ManagementInstance::new(&nio, &shared, admin());
+		
+		let (status, out) = mi.execute("batch validation add token abc springname foo; acl del id 99");
+		assert_eq!(status, Status::Error);
+		assert!(out.contains("stopped at command 2"));
+		assert!(!nio.gsn_check_token("abc"));
+		
+		assert_eq!(mi.execute("batch config reload").0, Status::Error);
+		assert_eq!(mi.execute("batch geotop exchange all").0, Status::Error);
+		assert_eq!(mi.execute("batch validation add token abc springname foo; bogus").0, Status::Error);
+		assert!(!nio.gsn_check_token("abc"));
+		
+		let viewer = Caller { peer: Some(Peer { uid: 1009, gid: 100, pid: 1 }), role: Some(Role::Viewer), remote: None };
+		let mi = ManagementInstance::new(&nio, &shared, viewer);
+		assert_eq!(mi.execute("batch network view all; validation add token abc springname foo").0, Status::Denied);
+	}
+	
+	#[test]
+	fn ts_management_batch_token_rollback_f() {
+		let nio = NetspaceIo::new(":memory:");
+		let shared = SharedConfig::new(Config::with_kvs(HashMap::new()));
+		let mi = ManagementInstance::new(&nio, &shared, admin());
+		nio.gsn_node_register(&Node::from_str("spring:foo,host:foobar,address:192.168.1.2,role:org,service:http").unwrap()).unwrap();
+		
+		// Revoking the node's tokens fails, so removing it must not stick
+		nio.db().execute("DROP TABLE `geosub_tokens`").unwrap();
+		let (status, out) = mi.execute("batch network remove node foo; validation remove node foo");
+		assert_eq!(status, Status::Error);
+		assert!(out.contains("stopped at command 2"));
+		assert!(nio.gsn_node_by_springname("foo").is_ok());
+	}
 }
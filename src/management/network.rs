@@ -2,6 +2,8 @@ use std::str::Split;
 
 use netspace::*;
 
+use management::{output,ZoneResult};
+
 use prettytable::Table;
 use prettytable::row::Row;
 use prettytable::cell::Cell;
@@ -54,6 +56,10 @@ impl NetworkZone {
 		}
 	}
 
+	pub fn action(&self) -> NetworkAction {
+		self.action
+	}
+
 	pub fn from_str(msg: &str) -> Option<NetworkZone> {
 		if msg.len() == 0 { return None; }
 		
@@ -140,7 +146,7 @@ impl NetworkZone {
 		})
 	}
 	
-	pub fn process(nz: NetworkZone, nio: &Netspace) -> Option<String> {
+	pub fn process(nz: NetworkZone, nio: &Netspace) -> Option<ZoneResult> {
 		match nz.action {
 			NetworkAction::View => NetworkZoneModel::view(nz.op1, nio),
 			NetworkAction::Update => NetworkZoneModel::update(nz.op1, nz.op2, nio),
@@ -152,10 +158,10 @@ impl NetworkZone {
 struct NetworkZoneModel;
 	
 impl NetworkZoneModel {
-	pub fn view(op: NetworkOperand, nio: &Netspace) -> Option<String> {
+	pub fn view(op: NetworkOperand, nio: &Netspace) -> Option<ZoneResult> {
 		match op {
 			NetworkOperand::All =>
-				Some( Self::tabulate_nodes(&nio.gsn_nodes()) ),
+				Some( Ok(Self::tabulate_nodes(&nio.gsn_nodes())) ),
 
 			NetworkOperand::Node(s) =>
 				Some( Self::tabulate_node(nio.gsn_node_by_springname(&s)) ),
@@ -164,21 +170,21 @@ impl NetworkZoneModel {
 				Some( Self::tabulate_node(nio.gsn_node_by_hostname(&s)) ),
 
 			NetworkOperand::Role(r) =>
-				Some( Self::tabulate_nodes(&nio.gsn_nodes_by_type(r)) ),
+				Some( Ok(Self::tabulate_nodes(&nio.gsn_nodes_by_type(r))) ),
 				
 			NetworkOperand::State(s) =>
-				Some( Self::tabulate_nodes(&nio.gsn_nodes_by_state(s)) ),
+				Some( Ok(Self::tabulate_nodes(&nio.gsn_nodes_by_state(s))) ),
 				
 			NetworkOperand::Address(a) =>
-				Some( Self::tabulate_nodes(&nio.gsn_nodes_by_address(&a)) ),
+				Some( Ok(Self::tabulate_nodes(&nio.gsn_nodes_by_address(&a))) ),
 
 			_ => None
 		}
 		
 	}
 	
-	pub fn update(target: NetworkOperand, value: NetworkOperand, nio: &Netspace) -> Option<String> {
-		let mut v : Vec<String> = Vec::new();
+	pub fn update(target: NetworkOperand, value: NetworkOperand, nio: &Netspace) -> Option<ZoneResult> {
+		let mut v : Vec<ZoneResult> = Vec::new();
 		
 		match target {
 			NetworkOperand::All => {
@@ -213,72 +219,77 @@ impl NetworkZoneModel {
 			_ => return None
 		}
 		
-		Some(format!("{}\n",v.join("\n")))
+		// Every node is reported on even when some updates fail
+		let failed = v.iter().any(|r| r.is_err());
+		let out = format!("{}\n", v.into_iter().map(|r| match r { Ok(s) | Err(s) => s }).collect::<Vec<String>>().join("\n"));
+		Some(match failed {
+			true => Err(out),
+			false => Ok(out)
+		})
 	}
 	
-	fn update_node(node_result: Result<Node, NetspaceFailure>, value: NetworkOperand, nio: &Netspace ) -> String {
+	fn update_node(node_result: Result<Node, NetspaceFailure>, value: NetworkOperand, nio: &Netspace ) -> ZoneResult {
 		
 		let mut node = match node_result {
 			Ok(n) => n,
-			Err(e) => return format!("Error requesting node {:?}", e)
+			Err(e) => return Err(format!("Error requesting node {:?}", e))
 		};
 
 		match value {
 			NetworkOperand::Role(r) => {
 				let old = node.role(); 
 				node.update_role(r);
-				nio.gsn_node_update_role(&node).unwrap();
-				format!("Updated {} role: {} -> {}", node.springname(), old, r)
+				Self::outcome(nio.gsn_node_update_role(&node), format!("Updated {} role: {} -> {}", node.springname(), old, r))
 			},
 			
 			NetworkOperand::State(s) => {
 				let old = node.state(); 
 				node.update_state(s);
-				nio.gsn_node_update_state(&node).unwrap();
-				format!("Updated {} state: {} -> {}", node.springname(), old, s)
+				Self::outcome(nio.gsn_node_update_state(&node), format!("Updated {} state: {} -> {}", node.springname(), old, s))
 			},
 
 			NetworkOperand::Service(s) => {
 				let old = node.service(); 
 				node.update_service(s);
-				nio.gsn_node_update_service(&node).unwrap();
-				format!("Updated {} service: {} -> {}", node.springname(), old, s)
+				Self::outcome(nio.gsn_node_update_service(&node), format!("Updated {} service: {} -> {}", node.springname(), old, s))
 			},
 
 			NetworkOperand::Host(s) => {
 				let old = node.hostname().to_string(); 
 				node.update_hostname(&s);
-				nio.gsn_node_update_hostname(&node).unwrap();
-				format!("Updated {} hostname: {} -> {}", node.springname(), old, s)
+				Self::outcome(nio.gsn_node_update_hostname(&node), format!("Updated {} hostname: {} -> {}", node.springname(), old, s))
 			},
 
 			NetworkOperand::Address(s) => {
 				let old = node.address().to_string(); 
 				node.update_address(&s);
-				nio.gsn_node_update_address(&node).unwrap();
-				format!("Updated {} address: {} -> {}", node.springname(), old, s)
+				Self::outcome(nio.gsn_node_update_address(&node), format!("Updated {} address: {} -> {}", node.springname(), old, s))
 			},
-			_ => "Error: Unknown or unsupported value for updating".to_string()
+			_ => Err("Error: Unknown or unsupported value for updating".to_string())
 		}
 	} 
 	
-	pub fn remove(op: NetworkOperand, nio: &Netspace) -> Option<String> {
+	pub fn remove(op: NetworkOperand, nio: &Netspace) -> Option<ZoneResult> {
 		
 		Some(match op {
 			NetworkOperand::Node(s) => {
 				match nio.gsn_node_by_springname(&s) {
-					Ok(n) => {
-						nio.gsn_node_unregister(&n).unwrap();
-						format!("Removed node {}\n", n.springname())
-					},
-					Err(e) => format!("Error: unabled to retrieve node ({:?})\n", e)
+					Ok(n) => Self::outcome(nio.gsn_node_unregister(&n), format!("Removed node {}\n", n.springname())),
+					Err(e) => Err(format!("Error: unabled to retrieve node ({:?})\n", e))
 				}
 								
 			},
-			e => format!("Error: Unknown or unsupported target filter ({:?})\n", e)		
+			e => Err(format!("Error: Unknown or unsupported target filter ({:?})\n", e))		
 		})
 	}
 	
+	fn outcome(result: Result<Success,NetspaceFailure>, message: String) -> ZoneResult {
+		match result {
+			Ok(_) => Ok(message),
+			Err(e) => Err(format!("Error: netspace operation failed ({:?})", e))
+		}
+	}
+	
 	fn tabulate_nodes(nodes: &Vec<Node>) -> String {
 		let mut table = Table::new();
 		Self::add_headings(&mut table);
@@ -294,14 +305,14 @@ impl NetworkZoneModel {
 		}
 		
 		
-		format!("{}", table)
+		output::table(table)
 	}
 	
-	fn tabulate_node(node_result: Result<Node, NetspaceFailure>) -> String {
+	fn tabulate_node(node_result: Result<Node, NetspaceFailure>) -> ZoneResult {
 		
 		let node = match node_result {
 			Ok(n) => n,
-			Err(e) => return format!("Error requesting node {:?}", e)
+			Err(e) => return Err(format!("Error requesting node {:?}", e))
 		};
 
 		let mut table = Table::new();
@@ -315,7 +326,7 @@ impl NetworkZoneModel {
 						Cell::new( &format!("{}", node.service()) )
 					]));
 		
-		format!("{}", table)	
+		Ok(output::table(table))
 	}
 	
 	fn add_headings(table: &mut Table) {
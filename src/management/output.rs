@@ -0,0 +1,175 @@
+use std::cell::Cell as FormatCell;
+use std::collections::BTreeMap;
+
+use rustc_serialize::json::{self, Json, ToJson};
+
+use prettytable::Table;
+
+use management::ZoneResult;
+
+/*
+ * Management output format
+ *
+ * A command prefixed with `--json` or `format json` gets its result
+ * back as a JSON object with an explicit `success` field: tables
+ * become `data`, an array of objects keyed by column, and anything
+ * else is a `message`, or the `error` of a command that failed.
+ * Each management command runs on its own thread so the format is
+ * kept per thread while the zones render their results.
+ */
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Format {
+	Text,
+	Json,
+}
+
+thread_local!(static FORMAT : FormatCell<Format> = FormatCell::new(Format::Text));
+
+pub fn format() -> Format {
+	FORMAT.with(|f| f.get())
+}
+
+pub fn set_format(format: Format) {
+	FORMAT.with(|f| f.set(format))
+}
+
+/// Split any output format directive off the front of a command
+pub fn directive(command: &str) -> (Format, &str) {
+	for prefix in &["--json ", "format json "] {
+		if command.starts_with(prefix) {
+			return (Format::Json, &command[prefix.len()..])
+		}
+	}
+
+	(Format::Text, command)
+}
+
+/// Render a table whose first row holds the `_column_` titles
+pub fn table(table: Table) -> String {
+	match format() {
+		Format::Text => format!("{}", table),
+		Format::Json => json::encode(&table_json(&table)).unwrap(),
+	}
+}
+
+/// Render several named results as one
+pub fn sections(parts: Vec<(&str, String)>) -> String {
+	match format() {
+		Format::Text => parts.into_iter().map(|(_, s)| s).collect::<Vec<String>>().concat(),
+		Format::Json => {
+			let mut d = BTreeMap::new();
+			for (name, s) in parts {
+				d.insert(name.to_string(), value(&s));
+			}
+			json::encode(&Json::Object(d)).unwrap()
+		}
+	}
+}
+
+/// The final reply to a command, `None` being one that was not understood;
+/// JSON replies carry the uid of the caller
+pub fn respond(out: Option<ZoneResult>, uid: Option<u32>) -> String {
+	let out = out.unwrap_or(Err("Error: Unrecognised or malformed command".to_string()));
+
+	if format() == Format::Text {
+		return match out { Ok(s) | Err(s) => s }
+	}
+
+	let mut d = BTreeMap::new();
+	d.insert("uid".to_string(), uid.map_or(Json::Null, |u| (u as u64).to_json()));
+	d.insert("success".to_string(), out.is_ok().to_json());
+
+	match out {
+		Ok(s) => match value(&s) {
+			Json::String(text) => { d.insert("message".to_string(), text.to_json()); },
+			data => { d.insert("data".to_string(), data); }
+		},
+		Err(s) => {
+			let error : Vec<&str> = s.lines()
+										.map(|l| l.trim_left_matches("Error:").trim())
+										.filter(|l| !l.is_empty())
+										.collect();
+			d.insert("error".to_string(), error.join("\n").to_json());
+		}
+	}
+
+	json::encode(&Json::Object(d)).unwrap()
+}
+
+/// Something a zone returned, as structured JSON if it already is
+fn value(s: &str) -> Json {
+	match Json::from_str(s) {
+		Ok(j @ Json::Array(_)) | Ok(j @ Json::Object(_)) => j,
+		_ => s.trim().to_json(),
+	}
+}
+
+fn table_json(table: &Table) -> Json {
+	let titles : Vec<String> = match table.get_row(0) {
+		Some(r) => (0..r.len()).map(|i| r.get_cell(i).map_or(String::new(), |c| c.get_content().trim_matches('_').to_string())).collect(),
+		None => return Json::Array(Vec::new())
+	};
+
+	let mut rows = Vec::new();
+
+	for i in 1..table.len() {
+		let row = match table.get_row(i) {
+			Some(r) => r,
+			None => continue
+		};
+
+		let mut d = BTreeMap::new();
+		for (n, title) in titles.iter().enumerate() {
+			d.insert(title.clone(), row.get_cell(n).map_or(Json::Null, |c| c.get_content().to_json()));
+		}
+		rows.push(Json::Object(d));
+	}
+
+	Json::Array(rows)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn ts_output_directive_p() {
+		assert_eq!(directive("--json network view all"), (Format::Json, "network view all"));
+		assert_eq!(directive("format json acl view all"), (Format::Json, "acl view all"));
+		assert_eq!(directive("network view all"), (Format::Text, "network view all"));
+	}
+
+	#[test]
+	fn ts_output_json_table_p() {
+		set_format(Format::Json);
+
+		let mut t = Table::new();
+		t.add_row(row!["_key_", "_value_"]);
+		t.add_row(row!["springname", "local"]);
+
+		let j = Json::from_str(&respond(Some(Ok(table(t))), Some(1000))).unwrap();
+		set_format(Format::Text);
+
+		assert_eq!(j.find("success"), Some(&Json::Boolean(true)));
+		assert_eq!(j.find("uid").unwrap().as_u64(), Some(1000));
+		let rows = j.find("data").unwrap().as_array().unwrap();
+		assert_eq!(rows[0].find("key").unwrap().as_string(), Some("springname"));
+		assert_eq!(rows[0].find("value").unwrap().as_string(), Some("local"));
+	}
+
+	#[test]
+	fn ts_output_json_error_f() {
+		set_format(Format::Json);
+
+		let e = Json::from_str(&respond(Some(Err("Error: no rule 4\n".to_string())), None)).unwrap();
+		let w = Json::from_str(&respond(Some(Err("Unknown or malformed action".to_string())), None)).unwrap();
+		let n = Json::from_str(&respond(None, None)).unwrap();
+		set_format(Format::Text);
+
+		assert_eq!(e.find("success"), Some(&Json::Boolean(false)));
+		assert_eq!(e.find("error").unwrap().as_string(), Some("no rule 4"));
+		assert_eq!(w.find("success"), Some(&Json::Boolean(false)));
+		assert_eq!(n.find("success"), Some(&Json::Boolean(false)));
+	}
+}
@@ -0,0 +1,207 @@
+use std::str::Split;
+
+use netspace::*;
+use pending::PendingRegistration;
+
+use management::{output,ZoneResult};
+
+use prettytable::Table;
+use prettytable::row::Row;
+use prettytable::cell::Cell;
+
+#[macro_export]
+macro_rules! extract_zone_registration {
+	($e: expr) => (
+		match $e {
+			ManagementZone::Registration(s) => s,
+			e => panic!("extract_zone_registration -- Unexpected value: {:?}", e)
+		}
+	)
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum RegistrationAction {
+	View,
+	Approve,
+	Reject,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum RegistrationOperand {
+	None,
+	All,
+	Node(String),
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct RegistrationZone {
+	action: RegistrationAction,
+	op: RegistrationOperand,
+}
+
+impl RegistrationZone {
+	pub fn new(action: RegistrationAction, op: RegistrationOperand) -> RegistrationZone {
+		RegistrationZone {
+			action: action,
+			op: op,
+		}
+	}
+
+	pub fn action(&self) -> RegistrationAction {
+		self.action
+	}
+
+	pub fn from_str(msg: &str) -> Option<RegistrationZone> {
+		if msg.len() == 0 { return None; }
+
+		let mut atom = msg.split(" ");
+
+		let action = match atom.next() {
+			Some("view") => RegistrationAction::View,
+			Some("app") | Some("approve") => RegistrationAction::Approve,
+			Some("rej") | Some("reject") => RegistrationAction::Reject,
+			_ => return None,
+		};
+
+		let op = match cascade_none_nowrap!(RegistrationZone::extract_operand(&mut atom)) {
+			RegistrationOperand::None => return None,
+			op => op
+		};
+
+		Some(RegistrationZone::new(action, op))
+	}
+
+	fn extract_operand(atom: &mut Split<&str>) -> Option<RegistrationOperand> {
+
+		Some(match atom.next() {
+			Some("all") =>
+						RegistrationOperand::All,
+
+			Some("node") | Some("springname") =>
+						RegistrationOperand::Node(
+							cascade_none_nowrap!(atom.next()).to_string()
+						),
+
+			_ => RegistrationOperand::None,
+		})
+	}
+
+	pub fn process(rz: RegistrationZone, nio: &NetspaceIo) -> Option<ZoneResult> {
+		match rz.action {
+			RegistrationAction::View => RegistrationZoneModel::view(rz.op, nio),
+			RegistrationAction::Approve => RegistrationZoneModel::decide(rz.op, nio, true),
+			RegistrationAction::Reject => RegistrationZoneModel::decide(rz.op, nio, false),
+		}
+	}
+}
+
+struct RegistrationZoneModel;
+
+impl RegistrationZoneModel {
+	pub fn view(op: RegistrationOperand, nio: &NetspaceIo) -> Option<ZoneResult> {
+		let mut pending = match nio.pending_list() {
+			Ok(v) => v,
+			Err(e) => return Some(Err(format!("Error: unable to read pending registrations ({:?})\n", e)))
+		};
+
+		match op {
+			RegistrationOperand::All => { },
+			RegistrationOperand::Node(s) => pending.retain(|p| p.springname == s),
+			RegistrationOperand::None => return None,
+		}
+
+		Some(Ok(Self::tabulate_pending(&pending, nio)))
+	}
+
+	pub fn decide(op: RegistrationOperand, nio: &NetspaceIo, approve: bool) -> Option<ZoneResult> {
+		let names : Vec<String> = match op {
+			RegistrationOperand::Node(s) => vec![s],
+			RegistrationOperand::All => match nio.pending_list() {
+				Ok(v) => v.into_iter().map(|p| p.springname).collect(),
+				Err(e) => return Some(Err(format!("Error: unable to read pending registrations ({:?})\n", e)))
+			},
+			RegistrationOperand::None => return None,
+		};
+
+		let mut out = String::new();
+		let mut failed = false;
+		for name in names {
+			let (r, verb) = match approve {
+				true => (nio.pending_approve(&name), "Approved"),
+				false => (nio.pending_reject(&name), "Rejected"),
+			};
+
+			failed |= r.is_err();
+			out.push_str(&match r {
+				Ok(_) => format!("{} {}\n", verb, name),
+				Err(NetspaceFailure::NodeNotFound) => format!("Error: {} is not pending\n", name),
+				Err(e) => format!("Error: {} failed ({:?})\n", name, e),
+			});
+		}
+
+		if out.is_empty() { out.push_str("No pending registrations\n") }
+		Some(match failed {
+			true => Err(out),
+			false => Ok(out)
+		})
+	}
+
+	fn tabulate_pending(pending: &Vec<PendingRegistration>, nio: &NetspaceIo) -> String {
+		let mut table = Table::new();
+		table.add_row(row!["_spring_", "_host_", "_address_", "_role_", "_service_", "_requested_"]);
+
+		for p in pending {
+			let (host, role, service) = match nio.gsn_node_by_springname(&p.springname) {
+				Ok(n) => (n.hostname().to_string(), format!("{}", n.role()), format!("{}", n.service())),
+				Err(_) => ("?".to_string(), "?".to_string(), "?".to_string())
+			};
+
+			table.add_row(Row::new(vec![
+							Cell::new(&p.springname),
+							Cell::new(&host),
+							Cell::new(&p.address),
+							Cell::new(&role),
+							Cell::new(&service),
+							Cell::new(&p.requested)
+							]));
+		}
+
+		output::table(table)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use management::ManagementZone;
+
+	macro_rules! unwrap_some {
+		($chk:expr) => (
+			match $chk {
+						Some(s) => s,
+						_ => panic!("Unwrapping a None")
+			})
+	}
+
+	#[test]
+	fn ts_registration_view_all_p() {
+		let mz = unwrap_some!(ManagementZone::from_str("registration view all"));
+		let rz : RegistrationZone = extract_zone_registration!(mz);
+		assert_eq!(rz.action, RegistrationAction::View);
+		assert_eq!(rz.op, RegistrationOperand::All);
+	}
+
+	#[test]
+	fn ts_registration_approve_node_p() {
+		let mz = unwrap_some!(ManagementZone::from_str("reg approve springname foo"));
+		let rz : RegistrationZone = extract_zone_registration!(mz);
+		assert_eq!(rz.action, RegistrationAction::Approve);
+		assert_eq!(rz.op, RegistrationOperand::Node("foo".to_string()));
+	}
+
+	#[test]
+	fn ts_registration_reject_f() {
+		assert_eq!(ManagementZone::from_str("registration reject"), None);
+		assert_eq!(ManagementZone::from_str("registration reject springname"), None);
+	}
+}
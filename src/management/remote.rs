@@ -0,0 +1,247 @@
+extern crate openssl;
+
+use std::fs::File;
+use std::io::prelude::*;
+use std::net::TcpStream;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize,Ordering};
+use std::time::{Duration,Instant};
+
+use self::openssl::nid;
+use self::openssl::pkey::PKey;
+use self::openssl::ssl::{SslMethod,SslAcceptor,SslAcceptorBuilder,SslStream,SSL_VERIFY_PEER};
+use self::openssl::x509::X509;
+
+use netspace::{self,NetspaceIo};
+use config::{NodeConfig,SharedConfig};
+use throttle::ThrottleLimits;
+
+use super::{ManagementInstance,IDLE_SECS,hello,check_token};
+use super::auth::Caller;
+use super::wire::{self,Status,WireError};
+
+/*
+ * Remote management
+ *
+ * An optional TLS listener taking the same framed sessions as the
+ * management socket, for running several primaries from one place.
+ * It is off unless node.conf has `remote_man=on`, binds to
+ * `remote_man_address` and serves `remote_man_cert` with
+ * `remote_man_key`. With `remote_man_ca` set, client certificates
+ * signed by that CA are asked for and verified; callers without one
+ * authenticate with a token. See auth.rs for how either gets a role.
+ * Only framed sessions are taken, never the old single command.
+ *
+ * Bans are checked on one connection held by the listener and a
+ * session only opens the netspace once its caller has a role, which
+ * it must get within ADMIT_SECS. At most MAX_SESSIONS are served at
+ * once; others are dropped.
+ */
+
+/// Where the listener binds unless `remote_man_address` says otherwise
+pub const REMOTE_ADDRESS : &'static str = "0.0.0.0:55301";
+
+/// Most remote sessions served at once
+const MAX_SESSIONS : usize = 8;
+
+/// Seconds a caller has from connecting to having a role, so callers
+/// that never authenticate cannot sit on every session for IDLE_SECS
+const ADMIT_SECS : u64 = 20;
+
+/// A place among the remote sessions, given back when dropped
+pub struct Session {
+	open: Arc<AtomicUsize>,
+}
+
+impl Session {
+	/// Take a place if fewer than MAX_SESSIONS are open
+	pub fn take(open: &Arc<AtomicUsize>) -> Option<Session> {
+		if open.fetch_add(1, Ordering::SeqCst) >= MAX_SESSIONS {
+			open.fetch_sub(1, Ordering::SeqCst);
+			return None
+		}
+
+		Some(Session { open: open.clone() })
+	}
+}
+
+impl Drop for Session {
+	fn drop(&mut self) {
+		self.open.fetch_sub(1, Ordering::SeqCst);
+	}
+}
+
+pub fn enabled(config: &NodeConfig) -> bool {
+	match config.setting("remote_man") {
+		Some(ref v) => v == "on",
+		None => false
+	}
+}
+
+fn read_pem(config: &NodeConfig, key: &str) -> Result<Vec<u8>,String> {
+	let path = try!(config.setting(key).ok_or(format!("{} is not set", key)));
+
+	let mut pem = Vec::new();
+	try!(File::open(&path).and_then(|mut f| f.read_to_end(&mut pem)).map_err(|e| format!("unable to read {} ({})", path, e)));
+	Ok(pem)
+}
+
+pub fn acceptor(config: &NodeConfig) -> Result<SslAcceptor,String> {
+	let cert = try!(X509::from_pem(&try!(read_pem(config, "remote_man_cert"))).map_err(|e| format!("bad certificate ({})", e)));
+	let key = try!(PKey::private_key_from_pem(&try!(read_pem(config, "remote_man_key"))).map_err(|e| format!("bad private key ({})", e)));
+
+	let mut builder = try!(SslAcceptorBuilder::mozilla_intermediate(SslMethod::tls(), &key, &cert, Vec::<X509>::new())
+							.map_err(|e| format!("unable to set up TLS ({})", e)));
+
+	if let Some(ca) = config.setting("remote_man_ca") {
+		let ctx = builder.builder_mut();
+		try!(ctx.set_ca_file(&ca).map_err(|e| format!("unable to read {} ({})", ca, e)));
+		ctx.set_verify(SSL_VERIFY_PEER);
+	}
+
+	Ok(builder.build())
+}
+
+fn common_name(stream: &SslStream<TcpStream>) -> Option<String> {
+	let cert = match stream.ssl().peer_certificate() {
+		Some(c) => c,
+		None => return None
+	};
+
+	let cn = cert.subject_name().entries_by_nid(nid::COMMONNAME).next()
+				.and_then(|e| e.data().as_utf8().ok().map(|s| s.to_string()));
+	cn
+}
+
+pub fn remote_handler(stream: TcpStream, acceptor: &SslAcceptor, shared: SharedConfig) {
+	let address = match stream.peer_addr() {
+		Ok(a) => a,
+		Err(_) => return
+	};
+	let ip = format!("{}", address.ip());
+
+	let started = Instant::now();
+	let _ = stream.set_read_timeout(Some(Duration::new(ADMIT_SECS, 0)));
+
+	let mut stream = match acceptor.accept(stream) {
+		Ok(s) => s,
+		Err(e) => {
+			log_warn!("management", "TLS handshake with {} failed ({:?})", address, e);
+			return
+		}
+	};
+
+	let caller = Caller::remote(&format!("{}", address), common_name(&stream), &shared.current());
+	log_info!("management", "Connection from {}", caller);
+
+	let mut lead = [0;4];
+	match stream.read_exact(&mut lead) {
+		Ok(_) if &lead == wire::MAGIC => { },
+		_ => {
+			log_warn!("management", "{} did not open a framed session", address);
+			return
+		}
+	}
+
+	let admitted = match hello(&mut stream) {
+		Ok(true) => admit(&mut stream, caller, &shared, &ip, started),
+		Ok(false) => return,
+		Err(e) => Err(e)
+	};
+
+	let caller = match admitted {
+		Ok(Some(c)) => c,
+		Ok(None) => return,
+		Err(e) => {
+			log_warn!("management", "Remote session with {} ended early ({:?})", address, e);
+			return
+		}
+	};
+
+	// Authenticated callers get the same time to think as local ones
+	let _ = stream.get_ref().set_read_timeout(Some(Duration::new(IDLE_SECS, 0)));
+
+	let nio = match NetspaceIo::attach(netspace::database_path(shared.current().live_test)) {
+		Ok(nio) => nio,
+		Err(e) => {
+			log_error!("management", "Unable to open the netspace for {} ({:?})", address, e);
+			return
+		}
+	};
+
+	nio.set_actor(&format!("management {}", ip));
+
+	let mi = ManagementInstance::new(&nio, &shared, caller).from_address(&ip);
+
+	if let Err(e) = mi.serve(&mut stream) {
+		log_warn!("management", "Remote session with {} ended early ({:?})", address, e);
+	}
+}
+
+/// Take nothing but tokens from a caller without a role until one
+/// is good; `None` if the session ends, the address is banned or
+/// ADMIT_SECS since `started` pass first
+fn admit<S: Read + Write>(stream: &mut S, mut caller: Caller, shared: &SharedConfig, ip: &str, started: Instant) -> Result<Option<Caller>,WireError> {
+	while caller.role.is_none() {
+		if started.elapsed() >= Duration::new(ADMIT_SECS, 0) {
+			log_warn!("management", "{} did not authenticate in time", ip);
+			return Ok(None)
+		}
+
+		let command = match try!(wire::read_command(stream)) {
+			Some(c) => c,
+			None => return Ok(None)
+		};
+
+		if !command.starts_with("auth ") {
+			try!(wire::write_reply(stream, Status::Denied, "Error: authenticate with a token first\n"));
+			continue
+		}
+
+		let config = shared.current();
+		match check_token(&mut caller, &command[5..], &config) {
+			Ok(s) => try!(wire::write_reply(stream, Status::Ok, &s)),
+			Err(s) => {
+				try!(wire::write_reply(stream, Status::Denied, &s));
+
+				// Bad tokens are rare enough to open the netspace for
+				let banned = NetspaceIo::attach(netspace::database_path(config.live_test))
+								.and_then(|nio| nio.throttle_failure(ip, &ThrottleLimits::from_config(&config)))
+								.unwrap_or(false);
+				if banned { return Ok(None) }
+			}
+		}
+	}
+
+	Ok(Some(caller))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::collections::HashMap;
+	use std::io::Cursor;
+	use config::Config;
+
+	#[test]
+	fn ts_remote_sessions_capped_p() {
+		let open = Arc::new(AtomicUsize::new(0));
+		let held : Vec<Session> = (0..MAX_SESSIONS).filter_map(|_| Session::take(&open)).collect();
+		assert_eq!(held.len(), MAX_SESSIONS);
+		assert!(Session::take(&open).is_none());
+
+		drop(held);
+		assert_eq!(open.load(Ordering::SeqCst), 0);
+		assert!(Session::take(&open).is_some());
+	}
+
+	#[test]
+	fn ts_remote_admit_deadline_f() {
+		let shared = SharedConfig::new(Config::with_kvs(HashMap::new()));
+		let caller = Caller { peer: None, role: None, remote: None };
+		let mut stream = Cursor::new(Vec::new());
+
+		let started = Instant::now() - Duration::new(ADMIT_SECS, 0);
+		assert!(admit(&mut stream, caller, &shared, "192.168.1.2", started).unwrap().is_none());
+	}
+}
@@ -2,9 +2,10 @@ use std::str::Split;
 
 use ::netservice;
 use ::netservice::cert;
+use ::netservice::sync;
 
 use ::protocol::Svr;
-use ::management::ManagedService;
+use ::management::{ManagedService,ZoneResult};
 
 
 #[derive(Clone, PartialEq, Debug)]
@@ -95,7 +96,7 @@ impl ServiceZone {
 		})
 	}
 	
-	pub fn process(sz: ServiceZone, svr: &Svr) -> Option<String> {
+	pub fn process(sz: ServiceZone, svr: &Svr) -> Option<ZoneResult> {
 		Some(match sz.action {
 			ServiceAction::Init => ServiceZoneModel::init(sz.op1),
 			ServiceAction::Manage => ServiceZoneModel::manage(sz.op1, sz.op2, svr)
@@ -106,38 +107,44 @@ impl ServiceZone {
 struct ServiceZoneModel;
 
 impl ServiceZoneModel {
-	pub fn init(op: ServiceOperand) -> String {
+	pub fn init(op: ServiceOperand) -> ZoneResult {
 		match op {
 			ServiceOperand::Module(m) => ServiceZoneModel::module_init(m),
-			_ => format!("Init operation is not supported by target filter")
+			_ => Err(format!("Error: Init operation is not supported by target filter"))
 		}
 		
 	}
 	
-	fn module_init(module: netservice::Module) -> String {
+	fn module_init(module: netservice::Module) -> ZoneResult {
 		match module {
 			netservice::Module::Cert => {
 				cert::manager::CertManagementInterface::new().init()
+			},
+			netservice::Module::Sync => {
+				sync::manager::SyncManagementInterface::new().init()
 			}
 		}
 	}
 	
-	fn manage(target: ServiceOperand, pass: ServiceOperand, svr: &Svr) -> String {
+	fn manage(target: ServiceOperand, pass: ServiceOperand, svr: &Svr) -> ZoneResult {
 		match target {
 			ServiceOperand::Module(m) => ServiceZoneModel::module_manage(m, pass, svr),
-			_ => format!("Manage operation is not supported by target filter")
+			_ => Err(format!("Error: Manage operation is not supported by target filter"))
 		}
 	}
 	
-	fn module_manage(module: netservice::Module, pass: ServiceOperand, svr: &Svr) -> String {
+	fn module_manage(module: netservice::Module, pass: ServiceOperand, svr: &Svr) -> ZoneResult {
 		let v = match pass {
 			ServiceOperand::Pass(p) => p,
-			_ => return "Bad operand for Manage operation".to_string()
+			_ => return Err("Error: Bad operand for Manage operation".to_string())
 		};
 
 		match module {
 			netservice::Module::Cert => {
 				cert::manager::CertManagementInterface::new().hook(&v, svr)
+			},
+			netservice::Module::Sync => {
+				sync::manager::SyncManagementInterface::new().hook(&v, svr)
 			}
 		}		
 	}
@@ -174,4 +181,13 @@ mod tests {
 		assert_eq!(sz.action, ServiceAction::Init);
 		assert_eq!(sz.op1, ServiceOperand::Module(netservice::Module::Cert));
 	}
+	
+	#[test]
+	fn ts_service_manage_module_sync_p() {
+		let mz = unwrap_some!(ManagementZone::from_str("service manage module sync : view"));
+		let sz : ServiceZone = extract_zone_service!(mz);
+		assert_eq!(sz.action, ServiceAction::Manage);
+		assert_eq!(sz.op1, ServiceOperand::Module(netservice::Module::Sync));
+		assert_eq!(sz.op2, ServiceOperand::Pass(vec!["view".to_string()]));
+	}
 }
\ No newline at end of file
@@ -0,0 +1,229 @@
+use std::str::Split;
+use std::fs::File;
+use std::io::prelude::*;
+
+use netspace::*;
+use snapshot::{Snapshot,ImportMode,diff,apply};
+
+use management::ZoneResult;
+
+#[macro_export]
+macro_rules! extract_zone_snapshot {
+	($e: expr) => (
+		match $e {
+			ManagementZone::Snapshot(s) => s,
+			e => panic!("extract_zone_snapshot -- Unexpected value: {:?}", e)
+		}
+	)
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum SnapshotAction {
+	Export,
+	Import,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum SnapshotOperand {
+	None,
+	Mode(ImportMode),
+	File(String),
+	DryRun,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct SnapshotZone {
+	action: SnapshotAction,
+	ops: Vec<SnapshotOperand>,
+}
+
+impl SnapshotZone {
+	pub fn new(action: SnapshotAction, ops: Vec<SnapshotOperand>) -> SnapshotZone {
+		SnapshotZone {
+			action: action,
+			ops: ops,
+		}
+	}
+
+	pub fn action(&self) -> SnapshotAction {
+		self.action
+	}
+
+	/// Whether the request writes to a file rather than replying
+	pub fn writes_file(&self) -> bool {
+		self.action == SnapshotAction::Export
+			&& self.ops.iter().any(|o| match *o { SnapshotOperand::File(_) => true, _ => false })
+	}
+
+	pub fn from_str(msg: &str) -> Option<SnapshotZone> {
+		if msg.len() == 0 { return None; }
+
+		let mut atom = msg.split(" ");
+
+		let action = match atom.next() {
+			Some("exp") | Some("export") => SnapshotAction::Export,
+			Some("imp") | Some("import") => SnapshotAction::Import,
+			_ => return None,
+		};
+
+		let mut ops = Vec::new();
+		loop {
+			match cascade_none_nowrap!(SnapshotZone::extract_operand(&mut atom)) {
+				SnapshotOperand::None => break,
+				op => ops.push(op)
+			}
+		}
+
+		// An import needs to know where from and how
+		if action == SnapshotAction::Import {
+			if !ops.iter().any(|o| match *o { SnapshotOperand::File(_) => true, _ => false }) { return None }
+			if !ops.iter().any(|o| match *o { SnapshotOperand::Mode(_) => true, _ => false }) { return None }
+		}
+
+		Some(SnapshotZone::new(action, ops))
+	}
+
+	fn extract_operand(atom: &mut Split<&str>) -> Option<SnapshotOperand> {
+
+		Some(match atom.next() {
+			Some("merge") =>
+						SnapshotOperand::Mode(ImportMode::Merge),
+
+			Some("replace") =>
+						SnapshotOperand::Mode(ImportMode::Replace),
+
+			Some("file") =>
+						SnapshotOperand::File(
+							cascade_none_nowrap!(atom.next()).to_string()
+						),
+
+			Some("dryrun") =>
+						SnapshotOperand::DryRun,
+
+			_ => SnapshotOperand::None,
+		})
+	}
+
+	pub fn process(sz: SnapshotZone, nio: &NetspaceIo) -> Option<ZoneResult> {
+		match sz.action {
+			SnapshotAction::Export => SnapshotZoneModel::export(sz.ops, nio),
+			SnapshotAction::Import => SnapshotZoneModel::import(sz.ops, nio),
+		}
+	}
+}
+
+struct SnapshotZoneModel;
+
+impl SnapshotZoneModel {
+	pub fn export(ops: Vec<SnapshotOperand>, nio: &NetspaceIo) -> Option<ZoneResult> {
+		let snapshot = match Snapshot::take(nio) {
+			Ok(s) => s,
+			Err(e) => return Some(Err(format!("Error: unable to read the netspace ({:?})\n", e)))
+		};
+
+		for op in ops {
+			match op {
+				SnapshotOperand::File(path) => {
+					return Some(match File::create(&path).and_then(|mut f| f.write_all(snapshot.to_string().as_bytes())) {
+						Ok(_) => Ok(format!("Exported {} nodes, {} geotop roots, {} tokens and {} metaspace entries to {}\n",
+										snapshot.netspace.len(), snapshot.geotop.len(),
+										snapshot.tokens.len(), snapshot.metaspace.len(), path)),
+						Err(e) => Err(format!("Error: unable to write {} ({})\n", path, e))
+					})
+				},
+				SnapshotOperand::None => return None,
+				_ => { }
+			}
+		}
+
+		Some(Ok(snapshot.to_string()))
+	}
+
+	pub fn import(ops: Vec<SnapshotOperand>, nio: &NetspaceIo) -> Option<ZoneResult> {
+		let mut path = String::new();
+		let mut mode = ImportMode::Merge;
+		let mut dryrun = false;
+
+		for op in ops {
+			match op {
+				SnapshotOperand::File(p) => path = p,
+				SnapshotOperand::Mode(m) => mode = m,
+				SnapshotOperand::DryRun => dryrun = true,
+				SnapshotOperand::None => return None,
+			}
+		}
+
+		let mut contents = String::new();
+		match File::open(&path).and_then(|mut f| f.read_to_string(&mut contents)) {
+			Ok(_) => { },
+			Err(e) => return Some(Err(format!("Error: unable to read {} ({})\n", path, e)))
+		}
+
+		let target = match Snapshot::from_str(&contents) {
+			Ok(s) => s,
+			Err(e) => return Some(Err(format!("Error: {}\n", e)))
+		};
+
+		let current = match Snapshot::take(nio) {
+			Ok(s) => s,
+			Err(e) => return Some(Err(format!("Error: unable to read the netspace ({:?})\n", e)))
+		};
+
+		let changes = diff(&current, &target, mode);
+
+		let mut out = String::new();
+		for change in &changes {
+			out.push_str(&format!("{}\n", change));
+		}
+
+		if dryrun {
+			out.push_str(&format!("Dry run: {} change(s) not applied\n", changes.len()));
+			return Some(Ok(out))
+		}
+
+		Some(match apply(&changes, nio) {
+			Ok(n) => Ok(format!("{}Applied {} change(s)\n", out, n)),
+			Err(e) => Err(format!("{}Error: {}\n", out, e))
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use management::ManagementZone;
+
+	macro_rules! unwrap_some {
+		($chk:expr) => (
+			match $chk {
+						Some(s) => s,
+						_ => panic!("Unwrapping a None")
+			})
+	}
+
+	#[test]
+	fn ts_snapshot_export_p() {
+		let mz = unwrap_some!(ManagementZone::from_str("snapshot export file /tmp/gsn.json"));
+		let sz : SnapshotZone = extract_zone_snapshot!(mz);
+		assert_eq!(sz.action, SnapshotAction::Export);
+		assert_eq!(sz.ops, vec![SnapshotOperand::File("/tmp/gsn.json".to_string())]);
+	}
+
+	#[test]
+	fn ts_snapshot_import_dryrun_p() {
+		let mz = unwrap_some!(ManagementZone::from_str("snapshot import replace file /tmp/gsn.json dryrun"));
+		let sz : SnapshotZone = extract_zone_snapshot!(mz);
+		assert_eq!(sz.action, SnapshotAction::Import);
+		assert_eq!(sz.ops, vec![
+			SnapshotOperand::Mode(ImportMode::Replace),
+			SnapshotOperand::File("/tmp/gsn.json".to_string()),
+			SnapshotOperand::DryRun,
+		]);
+	}
+
+	#[test]
+	fn ts_snapshot_import_f() {
+		assert_eq!(ManagementZone::from_str("snapshot import file /tmp/gsn.json"), None);
+		assert_eq!(ManagementZone::from_str("snapshot import merge"), None);
+	}
+}
@@ -0,0 +1,150 @@
+use metrics::{self,Sample};
+
+use management::{output,ZoneResult};
+
+use prettytable::Table;
+use prettytable::row::Row;
+use prettytable::cell::Cell;
+
+#[macro_export]
+macro_rules! extract_zone_stats {
+	($e: expr) => (
+		match $e {
+			ManagementZone::Stats(s) => s,
+			e => panic!("extract_zone_stats -- Unexpected value: {:?}", e)
+		}
+	)
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum StatsAction {
+	View,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum StatsOperand {
+	All,
+	Requests,
+	Responses,
+	Chain,
+	Multicast,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct StatsZone {
+	action: StatsAction,
+	op: StatsOperand,
+}
+
+impl StatsZone {
+	pub fn new(action: StatsAction, op: StatsOperand) -> StatsZone {
+		StatsZone {
+			action: action,
+			op: op,
+		}
+	}
+
+	pub fn from_str(msg: &str) -> Option<StatsZone> {
+		if msg.len() == 0 { return None; }
+
+		let mut atom = msg.split(" ");
+
+		let action = match atom.next() {
+			Some("view") => StatsAction::View,
+			_ => return None,
+		};
+
+		let op = match atom.next() {
+			None | Some("all") => StatsOperand::All,
+			Some("requests") => StatsOperand::Requests,
+			Some("responses") => StatsOperand::Responses,
+			Some("chain") => StatsOperand::Chain,
+			Some("multicast") => StatsOperand::Multicast,
+			_ => return None
+		};
+
+		match atom.next() {
+			None => Some(StatsZone::new(action, op)),
+			_ => None
+		}
+	}
+
+	pub fn process(sz: StatsZone) -> Option<ZoneResult> {
+		match sz.action {
+			StatsAction::View => StatsZoneModel::view(sz.op),
+		}
+	}
+}
+
+struct StatsZoneModel;
+
+impl StatsZoneModel {
+	pub fn view(op: StatsOperand) -> Option<ZoneResult> {
+		let prefix = match op {
+			StatsOperand::All => "dvsp_",
+			StatsOperand::Requests => "dvsp_request",
+			StatsOperand::Responses => "dvsp_responses",
+			StatsOperand::Chain => "dvsp_chain",
+			StatsOperand::Multicast => "dvsp_multicast",
+		};
+
+		let samples : Vec<Sample> = metrics::samples().into_iter().filter(|s| s.name.starts_with(prefix)).collect();
+
+		if samples.is_empty() {
+			return Some(Ok("No traffic recorded\n".to_string()))
+		}
+
+		Some(Ok(Self::tabulate_samples(&samples)))
+	}
+
+	fn tabulate_samples(samples: &Vec<Sample>) -> String {
+		let mut table = Table::new();
+		table.add_row(row!["_metric_", "_labels_", "_count_", "_mean_ms_"]);
+
+		for s in samples {
+			table.add_row(Row::new(vec![
+							Cell::new(&s.name),
+							Cell::new(&s.labels),
+							Cell::new(&format!("{}", s.count)),
+							Cell::new(&s.mean_ms().map_or("-".to_string(), |m| format!("{:.2}", m)))
+							]));
+		}
+
+		output::table(table)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use management::ManagementZone;
+
+	macro_rules! unwrap_some {
+		($chk:expr) => (
+			match $chk {
+						Some(s) => s,
+						_ => panic!("Unwrapping a None")
+			})
+	}
+
+	#[test]
+	fn ts_stats_view_p() {
+		let mz = unwrap_some!(ManagementZone::from_str("stats view"));
+		let sz : StatsZone = extract_zone_stats!(mz);
+		assert_eq!(sz.action, StatsAction::View);
+		assert_eq!(sz.op, StatsOperand::All);
+	}
+
+	#[test]
+	fn ts_stats_view_chain_p() {
+		let mz = unwrap_some!(ManagementZone::from_str("stats view chain"));
+		let sz : StatsZone = extract_zone_stats!(mz);
+		assert_eq!(sz.op, StatsOperand::Chain);
+	}
+
+	#[test]
+	fn ts_stats_f() {
+		assert_eq!(ManagementZone::from_str("stats view everything"), None);
+		assert_eq!(ManagementZone::from_str("stats reset"), None);
+	}
+}
@@ -0,0 +1,215 @@
+use std::fs::metadata;
+
+use config::{Config,NodeConfig,SharedConfig};
+use lifecycle;
+use netspace;
+use status::{self,Service};
+
+use management::{output,ZoneResult};
+
+use prettytable::Table;
+use prettytable::row::Row;
+use prettytable::cell::Cell;
+
+#[macro_export]
+macro_rules! extract_zone_status {
+	($e: expr) => (
+		match $e {
+			ManagementZone::Status(s) => s,
+			e => panic!("extract_zone_status -- Unexpected value: {:?}", e)
+		}
+	)
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum StatusAction {
+	View,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum StatusOperand {
+	All,
+	Services,
+	Databases,
+	Errors,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct StatusZone {
+	action: StatusAction,
+	op: StatusOperand,
+}
+
+impl StatusZone {
+	pub fn new(action: StatusAction, op: StatusOperand) -> StatusZone {
+		StatusZone {
+			action: action,
+			op: op,
+		}
+	}
+
+	pub fn from_str(msg: &str) -> Option<StatusZone> {
+		if msg.len() == 0 { return None; }
+
+		let mut atom = msg.split(" ");
+
+		let action = match atom.next() {
+			Some("view") => StatusAction::View,
+			_ => return None,
+		};
+
+		let op = match atom.next() {
+			None | Some("all") => StatusOperand::All,
+			Some("services") => StatusOperand::Services,
+			Some("databases") => StatusOperand::Databases,
+			Some("errors") => StatusOperand::Errors,
+			_ => return None
+		};
+
+		match atom.next() {
+			None => Some(StatusZone::new(action, op)),
+			_ => None
+		}
+	}
+
+	pub fn process(sz: StatusZone, config: &SharedConfig) -> Option<ZoneResult> {
+		let config = config.current();
+
+		match sz.action {
+			StatusAction::View => Some(Ok(match sz.op {
+				StatusOperand::All => output::sections(vec![
+											("summary", StatusZoneModel::summary(&config)),
+											("services", StatusZoneModel::services()),
+											("databases", StatusZoneModel::databases(&config)),
+											("errors", StatusZoneModel::errors()),
+										]),
+				StatusOperand::Services => StatusZoneModel::services(),
+				StatusOperand::Databases => StatusZoneModel::databases(&config),
+				StatusOperand::Errors => StatusZoneModel::errors(),
+			}))
+		}
+	}
+}
+
+struct StatusZoneModel;
+
+impl StatusZoneModel {
+	pub fn summary(config: &Config) -> String {
+		let mut table = Table::new();
+		table.add_row(row!["_key_", "_value_"]);
+
+		let threads = status::thread_count().map_or("unknown".to_string(), |n| format!("{}", n));
+
+		let rows = vec![
+			("version", ::SERVER_VERSION.to_string()),
+			("node", format!("{}.{}", config.springname(), config.geosub())),
+			("uptime", Self::duration(status::uptime())),
+			("offline", format!("{}", config.toggle_offline)),
+			("testing", format!("{}", config.live_test)),
+			("threads", threads),
+			("in_flight", format!("{}", lifecycle::in_flight())),
+			("stopping", format!("{}", lifecycle::stopping())),
+		];
+
+		for (k, v) in rows {
+			table.add_row(Row::new(vec![Cell::new(k), Cell::new(&v)]));
+		}
+
+		output::table(table)
+	}
+
+	pub fn services() -> String {
+		let mut table = Table::new();
+		table.add_row(row!["_service_", "_state_"]);
+
+		for s in Service::all() {
+			table.add_row(Row::new(vec![
+							Cell::new(s.name()),
+							Cell::new(match status::is_online(s) { true => "online", false => "offline" })
+							]));
+		}
+
+		output::table(table)
+	}
+
+	pub fn databases(config: &Config) -> String {
+		let mut table = Table::new();
+		table.add_row(row!["_database_", "_bytes_"]);
+
+		let gsn = netspace::database_path(config.live_test);
+
+		for path in vec![gsn, "/var/lib/springdvs/services.db"] {
+			table.add_row(Row::new(vec![
+							Cell::new(path),
+							Cell::new(&metadata(path).map(|m| format!("{}", m.len())).unwrap_or("missing".to_string()))
+							]));
+		}
+
+		output::table(table)
+	}
+
+	pub fn errors() -> String {
+		let errors = status::recent_errors();
+
+		if errors.is_empty() {
+			return "No recent errors\n".to_string()
+		}
+
+		let mut table = Table::new();
+		table.add_row(row!["_at_", "_target_", "_error_"]);
+
+		for e in errors {
+			table.add_row(Row::new(vec![
+							Cell::new(&format!("{}", e.at)),
+							Cell::new(&e.target),
+							Cell::new(&e.msg)
+							]));
+		}
+
+		output::table(table)
+	}
+
+	fn duration(secs: u64) -> String {
+		format!("{}d {:02}:{:02}:{:02}", secs / 86400, (secs % 86400) / 3600, (secs % 3600) / 60, secs % 60)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use management::ManagementZone;
+
+	macro_rules! unwrap_some {
+		($chk:expr) => (
+			match $chk {
+						Some(s) => s,
+						_ => panic!("Unwrapping a None")
+			})
+	}
+
+	#[test]
+	fn ts_status_view_p() {
+		let mz = unwrap_some!(ManagementZone::from_str("status view"));
+		let sz : StatusZone = extract_zone_status!(mz);
+		assert_eq!(sz.action, StatusAction::View);
+		assert_eq!(sz.op, StatusOperand::All);
+	}
+
+	#[test]
+	fn ts_status_view_errors_p() {
+		let mz = unwrap_some!(ManagementZone::from_str("status view errors"));
+		let sz : StatusZone = extract_zone_status!(mz);
+		assert_eq!(sz.op, StatusOperand::Errors);
+	}
+
+	#[test]
+	fn ts_status_duration_p() {
+		assert_eq!(StatusZoneModel::duration(90061), "1d 01:01:01");
+	}
+
+	#[test]
+	fn ts_status_f() {
+		assert_eq!(ManagementZone::from_str("status view threads"), None);
+		assert_eq!(ManagementZone::from_str("status restart"), None);
+	}
+}
@@ -0,0 +1,188 @@
+use std::str::Split;
+
+use netspace::*;
+use throttle::{ThrottleLimits,Throttled};
+
+use management::{output,ZoneResult};
+
+use prettytable::Table;
+use prettytable::row::Row;
+use prettytable::cell::Cell;
+
+#[macro_export]
+macro_rules! extract_zone_throttle {
+	($e: expr) => (
+		match $e {
+			ManagementZone::Throttle(s) => s,
+			e => panic!("extract_zone_throttle -- Unexpected value: {:?}", e)
+		}
+	)
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum ThrottleAction {
+	View,
+	Lift,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum ThrottleOperand {
+	None,
+	All,
+	Address(String),
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct ThrottleZone {
+	action: ThrottleAction,
+	op: ThrottleOperand,
+}
+
+impl ThrottleZone {
+	pub fn new(action: ThrottleAction, op: ThrottleOperand) -> ThrottleZone {
+		ThrottleZone {
+			action: action,
+			op: op,
+		}
+	}
+
+	pub fn action(&self) -> ThrottleAction {
+		self.action
+	}
+
+	pub fn from_str(msg: &str) -> Option<ThrottleZone> {
+		if msg.len() == 0 { return None; }
+
+		let mut atom = msg.split(" ");
+
+		let action = match atom.next() {
+			Some("view") => ThrottleAction::View,
+			Some("lift") => ThrottleAction::Lift,
+			_ => return None,
+		};
+
+		let op = match cascade_none_nowrap!(ThrottleZone::extract_operand(&mut atom)) {
+			ThrottleOperand::None => return None,
+			op => op
+		};
+
+		// Lifting everything at once is too blunt for a ban list
+		if action == ThrottleAction::Lift && op == ThrottleOperand::All { return None }
+
+		Some(ThrottleZone::new(action, op))
+	}
+
+	fn extract_operand(atom: &mut Split<&str>) -> Option<ThrottleOperand> {
+
+		Some(match atom.next() {
+			Some("all") =>
+						ThrottleOperand::All,
+
+			Some("address") =>
+						ThrottleOperand::Address(
+							cascade_none_nowrap!(atom.next()).to_string()
+						),
+
+			_ => ThrottleOperand::None,
+		})
+	}
+
+	pub fn process(tz: ThrottleZone, nio: &NetspaceIo, config: &NodeConfig) -> Option<ZoneResult> {
+		match tz.action {
+			ThrottleAction::View => ThrottleZoneModel::view(tz.op, nio, config),
+			ThrottleAction::Lift => ThrottleZoneModel::lift(tz.op, nio),
+		}
+	}
+}
+
+struct ThrottleZoneModel;
+
+impl ThrottleZoneModel {
+	pub fn view(op: ThrottleOperand, nio: &NetspaceIo, config: &NodeConfig) -> Option<ZoneResult> {
+		let limits = ThrottleLimits::from_config(config);
+
+		let mut throttled = match nio.throttle_listing(&limits) {
+			Ok(v) => v,
+			Err(e) => return Some(Err(format!("Error: unable to read throttled addresses ({:?})\n", e)))
+		};
+
+		match op {
+			ThrottleOperand::All => { },
+			ThrottleOperand::Address(a) => throttled.retain(|t| t.address == a),
+			ThrottleOperand::None => return None,
+		}
+
+		if !limits.enabled {
+			return Some(Ok(output::sections(vec![
+							("notice", "Throttling is off\n".to_string()),
+							("throttled", Self::tabulate_throttled(&throttled)),
+						])))
+		}
+
+		Some(Ok(Self::tabulate_throttled(&throttled)))
+	}
+
+	pub fn lift(op: ThrottleOperand, nio: &NetspaceIo) -> Option<ZoneResult> {
+		let address = match op {
+			ThrottleOperand::Address(a) => a,
+			_ => return None,
+		};
+
+		Some(match nio.throttle_clear(&address) {
+			Ok(_) => Ok(format!("Lifted throttling on {}\n", address)),
+			Err(e) => Err(format!("Error: failed to lift throttling on {} ({:?})\n", address, e))
+		})
+	}
+
+	fn tabulate_throttled(throttled: &Vec<Throttled>) -> String {
+		let mut table = Table::new();
+		table.add_row(row!["_address_", "_reason_", "_count_", "_until_"]);
+
+		for t in throttled {
+			table.add_row(Row::new(vec![
+							Cell::new(&t.address),
+							Cell::new(&t.reason),
+							Cell::new(&format!("{}", t.count)),
+							Cell::new(&t.until)
+							]));
+		}
+
+		output::table(table)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use management::ManagementZone;
+
+	macro_rules! unwrap_some {
+		($chk:expr) => (
+			match $chk {
+						Some(s) => s,
+						_ => panic!("Unwrapping a None")
+			})
+	}
+
+	#[test]
+	fn ts_throttle_view_all_p() {
+		let mz = unwrap_some!(ManagementZone::from_str("throttle view all"));
+		let tz : ThrottleZone = extract_zone_throttle!(mz);
+		assert_eq!(tz.action, ThrottleAction::View);
+		assert_eq!(tz.op, ThrottleOperand::All);
+	}
+
+	#[test]
+	fn ts_throttle_lift_address_p() {
+		let mz = unwrap_some!(ManagementZone::from_str("thr lift address 192.168.1.2"));
+		let tz : ThrottleZone = extract_zone_throttle!(mz);
+		assert_eq!(tz.action, ThrottleAction::Lift);
+		assert_eq!(tz.op, ThrottleOperand::Address("192.168.1.2".to_string()));
+	}
+
+	#[test]
+	fn ts_throttle_lift_f() {
+		assert_eq!(ManagementZone::from_str("throttle lift all"), None);
+		assert_eq!(ManagementZone::from_str("throttle lift address"), None);
+	}
+}
@@ -2,6 +2,8 @@ use std::str::Split;
 
 use netspace::*;
 
+use management::{output,ZoneResult};
+
 use prettytable::Table;
 use prettytable::row::Row;
 use prettytable::cell::Cell;
@@ -47,6 +49,10 @@ impl ValidationZone {
 		}
 	}
 
+	pub fn action(&self) -> ValidationAction {
+		self.action
+	}
+
 	pub fn from_str(msg: &str) -> Option<ValidationZone> {
 		if msg.len() == 0 { return None }
 		
@@ -93,7 +99,7 @@ impl ValidationZone {
 		})
 	}
 	
-	pub fn process(vz: ValidationZone, nio: &Netspace) -> Option<String> {
+	pub fn process(vz: ValidationZone, nio: &NetspaceIo) -> Option<ZoneResult> {
 		match vz.action {
 			ValidationAction::View => ValidationZoneModel::view(vz.op1, nio),
 			ValidationAction::Add => ValidationZoneModel::add(vz.op1, vz.op2, nio),
@@ -106,20 +112,20 @@ struct ValidationZoneModel;
 
 
 impl ValidationZoneModel {
-	pub fn view(op: ValidationOperand, nio: &Netspace) -> Option<String> {
+	pub fn view(op: ValidationOperand, nio: &Netspace) -> Option<ZoneResult> {
 		Some(match op {
 			ValidationOperand::All => {
-				Self::tabulate_tokens(nio.gsn_tokens())
+				Ok(Self::tabulate_tokens(nio.gsn_tokens()))
 			},
 			ValidationOperand::Node(s) => {
-				Self::tabulate_tokens(nio.gsn_token_by_springname(&s))
+				Ok(Self::tabulate_tokens(nio.gsn_token_by_springname(&s)))
 			}
-			e => format!("Error: Unsupported target filter ({:?})", e)
+			e => Err(format!("Error: Unsupported target filter ({:?})", e))
 		})
 		
 	}
 	
-	pub fn add(op1: ValidationOperand, op2: ValidationOperand, nio: &Netspace) -> Option<String> {
+	pub fn add(op1: ValidationOperand, op2: ValidationOperand, nio: &NetspaceIo) -> Option<ZoneResult> {
 		
 		let mut token = "".to_string();
 		let mut springname = "".to_string();
@@ -127,33 +133,35 @@ impl ValidationZoneModel {
 		match op1 {
 			ValidationOperand::Token(s) => token = s,
 			ValidationOperand::Node(s) => springname = s,
-			e => return Some(format!("Error: Invalid operand ({:?})\n", e)),
+			e => return Some(Err(format!("Error: Invalid operand ({:?})\n", e))),
 		}
 		
 		match op2 {
 			ValidationOperand::Token(s) => token = s,
 			ValidationOperand::Node(s) => springname = s,
-			e => return Some(format!("Error: Invalid operand ({:?})\n", e)),
+			e => return Some(Err(format!("Error: Invalid operand ({:?})\n", e))),
 		}
 		
 		if token.len() == 0 || springname.len() == 0 { return None }
 		
-		nio.gsn_add_token(&token, &springname);
-		Some(format!("Added token {} for {}\n", token, springname)) 
+		Some(match nio.try_gsn_add_token(&token, &springname) {
+			Ok(_) => Ok(format!("Added token {} for {}\n", token, springname)),
+			Err(e) => Err(format!("Error: failed to add token ({:?})\n", e))
+		})
 	}
 	
-	pub fn remove(op1: ValidationOperand, nio: &Netspace) -> Option<String> {
+	pub fn remove(op1: ValidationOperand, nio: &NetspaceIo) -> Option<ZoneResult> {
 		Some(match op1 {
-			ValidationOperand::Token(s) => {
-				nio.gsn_remove_token(&s);
-				format!("Removed token {}\n", s)
+			ValidationOperand::Token(s) => match nio.try_gsn_remove_token(&s) {
+				Ok(_) => Ok(format!("Removed token {}\n", s)),
+				Err(e) => Err(format!("Error: failed to remove token ({:?})\n", e))
 			},
-			ValidationOperand::Node(s) => {
-				nio.gsn_remove_token_by_springname(&s);
-				format!("Removed token for {}\n", s)
+			ValidationOperand::Node(s) => match nio.try_gsn_remove_token_by_springname(&s) {
+				Ok(_) => Ok(format!("Removed token for {}\n", s)),
+				Err(e) => Err(format!("Error: failed to remove token ({:?})\n", e))
 			},
 			
-			e => format!("Error: Unsupported target filter ({:?})\n", e)
+			e => Err(format!("Error: Unsupported target filter ({:?})\n", e))
 		})	
 	}
 	
@@ -171,7 +179,7 @@ impl ValidationZoneModel {
 		}
 		
 		
-		format!("{}", table)		
+		output::table(table)		
 	}
 }
 #[cfg(test)]
@@ -0,0 +1,206 @@
+use std::io;
+use std::io::prelude::*;
+
+/*
+ * Management socket framing
+ *
+ * A session opens with a hello from each side: the magic bytes
+ * then a little-endian u16 protocol version. The primary answers
+ * with the version it will speak, never higher than the client's.
+ * After that the client sends any number of commands, each a
+ * little-endian u32 length then the UTF-8 command, and gets back
+ * for each a status byte, a little-endian u32 length and the body.
+ * A zero length command or closing the stream ends the session.
+ *
+ * This file is shared with springctl so it only uses std; each
+ * side uses its own half of it, and the items only one side calls
+ * allow dead code.
+ */
+
+pub const MAGIC : &'static [u8;4] = b"SDVM";
+
+/// Protocol version this build speaks
+pub const VERSION : u16 = 2;
+
+/// Oldest version still accepted in a hello
+#[allow(dead_code)]
+pub const MIN_VERSION : u16 = 2;
+
+/// Largest command or reply body accepted
+pub const MAX_FRAME : usize = 1 << 20;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Status {
+	Ok,
+	/// The command ran and reported an error
+	Error,
+	/// The command was not understood
+	Unrecognised,
+	/// The frame could not be read as a command
+	BadFrame,
+	/// The client's protocol version is not supported
+	Unsupported,
+	/// The caller's role does not allow the command
+	Denied,
+}
+
+impl Status {
+	pub fn code(&self) -> u8 {
+		match *self {
+			Status::Ok => 0,
+			Status::Error => 1,
+			Status::Unrecognised => 2,
+			Status::BadFrame => 3,
+			Status::Unsupported => 4,
+			Status::Denied => 5,
+		}
+	}
+
+	pub fn from_code(code: u8) -> Option<Status> {
+		Some(match code {
+			0 => Status::Ok,
+			1 => Status::Error,
+			2 => Status::Unrecognised,
+			3 => Status::BadFrame,
+			4 => Status::Unsupported,
+			5 => Status::Denied,
+			_ => return None
+		})
+	}
+}
+
+#[derive(Debug)]
+pub enum WireError {
+	Io(io::Error),
+	BadMagic,
+	TooLarge(usize),
+	NotUtf8,
+	BadStatus(u8),
+}
+
+impl From<io::Error> for WireError {
+	fn from(e: io::Error) -> WireError {
+		WireError::Io(e)
+	}
+}
+
+pub fn u32_le(b: &[u8;4]) -> u32 {
+	(b[0] as u32) | (b[1] as u32) << 8 | (b[2] as u32) << 16 | (b[3] as u32) << 24
+}
+
+pub fn le_u32(n: u32) -> [u8;4] {
+	[n as u8, (n >> 8) as u8, (n >> 16) as u8, (n >> 24) as u8]
+}
+
+pub fn write_hello<W: Write>(w: &mut W, version: u16) -> io::Result<()> {
+	try!(w.write_all(MAGIC));
+	w.write_all(&[version as u8, (version >> 8) as u8])
+}
+
+/// Read the version from a hello whose magic has already been read
+pub fn read_version<R: Read>(r: &mut R) -> Result<u16,WireError> {
+	let mut b = [0;2];
+	try!(r.read_exact(&mut b));
+	Ok(b[0] as u16 | (b[1] as u16) << 8)
+}
+
+#[allow(dead_code)]
+pub fn read_hello<R: Read>(r: &mut R) -> Result<u16,WireError> {
+	let mut magic = [0;4];
+	try!(r.read_exact(&mut magic));
+	if &magic != MAGIC { return Err(WireError::BadMagic) }
+	read_version(r)
+}
+
+pub fn read_body<R: Read>(r: &mut R, len: usize) -> Result<String,WireError> {
+	if len > MAX_FRAME { return Err(WireError::TooLarge(len)) }
+
+	let mut body = vec![0; len];
+	try!(r.read_exact(&mut body));
+	String::from_utf8(body).map_err(|_| WireError::NotUtf8)
+}
+
+/// The next command, `None` when the client ends the session
+#[allow(dead_code)]
+pub fn read_command<R: Read>(r: &mut R) -> Result<Option<String>,WireError> {
+	let mut len = [0;4];
+	match r.read_exact(&mut len) {
+		Ok(_) => { },
+		Err(ref e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+		Err(e) => return Err(WireError::Io(e)),
+	}
+
+	match u32_le(&len) as usize {
+		0 => Ok(None),
+		n => read_body(r, n).map(Some)
+	}
+}
+
+#[allow(dead_code)]
+pub fn write_command<W: Write>(w: &mut W, command: &str) -> io::Result<()> {
+	try!(w.write_all(&le_u32(command.len() as u32)));
+	w.write_all(command.as_bytes())
+}
+
+#[allow(dead_code)]
+pub fn write_reply<W: Write>(w: &mut W, status: Status, body: &str) -> io::Result<()> {
+	try!(w.write_all(&[status.code()]));
+	try!(w.write_all(&le_u32(body.len() as u32)));
+	try!(w.write_all(body.as_bytes()));
+	w.flush()
+}
+
+#[allow(dead_code)]
+pub fn read_reply<R: Read>(r: &mut R) -> Result<(Status,String),WireError> {
+	let mut head = [0;5];
+	try!(r.read_exact(&mut head));
+
+	let status = match Status::from_code(head[0]) {
+		Some(s) => s,
+		None => return Err(WireError::BadStatus(head[0]))
+	};
+
+	let len = u32_le(&[head[1], head[2], head[3], head[4]]) as usize;
+	Ok((status, try!(read_body(r, len))))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::io::Cursor;
+
+	#[test]
+	fn ts_wire_command_p() {
+		let mut buf = Vec::new();
+		write_hello(&mut buf, VERSION).unwrap();
+		write_command(&mut buf, "status view").unwrap();
+		assert_eq!(&buf[6..10], &[11, 0, 0, 0]);
+
+		let mut r = Cursor::new(buf);
+		assert_eq!(read_hello(&mut r).unwrap(), VERSION);
+		assert_eq!(read_command(&mut r).unwrap(), Some("status view".to_string()));
+		assert_eq!(read_command(&mut r).unwrap(), None);
+	}
+
+	#[test]
+	fn ts_wire_reply_p() {
+		let mut buf = Vec::new();
+		write_reply(&mut buf, Status::Error, "Error: no rule 4\n").unwrap();
+
+		let (status, body) = read_reply(&mut Cursor::new(buf)).unwrap();
+		assert_eq!(status, Status::Error);
+		assert_eq!(body, "Error: no rule 4\n");
+	}
+
+	#[test]
+	fn ts_wire_frame_f() {
+		let mut r = Cursor::new(le_u32(MAX_FRAME as u32 + 1).to_vec());
+		assert!(match read_command(&mut r) { Err(WireError::TooLarge(_)) => true, _ => false });
+
+		let mut r = Cursor::new(vec![2, 0, 0, 0, 0xff, 0xfe]);
+		assert!(match read_command(&mut r) { Err(WireError::NotUtf8) => true, _ => false });
+
+		let mut r = Cursor::new(b"XXXX\x02\x00".to_vec());
+		assert!(match read_hello(&mut r) { Err(WireError::BadMagic) => true, _ => false });
+	}
+}
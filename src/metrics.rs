@@ -0,0 +1,243 @@
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::Instant;
+
+/*
+ * Traffic metrics
+ *
+ * Counters and latency histograms kept in memory for the life of
+ * the process, shared by every service thread. They are read by
+ * the `stats` management zone and rendered as a Prometheus text
+ * document for the local HTTP exporter in `service::MetricsHttp`.
+ * Labels taken from springnames are bounded so a flood of made up
+ * names cannot grow the registry without limit.
+ */
+
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub enum Kind {
+	Counter,
+	Histogram,
+}
+
+/// Every metric the primary keeps, with its help text
+pub const METRICS : &'static [(&'static str, Kind, &'static str)] = &[
+	("dvsp_requests_total", Kind::Counter, "Protocol requests handled, by command"),
+	("dvsp_request_seconds", Kind::Histogram, "Time to handle a protocol request, by command"),
+	("dvsp_responses_total", Kind::Counter, "Protocol responses sent, by response code"),
+	("dvsp_chain_requests_total", Kind::Counter, "Chained resolution requests, by target and result"),
+	("dvsp_chain_seconds", Kind::Histogram, "Time for a chained resolution request, by target"),
+	("dvsp_multicast_requests_total", Kind::Counter, "Service multicast requests, by node and result"),
+	("dvsp_multicast_seconds", Kind::Histogram, "Time for a node to answer a service multicast, by node"),
+];
+
+/// Labels whose values come from outside, such as springnames
+const UNBOUNDED : &'static [&'static str] = &["target", "node"];
+
+/// Most series a metric keeps; past this the unbounded labels of a
+/// new series are folded into `other`
+const MAX_SERIES : usize = 200;
+
+/// Upper bounds in seconds of the latency histogram buckets
+const BUCKETS : &'static [f64] = &[0.001, 0.005, 0.01, 0.05, 0.1, 0.5, 1.0, 5.0];
+
+#[derive(Debug,Clone,PartialEq)]
+pub struct Sample {
+	pub name: String,
+	pub labels: String,
+	pub count: u64,
+	pub sum: f64,
+	buckets: Vec<u64>,
+}
+
+impl Sample {
+	fn new(name: &str, labels: &str) -> Sample {
+		Sample {
+			name: name.to_string(),
+			labels: labels.to_string(),
+			count: 0,
+			sum: 0.0,
+			buckets: Vec::new(),
+		}
+	}
+
+	/// Mean observation in milliseconds, for histograms
+	pub fn mean_ms(&self) -> Option<f64> {
+		match (self.buckets.is_empty(), self.count) {
+			(true, _) | (_, 0) => None,
+			_ => Some(self.sum * 1000.0 / self.count as f64)
+		}
+	}
+}
+
+pub struct Registry {
+	series: BTreeMap<(String,String),Sample>,
+}
+
+impl Registry {
+	pub fn new() -> Registry {
+		Registry { series: BTreeMap::new() }
+	}
+
+	fn sample(&mut self, name: &str, labels: &[(&str,&str)]) -> &mut Sample {
+		let mut rendered = render_labels(labels);
+		let key = (name.to_string(), rendered.clone());
+
+		if !self.series.contains_key(&key) && self.series.keys().filter(|k| k.0 == name).count() >= MAX_SERIES {
+			let folded : Vec<(&str,&str)> = labels.iter()
+				.map(|&(k, v)| (k, if UNBOUNDED.contains(&k) { "other" } else { v }))
+				.collect();
+			rendered = render_labels(&folded);
+		}
+
+		let labels = rendered;
+		self.series.entry((name.to_string(), labels.clone())).or_insert_with(|| Sample::new(name, &labels))
+	}
+
+	pub fn count(&mut self, name: &str, labels: &[(&str,&str)]) {
+		self.sample(name, labels).count += 1;
+	}
+
+	pub fn observe(&mut self, name: &str, labels: &[(&str,&str)], secs: f64) {
+		let s = self.sample(name, labels);
+
+		if s.buckets.is_empty() { s.buckets = vec![0; BUCKETS.len()] }
+		for (i, bound) in BUCKETS.iter().enumerate() {
+			if secs <= *bound { s.buckets[i] += 1 }
+		}
+
+		s.count += 1;
+		s.sum += secs;
+	}
+
+	pub fn samples(&self) -> Vec<Sample> {
+		self.series.values().cloned().collect()
+	}
+
+	/// Prometheus text exposition format
+	pub fn render(&self) -> String {
+		let mut out = String::new();
+
+		for &(name, kind, help) in METRICS {
+			out.push_str(&format!("# HELP {} {}\n# TYPE {} {}\n", name, help, name,
+				match kind { Kind::Counter => "counter", Kind::Histogram => "histogram" }));
+
+			for s in self.series.values().filter(|s| s.name == name) {
+				match kind {
+					Kind::Counter => out.push_str(&format!("{}{} {}\n", name, braces(&s.labels), s.count)),
+					Kind::Histogram => {
+						for (i, bound) in BUCKETS.iter().enumerate() {
+							out.push_str(&format!("{}_bucket{} {}\n", name, braces(&join(&s.labels, &format!("le=\"{}\"", bound))), s.buckets[i]));
+						}
+						out.push_str(&format!("{}_bucket{} {}\n", name, braces(&join(&s.labels, "le=\"+Inf\"")), s.count));
+						out.push_str(&format!("{}_sum{} {}\n", name, braces(&s.labels), s.sum));
+						out.push_str(&format!("{}_count{} {}\n", name, braces(&s.labels), s.count));
+					}
+				}
+			}
+		}
+
+		out
+	}
+}
+
+fn render_labels(labels: &[(&str,&str)]) -> String {
+	labels.iter()
+		.map(|&(k, v)| format!("{}=\"{}\"", k, v.replace("\\", "\\\\").replace("\"", "\\\"").replace("\n", "\\n")))
+		.collect::<Vec<String>>()
+		.join(",")
+}
+
+fn join(labels: &str, extra: &str) -> String {
+	match labels.is_empty() {
+		true => extra.to_string(),
+		false => format!("{},{}", labels, extra)
+	}
+}
+
+fn braces(labels: &str) -> String {
+	match labels.is_empty() {
+		true => String::new(),
+		false => format!("{{{}}}", labels)
+	}
+}
+
+process_global!(registry, Mutex<Registry>, Mutex::new(Registry::new()));
+
+pub fn count(name: &str, labels: &[(&str,&str)]) {
+	if let Ok(mut r) = registry().lock() { r.count(name, labels) }
+}
+
+pub fn observe(name: &str, labels: &[(&str,&str)], secs: f64) {
+	if let Ok(mut r) = registry().lock() { r.observe(name, labels, secs) }
+}
+
+pub fn samples() -> Vec<Sample> {
+	match registry().lock() {
+		Ok(r) => r.samples(),
+		Err(_) => Vec::new()
+	}
+}
+
+pub fn render() -> String {
+	match registry().lock() {
+		Ok(r) => r.render(),
+		Err(_) => String::new()
+	}
+}
+
+/// Wall clock time since `start` in seconds
+pub fn since(start: Instant) -> f64 {
+	let d = start.elapsed();
+	d.as_secs() as f64 + d.subsec_nanos() as f64 / 1e9
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn ts_metrics_count_p() {
+		let mut r = Registry::new();
+		r.count("dvsp_requests_total", &[("cmd", "info")]);
+		r.count("dvsp_requests_total", &[("cmd", "info")]);
+		r.count("dvsp_requests_total", &[("cmd", "resolve")]);
+
+		let text = r.render();
+		assert!(text.contains("# TYPE dvsp_requests_total counter\n"));
+		assert!(text.contains("dvsp_requests_total{cmd=\"info\"} 2\n"));
+		assert!(text.contains("dvsp_requests_total{cmd=\"resolve\"} 1\n"));
+	}
+
+	#[test]
+	fn ts_metrics_histogram_p() {
+		let mut r = Registry::new();
+		r.observe("dvsp_chain_seconds", &[("target", "hub")], 0.002);
+		r.observe("dvsp_chain_seconds", &[("target", "hub")], 2.0);
+
+		let text = r.render();
+		assert!(text.contains("dvsp_chain_seconds_bucket{target=\"hub\",le=\"0.001\"} 0\n"));
+		assert!(text.contains("dvsp_chain_seconds_bucket{target=\"hub\",le=\"0.005\"} 1\n"));
+		assert!(text.contains("dvsp_chain_seconds_bucket{target=\"hub\",le=\"+Inf\"} 2\n"));
+		assert!(text.contains("dvsp_chain_seconds_count{target=\"hub\"} 2\n"));
+
+		let s = r.samples();
+		assert_eq!(s[0].mean_ms(), Some(1001.0));
+	}
+
+	#[test]
+	fn ts_metrics_bounded_labels_p() {
+		let mut r = Registry::new();
+		for i in 0..MAX_SERIES + 50 {
+			r.count("dvsp_chain_requests_total", &[("target", &format!("node{}", i)), ("result", "ok")]);
+		}
+
+		let s = r.samples();
+		assert_eq!(s.len(), MAX_SERIES + 1);
+		assert!(r.render().contains("dvsp_chain_requests_total{target=\"other\",result=\"ok\"} 50\n"));
+	}
+
+	#[test]
+	fn ts_metrics_label_escape_p() {
+		assert_eq!(render_labels(&[("node", "a\"b")]), "node=\"a\\\"b\"");
+	}
+}
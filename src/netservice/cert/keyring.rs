@@ -4,124 +4,281 @@ use rustc_serialize::json::{ToJson, Json};
 
 
 use ::netservice::database::{ServiceDatabase,State, Statement,Value,Connection};
+use ::schema;
+use ::netspace::{StorageFailure,step};
+
+macro_rules! cascade_none_nowrap {
+	($opt: expr) => (
+		match $opt {
+			Some(s) => s,
+			_ => return None,
+		}
+	)
+}
 
 pub struct Keyring {
 	db: Connection
 }
 
 impl Keyring {
-	pub fn new() -> Keyring {
-		Keyring {
-			db: ServiceDatabase::new()
-		}
+	pub fn new() -> Result<Keyring,StorageFailure> {
+		Ok(Keyring {
+			db: try!(ServiceDatabase::new())
+		})
+	}
+	
+	/// A keyring over an already open database
+	pub fn with_db(db: Connection) -> Result<Keyring,StorageFailure> {
+		try!(schema::migrate(&db, schema::SERVICES));
+		Ok(Keyring {
+			db: db
+		})
 	}
 	
 	pub fn init() -> bool {
-		let db = ServiceDatabase::new();
-		let mut statement = db.prepare("CREATE TABLE IF NOT EXISTS `certificates`( 
-				`keyid` TEXT, 
-				`name` TEXT, 
-				`email` TEXT, 
-				`sigs` TEXT, 
-				`key` TEXT, 
-				PRIMARY KEY(`keyid`) 
-			);").unwrap();
-		
-		match statement.next() {
-			Ok(_) => true ,
-			Err(_) => false   
+		match ServiceDatabase::new() {
+			Ok(db) => schema::migrate(&db, schema::SERVICES).is_ok(),
+			Err(_) => false
 		}
 	}
 	
-	pub fn import(&self, certificate: &Certificate) -> bool {
+	pub fn import(&self, certificate: &Certificate) -> Result<(),StorageFailure> {
 		
-		let mut statement = self.db.prepare("INSERT OR REPLACE INTO `certificates`
+		let mut statement = try!(self.db.prepare("INSERT OR REPLACE INTO `certificates`
 									(keyid,name,email,sigs,key)
-									VALUES (?,?,?,?,?)").unwrap();
+									VALUES (?,?,?,?,?)"));
 		
-		statement.bind(1, &Value::String( certificate.keyid().to_string() ) ).unwrap();
-		statement.bind(2, &Value::String( certificate.name().to_string() ) ).unwrap();
-		statement.bind(3, &Value::String( certificate.email().to_string() ) ).unwrap();
-		statement.bind(4, &Value::String( certificate.sigs().join(",") ) ).unwrap();
-		statement.bind(5, &Value::String( certificate.armor().to_string() ) ).unwrap();
+		try!(statement.bind(1, &Value::String( certificate.keyid().to_string() ) ));
+		try!(statement.bind(2, &Value::String( certificate.name().to_string() ) ));
+		try!(statement.bind(3, &Value::String( certificate.email().to_string() ) ));
+		try!(statement.bind(4, &Value::String( certificate.sigs().join(",") ) ));
+		try!(statement.bind(5, &Value::String( certificate.armor().to_string() ) ));
 		
-		match statement.next() {
-			Ok(_) => true,
-			Err(_) => false   
-		}
+		try!(step(&mut statement));
+		Ok(())
 	}
 	
-	pub fn listing(&self) -> Vec<Certificate> {
-		let mut statement = self.db.prepare("SELECT * FROM `certificates`").unwrap();
-		let mut v = Vec::new();
-		while let State::Row = statement.next().unwrap() {
-			v.push(self.certifcate_from_row(&statement))
+	/// Import every certificate in a bundle, or none of them if any fails
+	pub fn import_bundle(&self, certificates: &Vec<Certificate>) -> Result<usize,StorageFailure> {
+		try!(self.db.execute("SAVEPOINT `bundle`"));
+		
+		for cert in certificates {
+			if let Err(e) = self.import(cert) {
+				let _ = self.db.execute("ROLLBACK TO `bundle`; RELEASE `bundle`");
+				return Err(e)
+			}
 		}
 		
-		v								
+		try!(self.db.execute("RELEASE `bundle`"));
+		Ok(certificates.len())
+	}
+	
+	pub fn listing(&self) -> Result<Vec<Certificate>,StorageFailure> {
+		let mut statement = try!(self.db.prepare("SELECT * FROM `certificates`"));
+		self.certificates_from_statement(&mut statement)
+	}
+	
+	pub fn with_name(&self, name: &str) -> Result<Option<Certificate>,StorageFailure> {
+		let mut statement = try!(self.db.prepare("SELECT * FROM `certificates`
+											WHERE name=?"));
+		try!(statement.bind(1, &Value::String( name.to_string() ) ));
+		
+		Ok(try!(self.certificates_from_statement(&mut statement)).into_iter().next())
 	}
 	
-	pub fn with_name(&self, name: &str) -> Option<Certificate> {
-		let mut statement = self.db.prepare("SELECT * FROM `certificates`
-											WHERE name=?").unwrap();
-		statement.bind(1, &Value::String( name.to_string() ) ).unwrap();
+	pub fn with_keyid(&self, keyid: &str) -> Result<Option<Certificate>,StorageFailure> {
+		let mut statement = try!(self.db.prepare("SELECT * FROM `certificates`
+											WHERE keyid=?"));
+		try!(statement.bind(1, &Value::String( keyid.to_string() ) ));
 		
+		Ok(try!(self.certificates_from_statement(&mut statement)).into_iter().next())
+	}
+	
+	/// Case insensitive substring match on name, email or keyid
+	pub fn search(&self, query: &str) -> Result<Vec<Certificate>,StorageFailure> {
+		let mut statement = try!(self.db.prepare("SELECT * FROM `certificates`
+											WHERE name LIKE ?1 ESCAPE '\\'
+											OR email LIKE ?1 ESCAPE '\\'
+											OR keyid LIKE ?1 ESCAPE '\\'"));
+		try!(statement.bind(1, &Value::String( format!("%{}%", like_escape(query)) ) ));
 		
-		match statement.next().unwrap() {
-			State::Row => Some(self.certifcate_from_row(&statement)),
-			_ => None
-		}
+		self.certificates_from_statement(&mut statement)
+	}
+	
+	/// Certificates carrying a signature from `keyid`, excluding
+	/// the self-signature on `keyid` itself
+	pub fn signed_by(&self, keyid: &str) -> Result<Vec<Certificate>,StorageFailure> {
+		let mut v = try!(self.listing());
+		v.retain(|c| c.keyid() != keyid && c.sigs().iter().any(|s| s == keyid));
+		Ok(v)
 	}
 	
-	pub fn with_keyid(&self, keyid: &str) -> Option<Certificate> {
-		let mut statement = self.db.prepare("SELECT * FROM `certificates`
-											WHERE keyid=?").unwrap();
-		statement.bind(1, &Value::String( keyid.to_string() ) ).unwrap();
+	/// Certificates whose key has not signed any other certificate
+	/// in the keyring
+	pub fn signing_nothing(&self) -> Result<Vec<Certificate>,StorageFailure> {
+		let all = try!(self.listing());
+		let mut v = Vec::new();
 		
+		for cert in &all {
+			if !Keyring::signs_any(cert, &all) { v.push(cert.clone()) }
+		}
 		
-		match statement.next().unwrap() {
-			State::Row => Some(self.certifcate_from_row(&statement)),
-			_ => None
+		Ok(v)
+	}
+	
+	pub fn summary(&self) -> Result<KeyringSummary,StorageFailure> {
+		let all = try!(self.listing());
+		let mut summary = KeyringSummary { total: all.len(), signed: 0, unsigned: 0, signing_nothing: 0 };
+		
+		for cert in &all {
+			match cert.sigs().iter().any(|s| s.len() > 0 && s != cert.keyid()) {
+				true => summary.signed += 1,
+				false => summary.unsigned += 1,
+			}
+			
+			if !Keyring::signs_any(cert, &all) { summary.signing_nothing += 1 }
 		}
+		
+		Ok(summary)
 	}
 	
-	pub fn remove_keyid(&self, keyid: &str) -> bool {
-		let mut statement = self.db.prepare("DELETE FROM `certificates`
-											WHERE keyid=?").unwrap();
-		statement.bind(1, &Value::String( keyid.to_string() ) ).unwrap();
-		match statement.next() {
-			Ok(_) => true,
-			Err(_) => false   
-		}			
+	pub fn remove_keyid(&self, keyid: &str) -> Result<(),StorageFailure> {
+		let mut statement = try!(self.db.prepare("DELETE FROM `certificates`
+											WHERE keyid=?"));
+		try!(statement.bind(1, &Value::String( keyid.to_string() ) ));
+		try!(step(&mut statement));
+		Ok(())
 	}
 
-	pub fn remove_name(&self, name: &str) -> bool {
-		let mut statement = self.db.prepare("DELETE FROM `certificates`
-											WHERE name=?").unwrap();
-		statement.bind(1, &Value::String( name.to_string() ) ).unwrap();
-		match statement.next() {
-			Ok(_) => true,
-			Err(_) => false   
-		}			
+	pub fn remove_name(&self, name: &str) -> Result<(),StorageFailure> {
+		let mut statement = try!(self.db.prepare("DELETE FROM `certificates`
+											WHERE name=?"));
+		try!(statement.bind(1, &Value::String( name.to_string() ) ));
+		try!(step(&mut statement));
+		Ok(())
 	}
-	fn certifcate_from_row(&self, row: &Statement) -> Certificate {
+	
+	fn signs_any(cert: &Certificate, all: &Vec<Certificate>) -> bool {
+		all.iter().any(|c| c.keyid() != cert.keyid() && c.sigs().iter().any(|s| s == cert.keyid()))
+	}
+	
+	fn certificates_from_statement(&self, statement: &mut Statement) -> Result<Vec<Certificate>,StorageFailure> {
+		let mut v = Vec::new();
+		while let State::Row = try!(step(statement)) {
+			v.push(try!(self.certifcate_from_row(&statement)))
+		}
+		
+		Ok(v)
+	}
+	
+	fn certifcate_from_row(&self, row: &Statement) -> Result<Certificate,StorageFailure> {
 
-		let keyid = row.read::<String>(0).unwrap();
-		let name = row.read::<String>(1).unwrap();
-		let email = row.read::<String>(2).unwrap();
-		let sigs_str = row.read::<String>(3).unwrap();
+		let keyid = try!(row.read::<String>(0));
+		let name = try!(row.read::<String>(1));
+		let email = try!(row.read::<String>(2));
+		let sigs_str = try!(row.read::<String>(3));
 		let sigs_split = sigs_str.split(",");
-		let armor = row.read::<String>(4).unwrap();
+		let armor = try!(row.read::<String>(4));
 		
 		let mut sigs = Vec::new();
 		for sig in sigs_split {
 			sigs.push(sig.to_string())
 		}
 		
-		Certificate::new(&name, &email, &keyid, sigs, &armor)
+		Ok(Certificate::new(&name, &email, &keyid, sigs, &armor))
 	}
 }
 
+/// Escape the LIKE wildcards in user input so they match literally
+fn like_escape(s: &str) -> String {
+	s.replace("\\", "\\\\").replace("%", "\\%").replace("_", "\\_")
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct KeyringSummary {
+	pub total: usize,
+	pub signed: usize,
+	pub unsigned: usize,
+	pub signing_nothing: usize,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum BundleFormat {
+	Armor,
+	Json,
+}
+
+impl BundleFormat {
+	pub fn from_str(s: &str) -> Option<BundleFormat> {
+		match s {
+			"armor" | "asc" => Some(BundleFormat::Armor),
+			"json" => Some(BundleFormat::Json),
+			_ => None
+		}
+	}
+}
+
+/// Render a set of certificates as a single bundle
+///
+/// An armor bundle is the concatenation of each certificate's
+/// armored key; a JSON bundle is an array of certificate objects
+/// in the same form served by `cert/pull`
+pub fn bundle(certificates: &Vec<Certificate>, format: BundleFormat) -> String {
+	match format {
+		BundleFormat::Armor => {
+			let v : Vec<&str> = certificates.iter().map(|c| c.armor().trim()).collect();
+			format!("{}\n", v.join("\n\n"))
+		},
+		BundleFormat::Json => {
+			let v : Vec<Json> = certificates.iter().map(|c| c.to_json()).collect();
+			format!("{}\n", Json::Array(v).pretty())
+		}
+	}
+}
+
+/// Split a concatenated armor bundle into individual armored keys
+pub fn split_armor(bundle: &str) -> Vec<String> {
+	let mut v = Vec::new();
+	let mut current = String::new();
+	
+	for line in bundle.lines() {
+		if current.len() == 0 && line.trim().len() == 0 { continue }
+		
+		current.push_str(line);
+		current.push('\n');
+		
+		if line.trim().starts_with("-----END PGP PUBLIC KEY BLOCK") {
+			v.push(current.trim().to_string());
+			current = String::new();
+		}
+	}
+	
+	v
+}
+
+/// Parse a JSON bundle into certificates, failing on the first malformed entry
+pub fn unbundle_json(bundle: &str) -> Result<Vec<Certificate>,String> {
+	let data = match Json::from_str(bundle) {
+		Ok(d) => d,
+		Err(e) => return Err(format!("JSON parse error '{}'", e))
+	};
+	
+	let entries = match data.as_array() {
+		Some(a) => a,
+		None => return Err("Bundle is not an array of certificates".to_string())
+	};
+	
+	let mut v = Vec::new();
+	for (i, entry) in entries.iter().enumerate() {
+		match Certificate::from_json(entry) {
+			Some(c) => v.push(c),
+			None => return Err(format!("Malformed certificate at index {}", i))
+		}
+	}
+	
+	Ok(v)
+}
+
 #[derive(RustcEncodable,Debug,Clone)]
 pub struct Certificate {
 	name: String,
@@ -152,6 +309,40 @@ impl Certificate {
 		}
 	}
 	
+	/// Whether `parsed`, read from this certificate's armor, is the
+	/// key this certificate claims to be
+	pub fn matches_key(&self, parsed: &Certificate) -> bool {
+		self.keyid == parsed.keyid && self.name == parsed.name && self.email == parsed.email
+	}
+	
+	/// Build a certificate from either a bare certificate object or
+	/// one wrapped as `{"cert": {...}}`
+	pub fn from_json(data: &Json) -> Option<Certificate> {
+		let obj = match data.find("cert") {
+			Some(inner) => cascade_none_nowrap!(inner.as_object()),
+			None => cascade_none_nowrap!(data.as_object()),
+		};
+		
+		let mut sigs : Vec<String> = Vec::new();
+		for sig in cascade_none_nowrap!(cascade_none_nowrap!(obj.get("sigs")).as_array()) {
+			sigs.push(cascade_none_nowrap!(sig.as_string()).to_string())
+		}
+		
+		let cert = Certificate::new(
+			cascade_none_nowrap!(cascade_none_nowrap!(obj.get("name")).as_string()),
+			cascade_none_nowrap!(cascade_none_nowrap!(obj.get("email")).as_string()),
+			cascade_none_nowrap!(cascade_none_nowrap!(obj.get("keyid")).as_string()),
+			sigs,
+			cascade_none_nowrap!(cascade_none_nowrap!(obj.get("armor")).as_string())
+		);
+		
+		if cert.name().len() == 0 || cert.keyid().len() == 0 || cert.email().len() == 0 {
+			return None
+		}
+		
+		Some(cert)
+	}
+	
 	pub fn name(&self) -> &str {
 		&self.name
 	}
@@ -222,4 +413,126 @@ impl ToJson for Key {
 		
 		Json::Object(outer)
 	}
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use ::netspace::open_database;
+	
+	fn keyring() -> Keyring {
+		let kr = Keyring::with_db(open_database(":memory:").unwrap()).unwrap();
+		kr.import(&Certificate::new("foo", "foo@example.org", "AB12", vec!["AB12".to_string()], &armor("AAAA"))).unwrap();
+		kr.import(&Certificate::new("bar_baz", "bar@example.org", "CD34", vec!["CD34".to_string(), "AB12".to_string()], &armor("BBBB"))).unwrap();
+		kr.import(&Certificate::new("quxx", "qux@example.org", "EF56", vec!["EF56".to_string()], &armor("CCCC"))).unwrap();
+		kr
+	}
+	
+	#[test]
+	fn ts_keyring_search_p() {
+		let kr = keyring();
+		assert_eq!(kr.search("FOO").unwrap().len(), 1);
+		assert_eq!(kr.search("example.org").unwrap().len(), 3);
+		assert_eq!(kr.search("r_b").unwrap()[0].name(), "bar_baz");
+	}
+	
+	#[test]
+	fn ts_keyring_search_wildcard_f() {
+		let kr = keyring();
+		assert_eq!(kr.search("%").unwrap().len(), 0);
+		assert_eq!(kr.search("qu_x").unwrap().len(), 0);
+		assert_eq!(kr.search("\\").unwrap().len(), 0);
+		assert_eq!(kr.search("nobody").unwrap().len(), 0);
+	}
+	
+	#[test]
+	fn ts_keyring_signers_p() {
+		let kr = keyring();
+		let signed = kr.signed_by("AB12").unwrap();
+		assert_eq!(signed.len(), 1);
+		assert_eq!(signed[0].keyid(), "CD34");
+		
+		let nothing : Vec<String> = kr.signing_nothing().unwrap().iter().map(|c| c.keyid().to_string()).collect();
+		assert_eq!(nothing, vec!["CD34".to_string(), "EF56".to_string()]);
+		
+		assert_eq!(kr.summary().unwrap(), KeyringSummary { total: 3, signed: 1, unsigned: 2, signing_nothing: 2 });
+	}
+	
+	#[test]
+	fn ts_keyring_signers_f() {
+		let kr = keyring();
+		assert!(kr.signed_by("EF56").unwrap().is_empty());
+		assert!(kr.signed_by("0000").unwrap().is_empty());
+	}
+	
+	fn armor(tag: &str) -> String {
+		format!("-----BEGIN PGP PUBLIC KEY BLOCK-----\n\n{}\n-----END PGP PUBLIC KEY BLOCK-----", tag)
+	}
+	
+	#[test]
+	fn ts_keyring_split_armor_p() {
+		let bundle = format!("\n{}\n\n{}\n", armor("AAAA"), armor("BBBB"));
+		let v = split_armor(&bundle);
+		assert_eq!(v.len(), 2);
+		assert_eq!(v[0], armor("AAAA"));
+		assert_eq!(v[1], armor("BBBB"));
+	}
+	
+	#[test]
+	fn ts_keyring_split_armor_f() {
+		let v = split_armor("-----BEGIN PGP PUBLIC KEY BLOCK-----\n\nAAAA\n");
+		assert_eq!(v.len(), 0);
+	}
+	
+	#[test]
+	fn ts_keyring_bundle_json_roundtrip_p() {
+		let certs = vec![
+			Certificate::new("foo", "foo@example.org", "AB12", vec!["AB12".to_string()], &armor("AAAA")),
+			Certificate::new("bar", "bar@example.org", "CD34", vec!["AB12".to_string()], &armor("BBBB")),
+		];
+		
+		let v = unbundle_json(&bundle(&certs, BundleFormat::Json)).unwrap();
+		assert_eq!(v.len(), 2);
+		assert_eq!(v[1].name(), "bar");
+		assert_eq!(v[1].sigs()[0], "AB12");
+		assert_eq!(v[0].armor(), armor("AAAA"));
+	}
+	
+	#[test]
+	fn ts_keyring_import_bundle_p() {
+		let kr = keyring();
+		let certs = vec![Certificate::new("corge", "corge@example.org", "GH78", vec!["GH78".to_string()], &armor("DDDD"))];
+		assert_eq!(kr.import_bundle(&certs).unwrap(), 1);
+		assert!(kr.with_keyid("GH78").unwrap().is_some());
+	}
+	
+	#[test]
+	fn ts_keyring_import_bundle_f() {
+		let kr = keyring();
+		kr.db.execute("CREATE TRIGGER `refuse` BEFORE INSERT ON `certificates`
+						WHEN NEW.keyid = 'BAD0' BEGIN SELECT RAISE(ABORT, 'refused'); END").unwrap();
+		
+		let certs = vec![
+			Certificate::new("corge", "corge@example.org", "GH78", vec!["GH78".to_string()], &armor("DDDD")),
+			Certificate::new("bad", "bad@example.org", "BAD0", vec!["BAD0".to_string()], &armor("EEEE")),
+		];
+		
+		// The certificate before the failure is taken back out
+		assert!(kr.import_bundle(&certs).is_err());
+		assert!(kr.with_keyid("GH78").unwrap().is_none());
+		assert_eq!(kr.listing().unwrap().len(), 3);
+	}
+	
+	#[test]
+	fn ts_keyring_matches_key_f() {
+		let cert = Certificate::new("foo", "foo@example.org", "AB12", vec!["AB12".to_string()], &armor("AAAA"));
+		assert!(cert.matches_key(&cert));
+		assert!(!cert.matches_key(&Certificate::new("foo", "foo@example.org", "CD34", vec![], &armor("AAAA"))));
+		assert!(!cert.matches_key(&Certificate::new("mallory", "foo@example.org", "AB12", vec![], &armor("AAAA"))));
+	}
+	
+	#[test]
+	fn ts_keyring_bundle_json_f() {
+		assert!(unbundle_json("[{\"cert\":{\"name\":\"foo\"}}]").is_err());
+		assert!(unbundle_json("{}").is_err());
+	}
+}
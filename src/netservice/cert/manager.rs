@@ -1,6 +1,8 @@
 use std::slice::Iter;
 use std::str::FromStr;
 
+use ::management::output;
+
 use prettytable::Table;
 use prettytable::row::Row;
 use prettytable::cell::Cell;
@@ -11,10 +13,10 @@ use ::spring_dvs::protocol::{generate_message_service};
 
 use ::protocol::Svr;
 use ::resolution::{ResolutionResult,resolve};
-use ::netservice::cert::keyring::{Keyring,Certificate};
+use ::netservice::cert::keyring::{self,Keyring,Certificate,BundleFormat};
 
 
-use ::management::ManagedService;
+use ::management::{ManagedService,ZoneResult};
 use rustc_serialize::json::{Json};
 
 /* ToDo: Import: If key exists in keyring -- run an import against that key
@@ -29,6 +31,15 @@ macro_rules! cascade_none_nowrap {
 	)
 }
 
+macro_rules! storage_try {
+	($e: expr) => (
+		match $e {
+			Ok(s) => s,
+			Err(e) => return Err(format!("Error: Keyring storage failure ({:?})\n", e)),
+		}
+	)
+}
+
 pub struct CertManagementInterface;
 
 impl CertManagementInterface {
@@ -40,18 +51,18 @@ impl CertManagementInterface {
 
 impl ManagedService for CertManagementInterface {
 	
-	fn init(&self) -> String {
+	fn init(&self) -> ZoneResult {
 		match Keyring::init() {
-			true => format!("Module `certificate` initialised successfully"),
-			false => format!("Module `certificate` initialisation error")
+			true => Ok(format!("Module `certificate` initialised successfully")),
+			false => Err(format!("Error: Module `certificate` initialisation error"))
 		}
 	}
 
-	fn hook(&self, atom: &Vec<String>, svr: &Svr) -> String {
+	fn hook(&self, atom: &Vec<String>, svr: &Svr) -> ZoneResult {
 		
 		let mz : Zone = match Zone::parse(atom) {
 			Some(m) => m,
-			None => return "Unknown or malformed action".to_string(),	
+			None => return Err("Error: Unknown or malformed action".to_string()),	
 		};
 		
 		Zone::process(mz, svr)
@@ -65,6 +76,10 @@ enum Action {
 	Remove,
 	Import,
 	PullReq,
+	Export,
+	Bundle,
+	Search,
+	Count,
 }
 
 impl Action {
@@ -74,6 +89,10 @@ impl Action {
 			"viw" | "view" => Some(Action::View),
 			"rem" | "remove" => Some(Action::Remove),
 			"pr"  | "pullreq" => Some(Action::PullReq),
+			"exp" | "export" => Some(Action::Export),
+			"bun" | "bundle" => Some(Action::Bundle),
+			"sea" | "search" => Some(Action::Search),
+			"cnt" | "count" => Some(Action::Count),
 			_ => None,
 		}
 	}
@@ -87,18 +106,24 @@ enum Operand {
 	Name(String),
 	Key(String),
 	Node(String),
+	Bundle(BundleFormat, String),
+	Query(String),
+	SignedBy(String),
+	SigningNothing,
 }
 
 struct Zone {
 	pub action: Action,
-	pub op1: Operand
+	pub op1: Operand,
+	pub op2: Operand,
 }
 
 impl Zone {
-	pub fn new(action: Action, op1: Operand) -> Zone {
+	pub fn new(action: Action, op1: Operand, op2: Operand) -> Zone {
 		Zone {
 			action: action,
-			op1: op1
+			op1: op1,
+			op2: op2
 		}
 	}
 	pub fn parse(v: &Vec<String>) -> Option<Zone> {
@@ -109,8 +134,13 @@ impl Zone {
 			None => return None,
 		};
 		
+		if action == Action::Search {
+			return Some(Zone::new(action, Operand::Query(Zone::join_iter(&mut atom)), Operand::None))
+		}
+		
 		let op1 = cascade_none_nowrap!(Zone::extract_operand(&mut atom));
-		Some(Zone::new(action, op1))	
+		let op2 = cascade_none_nowrap!(Zone::extract_operand(&mut atom));
+		Some(Zone::new(action, op1, op2))	
 	}
 	
 	fn extract_operand(mut atom: &mut Iter<String>) -> Option<Operand> {
@@ -136,8 +166,17 @@ impl Zone {
 								String::from_str(cascade_none_nowrap!(atom.next())).unwrap()
 						)
 					},
+					"signedby" => {
+						Operand::SignedBy(
+								String::from_str(cascade_none_nowrap!(atom.next())).unwrap()
+						)
+					},
+					"nosigning" => Operand::SigningNothing,
 					"all" => Operand::All,
-					_ => Operand::None,
+					f => match BundleFormat::from_str(f) {
+						Some(format) => Operand::Bundle(format, Zone::join_iter(&mut atom)),
+						None => Operand::None,
+					}
 			},
 			_ => Operand::None,
 		})
@@ -157,12 +196,16 @@ impl Zone {
 		s
 	}
 	
-	pub fn process(mz: Zone, svr: &Svr) -> String {
+	pub fn process(mz: Zone, svr: &Svr) -> ZoneResult {
 		match mz.action {
 			Action::Import => ZoneModel::import(mz.op1),
 			Action::View => ZoneModel::view(mz.op1),
 			Action::Remove => ZoneModel::remove(mz.op1),
-			Action::PullReq => ZoneModel::pullreq(mz.op1, svr)
+			Action::PullReq => ZoneModel::pullreq(mz.op1, svr),
+			Action::Export => ZoneModel::export(mz.op1, mz.op2),
+			Action::Bundle => ZoneModel::bundle(mz.op1),
+			Action::Search => ZoneModel::search(mz.op1),
+			Action::Count => ZoneModel::count(),
 		}	
 	}
 }
@@ -173,79 +216,169 @@ struct ZoneModel;
 impl ZoneModel {
 	
 	// ToDo: If key exists in keyring -- run an import against that key
-	pub fn import(op: Operand) -> String {
+	pub fn import(op: Operand) -> ZoneResult {
 		let key = match op {
 			Operand::Certificate(s) => s,
-			_ => return format!("Import action does not support operand ({:?})", op)
+			_ => return Err(format!("Error: Import action does not support operand ({:?})", op))
 		};
 		
+		let cert = match ZoneModel::pkserv_parse(&key) {
+			Ok(c) => c,
+			Err(e) => return Err(format!("Error: {}\n", e))
+		};
+		
+		let kr = storage_try!(Keyring::new());
+		match kr.import(&cert) {
+			Ok(_) => Ok(format!("Imported certificate for `{}`\n", cert.name())),
+			Err(e) => Err(format!("Error importing certificate `{}` into keyring ({:?})\n", cert.name(), e))
+		}
+	}
+	
+	fn pkserv_parse(key: &str) -> Result<Certificate,String> {
 		let req : String = format!("IMPORT\nPUBLIC {{\n{}\n}}\n", key);
 		let op = Outbound::request(req.as_bytes(), "217.194.223.50", "pkserv.spring-dvs.org", "process");
 		
 		let resp = match op {
-			Some(v) => String::from_utf8(v).unwrap(),
-			None => "Error Importing".to_string() 
+			Some(v) => match String::from_utf8(v) {
+				Ok(s) => s,
+				Err(_) => return Err("Received non UTF-8 response from key server".to_string())
+			},
+			None => return Err("Failed to reach key server".to_string())
 		};
 		
 		let data = match Json::from_str(&resp) {
 			Ok(s) => s,
-			Err(e) => return format!("JSON parse error '{}'", e)
+			Err(e) => return Err(format!("JSON parse error '{}'", e))
 		};
 		
+		match Certificate::from_json(&data) {
+			Some(c) => Ok(c),
+			None => Err("Received malformed certificate".to_string())
+		}
+	}
+	
+	fn export(filter: Operand, format: Operand) -> ZoneResult {
+		let kr = storage_try!(Keyring::new());
+		
+		let certs : Vec<Certificate> = match filter {
+			Operand::All => storage_try!(kr.listing()),
+			Operand::Key(s) => match storage_try!(kr.with_keyid(&s)) {
+				Some(c) => vec![c],
+				None => return Err(format!("Error: Could not find certificate\n"))
+			},
+			Operand::Name(s) => match storage_try!(kr.with_name(&s)) {
+				Some(c) => vec![c],
+				None => return Err(format!("Error: Could not find certificate\n"))
+			},
+			e => return Err(format!("Error: Unknown or unsupported target filter ({:?})\n", e))
+		};
 		
-		let json_cert = data.as_object().unwrap();
+		let format = match format {
+			Operand::None => BundleFormat::Armor,
+			Operand::Bundle(f, _) => f,
+			e => return Err(format!("Error: Unknown or unsupported bundle format ({:?})\n", e))
+		};
 		
-		let json_sigs = json_cert.get("sigs").unwrap().as_array().unwrap();
-		let mut sigs : Vec<String> = Vec::new();
+		Ok(keyring::bundle(&certs, format))
+	}
+	
+	fn bundle(op: Operand) -> ZoneResult {
+		let (format, bundle) = match op {
+			Operand::Bundle(f, s) => (f, s),
+			e => return Err(format!("Error: Bundle action does not support operand ({:?})\n", e))
+		};
 		
-		for sig in json_sigs {
-			sigs.push(sig.as_string().unwrap().to_string())
-		}
-		let cert = Certificate::new(
-			json_cert.get("name").unwrap().as_string().unwrap(),
-			json_cert.get("email").unwrap().as_string().unwrap(), 
-			json_cert.get("keyid").unwrap().as_string().unwrap(),
-			sigs,
-			json_cert.get("armor").unwrap().as_string().unwrap()
-		);
+		let certs : Vec<Certificate> = match format {
+			// Entries are only trusted as far as their armor bears them out
+			BundleFormat::Json => {
+				let claimed = match keyring::unbundle_json(&bundle) {
+					Ok(v) => v,
+					Err(e) => return Err(format!("Error: {}\n", e))
+				};
+				
+				let mut v = Vec::new();
+				for (i, cert) in claimed.iter().enumerate() {
+					match ZoneModel::pkserv_parse(cert.armor()) {
+						Ok(ref c) if !cert.matches_key(c) =>
+							return Err(format!("Error: Certificate at index {} does not match its key; no certificates imported\n", i)),
+						Ok(c) => v.push(c),
+						Err(e) => return Err(format!("Error: {}; no certificates imported\n", e))
+					}
+				}
+				v
+			},
+			BundleFormat::Armor => {
+				let mut v = Vec::new();
+				for key in keyring::split_armor(&bundle) {
+					match ZoneModel::pkserv_parse(&key) {
+						Ok(c) => v.push(c),
+						Err(e) => return Err(format!("Error: {}; no certificates imported\n", e))
+					}
+				}
+				v
+			}
+		};
 		
-		if cert.name().len() == 0 || cert.keyid().len() == 0 || cert.email().len() == 0 {
-			return format!("Error: Received malformed certificate")
+		if certs.is_empty() {
+			return Err(format!("Error: Bundle contains no certificates\n"))
 		}
 		
-		let kr = Keyring::new();
-		match kr.import(&cert) {
-			true => format!("Imported certificate for `{}`\n", cert.name()),
-			false => format!("Error importing certificate `{}` into keyring\n", cert.name())
+		let kr = storage_try!(Keyring::new());
+		match kr.import_bundle(&certs) {
+			Ok(imported) => Ok(format!("Imported {} certificate(s)\n", imported)),
+			Err(e) => Err(format!("Error importing bundle into keyring, no certificates imported ({:?})\n", e))
 		}
 	}
 	
-	fn view(filter: Operand) -> String {
+	fn view(filter: Operand) -> ZoneResult {
 		match filter {
 			Operand::All => ZoneModel::view_listing(),
 			Operand::Key(s) => ZoneModel::view_with_id(&s),
 			Operand::Name(s) => ZoneModel::view_with_name(&s),
-			e => format!("Error: Unknown or unsupported target filter ({:?})\n", e)
+			Operand::SignedBy(s) => Ok(ZoneModel::tabulate(storage_try!(storage_try!(Keyring::new()).signed_by(&s)))),
+			Operand::SigningNothing => Ok(ZoneModel::tabulate(storage_try!(storage_try!(Keyring::new()).signing_nothing()))),
+			e => Err(format!("Error: Unknown or unsupported target filter ({:?})\n", e))
+		}
+	}
+	
+	fn search(query: Operand) -> ZoneResult {
+		match query {
+			Operand::Query(ref q) if q.len() > 0 => Ok(ZoneModel::tabulate(storage_try!(storage_try!(Keyring::new()).search(q)))),
+			e => Err(format!("Error: Search requires a query ({:?})\n", e))
 		}
 	}
 	
-	fn remove(filter: Operand) -> String {
+	fn count() -> ZoneResult {
+		let summary = storage_try!(storage_try!(Keyring::new()).summary());
+		let mut table = Table::new();
+		table.add_row(row!["_total_", "_signed_", "_unsigned_", "_signing nothing_"]);
+		table.add_row(Row::new(vec![
+			Cell::new(&format!("{}", summary.total)),
+			Cell::new(&format!("{}", summary.signed)),
+			Cell::new(&format!("{}", summary.unsigned)),
+			Cell::new(&format!("{}", summary.signing_nothing))
+			]));
+		
+		Ok(output::table(table))
+	}
+	
+	fn remove(filter: Operand) -> ZoneResult {
 		match filter {
 			Operand::Key(s) => ZoneModel::remove_with_id(&s),
 			Operand::Name(s) => ZoneModel::remove_with_name(&s),
-			e => format!("Error: Unknown or unsupported target filter ({:?})\n", e)
+			e => Err(format!("Error: Unknown or unsupported target filter ({:?})\n", e))
 		}
 	}
 	
-	fn pullreq(target: Operand, svr: &Svr) -> String {
+	fn pullreq(target: Operand, svr: &Svr) -> ZoneResult {
 		let node_uri = match target {
 			Operand::Node(n) => n,
-			e => return format!("Error: Unknown or unsupported target filter ({:?})\n", e)
+			e => return Err(format!("Error: Unknown or unsupported target filter ({:?})\n", e))
 		};
 		
-		let node = match resolve(&node_uri, svr.nio, svr.config.as_ref()) {
+		let node = match resolve(&node_uri, svr.nio.netspace(), svr.config.as_ref()) {
 			ResolutionResult::Node(n) => n,
-			_ => return format!("Error: Faild to resolve node\n")
+			_ => return Err(format!("Error: Faild to resolve node\n"))
 		};
 		
 		
@@ -253,20 +386,21 @@ impl ZoneModel {
 		
 		if node.service() == NodeService::Http {
 			match Outbound::request_node(&message, &node) {
-				Some(_) => format!("Made pull request on {}\n", node.springname()),
-				None => format!("Error: Failed to make a pull request\n")
+				Some(_) => Ok(format!("Made pull request on {}\n", node.springname())),
+				None => Err(format!("Error: Failed to make a pull request\n"))
 			}
 		} else {
-			format!("Error: Pull Requests currently only supported on HTTP service layer")
+			Err(format!("Error: Pull Requests currently only supported on HTTP service layer"))
 		}
 	}
 	
-	fn view_listing() -> String {
-		let kr = Keyring::new();
+	fn view_listing() -> ZoneResult {
+		Ok(ZoneModel::tabulate(storage_try!(storage_try!(Keyring::new()).listing())))
+	}
+	
+	fn tabulate(certs: Vec<Certificate>) -> String {
 		let mut table = Table::new();
 		
-		let certs : Vec<Certificate> = kr.listing();
-		
 		ZoneModel::add_listing_headings(&mut table);
 		for cert in certs {
 			table.add_row(Row::new(vec![
@@ -276,44 +410,44 @@ impl ZoneModel {
 				]));
 		}
 		
-		format!("{}", table)
+		output::table(table)
 	}
 	
-	fn view_with_id(keyid: &str) -> String {
-		let kr = Keyring::new();
-		match kr.with_keyid(keyid) {
+	fn view_with_id(keyid: &str) -> ZoneResult {
+		let kr = storage_try!(Keyring::new());
+		match storage_try!(kr.with_keyid(keyid)) {
 			Some(c) => ZoneModel::format_certificate(&c),
-			None =>  format!("Error: Could not find certificate\n")
+			None =>  Err(format!("Error: Could not find certificate\n"))
 		}
 	}
 	
 	
-	fn view_with_name(name: &str) -> String {
-		let kr = Keyring::new();
-		match kr.with_name(name) {
+	fn view_with_name(name: &str) -> ZoneResult {
+		let kr = storage_try!(Keyring::new());
+		match storage_try!(kr.with_name(name)) {
 			Some(c) => ZoneModel::format_certificate(&c),
-			None =>  format!("Error: Could not find certificate\n")
+			None =>  Err(format!("Error: Could not find certificate\n"))
 		}
 	}
 	
-	fn remove_with_id(keyid: &str) -> String {
-		let kr = Keyring::new();
+	fn remove_with_id(keyid: &str) -> ZoneResult {
+		let kr = storage_try!(Keyring::new());
 		match kr.remove_keyid(keyid) {
-			true => format!("Removed certificate"),
-			false =>  format!("Error: Removing certificate failed\n")
+			Ok(_) => Ok(format!("Removed certificate")),
+			Err(e) =>  Err(format!("Error: Removing certificate failed ({:?})\n", e))
 		}		
 	}
 	
-	fn remove_with_name(name: &str) -> String {
-		let kr = Keyring::new();
+	fn remove_with_name(name: &str) -> ZoneResult {
+		let kr = storage_try!(Keyring::new());
 		match kr.remove_name(name) {
-			true => format!("Removed certificate"),
-			false =>  format!("Error: Removing certificate failed\n")
+			Ok(_) => Ok(format!("Removed certificate")),
+			Err(e) =>  Err(format!("Error: Removing certificate failed ({:?})\n", e))
 		}		
 	}
 
-	fn format_certificate(cert: &Certificate) -> String {
-		let kr = Keyring::new();
+	fn format_certificate(cert: &Certificate) -> ZoneResult {
+		let kr = storage_try!(Keyring::new());
 		let mut out = String::new();
 		
 		out.push_str(&format!("Name:\n\t{}\n\n", cert.name()));
@@ -323,15 +457,15 @@ impl ZoneModel {
 		out.push_str(&format!("Signatures:\n"));
 		for sig in cert.sigs() {
 			match kr.with_keyid(sig) {
-				Some(c) =>  out.push_str(&format!("\t{} ({})\n", sig, c.name())),
-				None => out.push_str(&format!("\t{} (unknown)\n", sig))
+				Ok(Some(c)) =>  out.push_str(&format!("\t{} ({})\n", sig, c.name())),
+				_ => out.push_str(&format!("\t{} (unknown)\n", sig))
 			}
 		}
 		
 		
 		out.push_str(&format!("\n\n{}", cert.armor()));
 		
-		out
+		Ok(out)
 	}
 	
 	fn add_listing_headings(table: &mut Table) {
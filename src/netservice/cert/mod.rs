@@ -18,6 +18,7 @@ use self::keyring::{Certificate,Key,Keyring};
 #[derive(RustcEncodable,Debug,Clone)]
 enum Response {
 	Certificate(Certificate),
+	Certificates(Vec<Certificate>),
 	Key(Key)
 }
 
@@ -39,6 +40,7 @@ impl ToJson for CertResponse {
 		
 		d.insert(self.uri.clone(), match &self.response {
 				&Response::Certificate(ref c) => c.to_json(),
+				&Response::Certificates(ref v) => Json::Array(v.iter().map(|c| c.to_json()).collect()),
 				&Response::Key(ref k) => k.to_json(),
 		});
 		
@@ -47,6 +49,8 @@ impl ToJson for CertResponse {
 }
 
 pub fn request(uri: &Uri, svr: &Svr) -> Message {
+	log_debug!("cert", "Request {} from {}", uri.to_string(), svr.sock);
+	
 	match uri.res_index(1) {
 		None => request_certificate(svr),
 		Some("key") => request_key(svr),
@@ -57,6 +61,12 @@ pub fn request(uri: &Uri, svr: &Svr) -> Message {
 			};
 			handle_pull(keyid,svr)
 		},
+		Some("search") => {
+			match uri.query_param("q") {
+				Some(q) => handle_search(&q, svr),
+				None => generate_response_empty_code(::spring_dvs::protocol::Response::MalformedContent)
+			}
+		},
 		Some("pullreq") => generate_response_empty_code(::spring_dvs::protocol::Response::UnsupportedAction),
 		_ => generate_response_empty_code(::spring_dvs::protocol::Response::MalformedContent)
 	}
@@ -84,15 +94,25 @@ fn request_key(svr: &Svr) -> Message {
 }
 
 fn handle_pull(keyid: &str, svr: &Svr) -> Message {
-	let kr = Keyring::new();
-	let cert = match kr.with_keyid(keyid) {
-		None => return service_response(Response::Key(Key::error()), svr),
-		Some(c) => c
+	let cert = match Keyring::new().and_then(|kr| kr.with_keyid(keyid)) {
+		Ok(Some(c)) => c,
+		_ => return service_response(Response::Key(Key::error()), svr),
 	};
 	
 	service_response(Response::Key(Key::new(cert.armor())), svr)	
 }
 
+fn handle_search(query: &str, svr: &Svr) -> Message {
+	if query.len() == 0 {
+		return generate_response_empty_code(::spring_dvs::protocol::Response::MalformedContent)
+	}
+	
+	match Keyring::new().and_then(|kr| kr.search(query)) {
+		Ok(v) => service_response(Response::Certificates(v), svr),
+		Err(_) => generate_response_empty_code(::spring_dvs::protocol::Response::NetspaceError)
+	}
+}
+
 fn service_response(response: Response, svr: &Svr) -> Message {
 	let r = CertResponse::new(format!("{}.{}.uk", svr.config.springname(), svr.config.geosub()), response);
 	generate_response_service_text(&json::encode(&r.to_json()).unwrap())
@@ -1,10 +1,20 @@
 extern crate sqlite;
 pub use self::sqlite::{State,Statement,Value,Connection};
+
+use ::schema;
+use ::netspace::{StorageFailure,open_database};
 	
 pub struct ServiceDatabase;
 
 impl ServiceDatabase {
-	pub fn new() -> Connection {
-		sqlite::open("/var/lib/springdvs/services.db").unwrap()
+	pub fn new() -> Result<Connection,StorageFailure> {
+		let db = try!(open_database("/var/lib/springdvs/services.db"));
+		
+		match schema::migrate(&db, schema::SERVICES) {
+			Ok(_) => { },
+			Err(e) => log_error!("database", "Service schema migration failed ({:?})", e)
+		}
+		
+		Ok(db)
 	}
 }
\ No newline at end of file
@@ -0,0 +1,91 @@
+use std::thread;
+use std::time::Duration;
+
+use ::netspace::*;
+use ::geotop::GeotopRoot;
+use ::netservice::service_request;
+use ::config::SharedConfig;
+use ::lifecycle::{self,Work};
+
+use super::roots_from_str;
+
+/// How often this primary announces itself to the other geosubs
+const ANNOUNCE_INTERVAL_SECS : u64 = 600;
+
+pub struct Discovery;
+
+impl Discovery {
+	pub fn start(cfg: &SharedConfig) -> Result<Success,Failure> {
+		let shared = cfg.clone();
+
+		thread::spawn(move|| {
+			let opened = NetspaceIo::open(database_path(shared.current().live_test));
+
+			let nio = match opened {
+				Ok(nio) => nio,
+				Err(e) => {
+					log_error!("geotop", "Discovery failed to open the netspace ({:?})", e);
+					return
+				}
+			};
+			nio.set_actor("discovery");
+
+			// Held until the loop ends so shutdown waits for the netspace to close
+			let _service = Work::hold();
+
+			log_info!("system", "Geotop Discovery Online");
+			while !lifecycle::stopping() {
+				// Announce whatever hostname the configuration has now
+				let config = shared.current();
+				for (geosub, result) in Discovery::exchange(&nio, &config, None) {
+					match result {
+						Ok(0) => { },
+						Ok(n) => log_info!("geotop", "Learnt {} root(s) through {}", n, geosub),
+						Err(e) => log_warn!("geotop", "Exchange with {} failed: {}", geosub, e),
+					}
+				}
+				if !lifecycle::pause(Duration::new(ANNOUNCE_INTERVAL_SECS, 0)) { break }
+			}
+		});
+
+		Ok(Success::Ok)
+	}
+
+	/// Announce ourselves to the first reachable root of every other
+	/// geosub, or of just `only`, learning the roots each one knows
+	pub fn exchange(nio: &NetspaceIo, config: &NodeConfig, only: Option<&str>) -> Vec<(String,Result<usize,String>)> {
+		let roots = match nio.gtn_roots(only) {
+			Ok(v) => v,
+			Err(e) => return vec![(only.unwrap_or("*").to_string(), Err(format!("{:?}", e)))]
+		};
+
+		let own = config.geosub();
+		let mut geosubs : Vec<String> = roots.iter().map(|r| r.geosub.clone()).collect();
+		geosubs.dedup();
+		geosubs.retain(|g| *g != own);
+
+		geosubs.into_iter().map(|g| {
+			let candidates : Vec<&GeotopRoot> = roots.iter().filter(|r| r.geosub == g).collect();
+			let result = Discovery::announce(nio, config, &candidates);
+			(g, result)
+		}).collect()
+	}
+
+	fn announce(nio: &NetspaceIo, config: &NodeConfig, candidates: &Vec<&GeotopRoot>) -> Result<usize,String> {
+		let mut last = "No roots".to_string();
+
+		// Roots are in priority order; stop at the first that answers
+		for root in candidates {
+			let uri = format!("spring://{}.{}.uk/geotop/announce?geosub={}&spring={}&host={}&service={}",
+							root.springname, root.geosub, config.geosub(),
+							config.springname(), config.hostname(), NodeService::Dvsp);
+
+			match service_request(&root.to_node(), &uri).and_then(|s| roots_from_str(&s)) {
+				Ok(learnt) => return nio.gtn_roots_learn(&learnt, &config.geosub()).map_err(|e| format!("{:?}", e)),
+				Err(e) => last = e
+			}
+		}
+
+		Err(last)
+	}
+}
@@ -0,0 +1,130 @@
+pub mod discovery;
+
+use std::collections::BTreeMap;
+
+use rustc_serialize::json::{self, ToJson, Json};
+
+use ::spring_dvs::protocol::{Message, Response, ipaddr_str, generate_response_service_text, generate_response_empty_code};
+use ::spring_dvs::uri::Uri;
+
+use ::protocol::Svr;
+use ::netspace::{NetspaceFailure,NodeService};
+use ::geotop::GeotopRoot;
+
+/*
+ * Geosub root announcement and discovery
+ *
+ * geotop/list      -- every geosub root this primary knows
+ * geotop/announce  -- the requester is a root of a geosub; the query
+ *                     carries geosub, spring, host and service. The
+ *                     address is always taken from the request itself,
+ *                     and a known root is only updated from the
+ *                     address it is registered at.
+ *
+ * Both respond with the list of known roots so an announcement
+ * doubles as discovery.
+ */
+
+impl ToJson for GeotopRoot {
+	fn to_json(&self) -> Json {
+		let mut d = BTreeMap::new();
+		d.insert("geosub".to_string(), self.geosub.to_json());
+		d.insert("spring".to_string(), self.springname.to_json());
+		d.insert("host".to_string(), self.hostname.to_json());
+		d.insert("address".to_string(), self.address.to_json());
+		d.insert("service".to_string(), format!("{}", self.service).to_json());
+		d.insert("priority".to_string(), self.priority.to_json());
+		Json::Object(d)
+	}
+}
+
+pub fn root_from_json(data: &Json) -> Option<GeotopRoot> {
+	let field = |k: &str| match data.find(k) {
+		Some(&Json::String(ref s)) => Some(s.clone()),
+		_ => None
+	};
+
+	let service = match field("service").and_then(|s| NodeService::from_str(&s)) {
+		Some(s) => s,
+		None => return None
+	};
+
+	match (field("geosub"), field("spring"), field("host"), field("address")) {
+		(Some(g), Some(s), Some(h), Some(a)) => Some(GeotopRoot::new(&g, &s, &h, &a, service,
+										data.find("priority").and_then(|p| p.as_i64()).unwrap_or(1))),
+		_ => None
+	}
+}
+
+pub fn roots_from_str(s: &str) -> Result<Vec<GeotopRoot>,String> {
+	match Json::from_str(s) {
+		Ok(Json::Array(a)) => Ok(a.iter().filter_map(root_from_json).collect()),
+		Ok(_) => Err("Expected a list of roots".to_string()),
+		Err(e) => Err(format!("JSON parse error '{}'", e))
+	}
+}
+
+pub fn request(uri: &Uri, svr: &Svr) -> Message {
+	match uri.res_index(1) {
+		Some("list") => handle_list(svr),
+		Some("announce") => handle_announce(uri, svr),
+		_ => generate_response_empty_code(Response::MalformedContent)
+	}
+}
+
+fn handle_list(svr: &Svr) -> Message {
+	match svr.nio.gtn_roots(None) {
+		Ok(v) => service_response(&v),
+		Err(_) => generate_response_empty_code(Response::NetspaceError)
+	}
+}
+
+fn handle_announce(uri: &Uri, svr: &Svr) -> Message {
+	let service = match uri.query_param("service").and_then(|s| NodeService::from_str(&s)) {
+		Some(s) => s,
+		None => return generate_response_empty_code(Response::MalformedContent)
+	};
+
+	let root = match (uri.query_param("geosub"), uri.query_param("spring"), uri.query_param("host")) {
+		(Some(g), Some(s), Some(h)) => GeotopRoot::new(&g, &s, &h, &ipaddr_str(svr.sock.ip()), service, 1),
+		_ => return generate_response_empty_code(Response::MalformedContent)
+	};
+
+	// Our own roots are managed locally and through replication
+	if root.geosub == svr.config.geosub() {
+		return generate_response_empty_code(Response::NetworkError)
+	}
+
+	match svr.nio.gtn_root_announce(&root) {
+		Ok(_) => { },
+		Err(NetspaceFailure::DuplicateNode) => return generate_response_empty_code(Response::NetworkError),
+		Err(_) => return generate_response_empty_code(Response::NetspaceError)
+	}
+
+	handle_list(svr)
+}
+
+fn service_response(roots: &Vec<GeotopRoot>) -> Message {
+	let data = Json::Array(roots.iter().map(|r| r.to_json()).collect());
+	generate_response_service_text(&json::encode(&data).unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use ::geotop::GeotopRoot;
+	use ::netspace::NodeService;
+
+	#[test]
+	fn ts_geotop_roots_json_roundtrip_p() {
+		let roots = vec![GeotopRoot::new("wsusx", "springa", "greenman", "192.168.1.2", NodeService::Dvsp, 2)];
+		let s = json::encode(&Json::Array(roots.iter().map(|r| r.to_json()).collect())).unwrap();
+		assert_eq!(roots_from_str(&s), Ok(roots));
+	}
+
+	#[test]
+	fn ts_geotop_roots_json_f() {
+		assert!(roots_from_str("{}").is_err());
+		assert_eq!(roots_from_str("[{\"spring\":\"foo\"}]"), Ok(vec![]));
+	}
+}
@@ -1,9 +1,20 @@
 pub mod cert;
 pub mod database;
+pub mod sync;
+pub mod geotop;
+pub mod network;
+
+use ::spring_dvs::enums::NodeService;
+use ::spring_dvs::http::Outbound;
+use ::spring_dvs::node::Node;
+use ::spring_dvs::protocol::{Message,MessageContent,ResponseContent,generate_message_service};
+
+use ::service::Tcp;
 
 #[derive(Debug,Clone,Copy,PartialEq)]
 pub enum Module {
 	Cert,
+	Sync,
 }
 
 
@@ -11,7 +22,36 @@ impl Module {
 	pub fn from_str(s: &str) -> Option<Module> {
 		match s {
 			"cert" => Some(Module::Cert),
+			"sync" => Some(Module::Sync),
 			_ => None
 		}
 	}
-}
\ No newline at end of file
+}
+
+/// Make a service request on another primary and hand back the
+/// service text it responds with
+pub fn service_request(target: &Node, uri: &str) -> Result<String,String> {
+	let message = match generate_message_service(uri) {
+		Ok(m) => m,
+		Err(_) => return Err(format!("Bad request {}", uri))
+	};
+
+	let response : Message = match target.service() {
+		NodeService::Http => match Outbound::request_node(&message, target) {
+			Some(m) => m,
+			None => return Err("Request failed".to_string())
+		},
+		_ => match Tcp::make_request(&message, &target.address(), target.hostname(), target.service()) {
+			Ok(m) => m,
+			Err(e) => return Err(format!("Request failed ({:?})", e))
+		}
+	};
+
+	match response.content {
+		MessageContent::Response(ref r) => match r.content {
+			ResponseContent::ServiceText(ref t) => Ok(t.content.clone()),
+			_ => Err(format!("Peer responded {:?}", r.code))
+		},
+		_ => Err("Unexpected response".to_string())
+	}
+}
@@ -0,0 +1,127 @@
+use std::collections::BTreeMap;
+
+use rustc_serialize::json::{self, ToJson, Json};
+
+use ::spring_dvs::protocol::{Message, Response, generate_response_service_text, generate_response_empty_code};
+use ::spring_dvs::uri::Uri;
+
+use ::protocol::Svr;
+use ::netspace::{Node,NodeRole,NodeService,NodeState};
+
+/*
+ * Filtered and paginated network listing
+ *
+ * network?role=org&state=enabled&service=http&after=<spring>&limit=<n>
+ *
+ * Nodes are listed in springname order. A page holds at most `limit`
+ * nodes and never more than fits the transport the request came in
+ * on; when there is more to come `next` is the springname to pass
+ * as `after` for the following page.
+ *
+ * `info network` takes no parameters on the wire and always answers
+ * with the whole listing; clients that want it filtered or in pages
+ * come here instead.
+ */
+
+/// Most nodes a page will hold, whatever the transport
+const PAGE_LIMIT : usize = 50;
+
+#[derive(Debug,Clone,PartialEq)]
+pub struct NetworkFilter {
+	pub role: Option<NodeRole>,
+	pub state: Option<NodeState>,
+	pub service: Option<NodeService>,
+	pub after: Option<String>,
+	pub limit: usize,
+}
+
+impl NetworkFilter {
+	pub fn from_uri(uri: &Uri) -> Option<NetworkFilter> {
+		let mut filter = NetworkFilter { role: None, state: None, service: None, after: None, limit: PAGE_LIMIT };
+
+		if let Some(r) = uri.query_param("role") {
+			filter.role = Some(match NodeRole::from_str(&r) { Some(r) => r, None => return None });
+		}
+
+		if let Some(s) = uri.query_param("state") {
+			filter.state = Some(match NodeState::from_str(&s) { Some(s) => s, None => return None });
+		}
+
+		if let Some(s) = uri.query_param("service") {
+			filter.service = Some(match NodeService::from_str(&s) { Some(s) => s, None => return None });
+		}
+
+		if let Some(l) = uri.query_param("limit") {
+			filter.limit = match l.parse::<usize>() {
+				Ok(n) if n > 0 => n.min(PAGE_LIMIT),
+				_ => return None
+			};
+		}
+
+		filter.after = uri.query_param("after").map(|s| s.to_string());
+
+		Some(filter)
+	}
+
+	pub fn matches(&self, node: &Node) -> bool {
+		self.role.as_ref().map_or(true, |r| node.role() == *r)
+			&& self.state.as_ref().map_or(true, |s| node.state() == *s)
+			&& self.service.as_ref().map_or(true, |s| node.service() == *s)
+			&& self.after.as_ref().map_or(true, |a| node.springname() > a.as_str())
+	}
+}
+
+pub fn request(uri: &Uri, svr: &Svr) -> Message {
+	let filter = match NetworkFilter::from_uri(uri) {
+		Some(f) => f,
+		None => return generate_response_empty_code(Response::MalformedContent)
+	};
+
+	let nodes = match svr.nio.try_gsn_nodes() {
+		Ok(v) => v,
+		Err(e) => {
+			log_error!("netspace", "Unable to list the network ({:?})", e);
+			return generate_response_empty_code(Response::NetspaceError)
+		}
+	};
+
+	let mut nodes : Vec<Node> = nodes.into_iter()
+									.filter(|n| filter.matches(n) && !svr.nio.pending_is(n.springname()))
+									.collect();
+	nodes.sort_by(|a, b| a.springname().cmp(b.springname()));
+
+	let more = nodes.len() > filter.limit;
+	nodes.truncate(filter.limit);
+
+	let mut page = page_response(&nodes, more);
+
+	// Shed nodes until the page fits whatever carries it back
+	while page.to_bytes().len() > svr.transport.max_response() && !nodes.is_empty() {
+		nodes.pop();
+		page = page_response(&nodes, true);
+	}
+
+	page
+}
+
+fn page_response(nodes: &Vec<Node>, more: bool) -> Message {
+	let mut d = BTreeMap::new();
+	d.insert("nodes".to_string(), Json::Array(nodes.iter().map(node_json).collect()));
+	d.insert("next".to_string(), match (more, nodes.last()) {
+		(true, Some(n)) => n.springname().to_json(),
+		_ => Json::Null
+	});
+
+	generate_response_service_text(&json::encode(&Json::Object(d)).unwrap())
+}
+
+fn node_json(node: &Node) -> Json {
+	let mut d = BTreeMap::new();
+	d.insert("spring".to_string(), node.springname().to_json());
+	d.insert("host".to_string(), node.hostname().to_json());
+	d.insert("address".to_string(), node.address().to_string().to_json());
+	d.insert("service".to_string(), format!("{}", node.service()).to_json());
+	d.insert("state".to_string(), format!("{}", node.state()).to_json());
+	d.insert("role".to_string(), format!("{}", node.role()).to_json());
+	Json::Object(d)
+}
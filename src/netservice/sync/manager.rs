@@ -0,0 +1,96 @@
+use ::management::output;
+
+use prettytable::Table;
+use prettytable::row::Row;
+use prettytable::cell::Cell;
+
+use ::protocol::Svr;
+use ::management::{ManagedService,ZoneResult};
+use ::netservice::sync::peers::{Peers,PeerStatus};
+use ::ledger;
+
+macro_rules! storage_try {
+	($e: expr) => (
+		match $e {
+			Ok(s) => s,
+			Err(e) => return Err(format!("Error: Replication storage failure ({:?})\n", e)),
+		}
+	)
+}
+
+pub struct SyncManagementInterface;
+
+impl SyncManagementInterface {
+	pub fn new() -> SyncManagementInterface {
+		SyncManagementInterface{ }
+	}
+}
+
+impl ManagedService for SyncManagementInterface {
+
+	fn init(&self) -> ZoneResult {
+		match Peers::init() {
+			true => Ok(format!("Module `sync` initialised successfully")),
+			false => Err(format!("Error: Module `sync` initialisation error"))
+		}
+	}
+
+	fn hook(&self, atom: &Vec<String>, svr: &Svr) -> ZoneResult {
+		let mut atom = atom.iter();
+
+		match atom.next().map(|s| s.as_ref()) {
+			Some("view") => ZoneModel::view(svr),
+			Some("sync") => match atom.next() {
+				Some(s) => ZoneModel::sync(s),
+				None => Err("Error: No peer specified\n".to_string())
+			},
+			_ => Err("Error: Unknown or malformed action".to_string())
+		}
+	}
+}
+
+struct ZoneModel;
+
+impl ZoneModel {
+	fn view(svr: &Svr) -> ZoneResult {
+		let peers = storage_try!(storage_try!(Peers::new()).listing());
+		let ledger = storage_try!(svr.nio.ledger_entries(&svr.config.springname()));
+
+		let mut out = ZoneModel::tabulate(peers);
+		out.push_str(&format!("Ledger: {} node(s), {} tombstone(s)\n",
+							ledger.iter().filter(|e| !e.deleted).count(),
+							ledger.iter().filter(|e| e.deleted).count()));
+		Ok(out)
+	}
+
+	/// Wake the replication thread to sync with a peer
+	fn sync(springname: &str) -> ZoneResult {
+		let peers = storage_try!(Peers::new());
+		match storage_try!(peers.with_name(springname)) {
+			Some(_) => { },
+			None => return Err(format!("Error: Unknown peer {}\n", springname))
+		}
+
+		storage_try!(peers.mark_pending(springname));
+		ledger::notify();
+		Ok(format!("Sync with {} scheduled\n", springname))
+	}
+
+	fn tabulate(peers: Vec<PeerStatus>) -> String {
+		let mut table = Table::new();
+
+		table.add_row(row!["_peer_", "_address_", "_pushed_", "_synced_", "_pending_", "_status_"]);
+		for peer in peers {
+			table.add_row(Row::new(vec![
+				Cell::new(&peer.springname),
+				Cell::new(&peer.address),
+				Cell::new(&peer.pushed_at),
+				Cell::new(&peer.synced_at),
+				Cell::new(if peer.pending { "yes" } else { "no" }),
+				Cell::new(&peer.status)
+				]));
+		}
+
+		output::table(table)
+	}
+}
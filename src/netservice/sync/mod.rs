@@ -0,0 +1,216 @@
+pub mod peers;
+pub mod replicator;
+pub mod manager;
+
+use std::collections::BTreeMap;
+
+use rustc_serialize::json::{self, ToJson, Json};
+
+use ::spring_dvs::protocol::{Message, Response, ipaddr_str, generate_response_service_text, generate_response_empty_code};
+use ::spring_dvs::uri::Uri;
+
+use ::protocol::Svr;
+use ::netspace::{Netspace,Node};
+use ::ledger::{self,LedgerEntry};
+use ::snapshot::NodeRecord;
+
+use self::peers::Peers;
+
+/// Most ledger entries a digest page will hold, whatever the transport
+const DIGEST_LIMIT : usize = 100;
+
+/*
+ * Replication between the primaries rooting a geosub
+ *
+ * sync/digest        -- ledger stamp of every node this primary knows,
+ *                       a page at a time: `after=<spring>` continues
+ *                       from the `next` of the page before
+ * sync/node/<spring> -- ledger entry and record of a single node
+ * sync/notify        -- the requester has changes; sync with it now
+ *
+ * Only other roots of the same geosub may use these. Nodes awaiting
+ * approval are kept to this primary until they are approved.
+ */
+
+impl ToJson for LedgerEntry {
+	fn to_json(&self) -> Json {
+		let mut d = BTreeMap::new();
+		d.insert("spring".to_string(), self.springname.to_json());
+		d.insert("stamp".to_string(), self.stamp.to_json());
+		d.insert("origin".to_string(), self.origin.to_json());
+		d.insert("deleted".to_string(), self.deleted.to_json());
+		Json::Object(d)
+	}
+}
+
+pub fn entry_from_json(data: &Json) -> Option<LedgerEntry> {
+	let spring = match data.find("spring") { Some(&Json::String(ref s)) => s.clone(), _ => return None };
+	let origin = match data.find("origin") { Some(&Json::String(ref s)) => s.clone(), _ => return None };
+	let stamp = match data.find("stamp").and_then(|s| s.as_i64()) { Some(s) => s, None => return None };
+	let deleted = match data.find("deleted").and_then(|d| d.as_boolean()) { Some(d) => d, None => return None };
+
+	Some(LedgerEntry::new(&spring, stamp, &origin, deleted))
+}
+
+/// The entries of a digest page and the `after` of the next, if any
+pub fn page_from_json(data: &Json) -> Option<(Vec<LedgerEntry>,Option<String>)> {
+	let entries = match data.find("entries").and_then(|e| e.as_array()) {
+		Some(a) => a.iter().filter_map(entry_from_json).collect(),
+		None => return None
+	};
+
+	let next = match data.find("next") {
+		Some(&Json::String(ref s)) => Some(s.clone()),
+		Some(&Json::Null) => None,
+		_ => return None
+	};
+
+	Some((entries, next))
+}
+
+pub fn request(uri: &Uri, svr: &Svr) -> Message {
+	let peer = match peer_of(svr) {
+		Some(p) => p,
+		None => return generate_response_empty_code(Response::NetworkError)
+	};
+
+	match uri.res_index(1) {
+		Some("digest") => handle_digest(uri, svr),
+		Some("node") => {
+			match uri.res_index(2) {
+				Some(s) => handle_node(s, svr),
+				None => generate_response_empty_code(Response::MalformedContent)
+			}
+		},
+		Some("notify") => handle_notify(&peer),
+		_ => generate_response_empty_code(Response::MalformedContent)
+	}
+}
+
+/// The geosub root the request came from, if it is one
+fn peer_of(svr: &Svr) -> Option<Node> {
+	let address = ipaddr_str(svr.sock.ip());
+	svr.nio.gtn_geosub_root_nodes(&svr.config.geosub()).into_iter()
+		.find(|n| n.address() == address && n.springname() != svr.config.springname())
+}
+
+fn handle_digest(uri: &Uri, svr: &Svr) -> Message {
+	let after = uri.query_param("after").map(|s| s.to_string());
+
+	match svr.nio.ledger_entries(&svr.config.springname()) {
+		Ok(v) => digest_page(v.into_iter()
+							.filter(|e| !svr.nio.pending_is(&e.springname))
+							.filter(|e| after.as_ref().map_or(true, |a| e.springname.as_str() > a.as_str()))
+							.collect(), svr.transport.max_response()),
+		Err(_) => generate_response_empty_code(Response::NetspaceError)
+	}
+}
+
+/// The first page of `entries` in springname order, shedding entries
+/// until it fits in `max` bytes
+fn digest_page(mut entries: Vec<LedgerEntry>, max: usize) -> Message {
+	entries.sort_by(|a, b| a.springname.cmp(&b.springname));
+
+	let more = entries.len() > DIGEST_LIMIT;
+	entries.truncate(DIGEST_LIMIT);
+
+	let mut page = digest_response(&entries, more);
+	while page.to_bytes().len() > max && !entries.is_empty() {
+		entries.pop();
+		page = digest_response(&entries, true);
+	}
+
+	page
+}
+
+fn digest_response(entries: &Vec<LedgerEntry>, more: bool) -> Message {
+	let mut d = BTreeMap::new();
+	d.insert("entries".to_string(), Json::Array(entries.iter().map(|e| e.to_json()).collect()));
+	d.insert("next".to_string(), match (more, entries.last()) {
+		(true, Some(e)) => e.springname.to_json(),
+		_ => Json::Null
+	});
+
+	service_response(Json::Object(d))
+}
+
+fn handle_node(springname: &str, svr: &Svr) -> Message {
+	if svr.nio.pending_is(springname) {
+		return generate_response_empty_code(Response::NetspaceError)
+	}
+
+	let entry = match svr.nio.ledger_entry(springname, &svr.config.springname()) {
+		Ok(Some(e)) => e,
+		Ok(None) => return generate_response_empty_code(Response::NetspaceError),
+		Err(_) => return generate_response_empty_code(Response::NetspaceError)
+	};
+
+	let mut d = BTreeMap::new();
+	d.insert("entry".to_string(), entry.to_json());
+	d.insert("node".to_string(), match svr.nio.gsn_node_by_springname(springname) {
+		Ok(ref n) if !entry.deleted => NodeRecord::from_node(n).to_json(),
+		_ => Json::Null
+	});
+
+	service_response(Json::Object(d))
+}
+
+fn handle_notify(peer: &Node) -> Message {
+	let marked = Peers::new().and_then(|p| {
+		try!(p.track(peer.springname(), &peer.address()));
+		p.mark_pending(peer.springname())
+	});
+
+	match marked {
+		Ok(_) => {
+			ledger::notify();
+			generate_response_empty_code(Response::Ok)
+		},
+		Err(_) => generate_response_empty_code(Response::NetspaceError)
+	}
+}
+
+fn service_response(data: Json) -> Message {
+	generate_response_service_text(&json::encode(&data).unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use ::spring_dvs::protocol::{MessageContent,ResponseContent};
+	use ::protocol::Transport;
+
+	fn page_of(m: &Message) -> (Vec<LedgerEntry>,Option<String>) {
+		match m.content {
+			MessageContent::Response(ref r) => match r.content {
+				ResponseContent::ServiceText(ref t) => page_from_json(&Json::from_str(&t.content).unwrap()).unwrap(),
+				_ => panic!("Not service text")
+			},
+			_ => panic!("Not a response")
+		}
+	}
+
+	#[test]
+	fn ts_sync_digest_paged_p() {
+		let entries : Vec<LedgerEntry> = (0..200).map(|i| LedgerEntry::new(&format!("node{:03}", i), 1000 + i, "peer", false)).collect();
+		let max = Transport::Tcp.max_response();
+
+		let mut seen = Vec::new();
+		let mut after : Option<String> = None;
+		loop {
+			let rest = entries.iter().cloned()
+						.filter(|e| after.as_ref().map_or(true, |a| e.springname > *a))
+						.collect();
+
+			let page = digest_page(rest, max);
+			assert!(page.to_bytes().len() <= max);
+
+			let (v, next) = page_of(&page);
+			seen.extend(v);
+			if next.is_none() { break }
+			after = next;
+		}
+
+		assert_eq!(seen, entries);
+	}
+}
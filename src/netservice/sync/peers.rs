@@ -0,0 +1,115 @@
+use ::netservice::database::{ServiceDatabase,State,Statement,Value,Connection};
+use ::schema;
+use ::netspace::{StorageFailure,step};
+
+/// What this primary knows about replicating with another root
+#[derive(Debug,Clone,PartialEq)]
+pub struct PeerStatus {
+	pub springname: String,
+	pub address: String,
+	/// Ledger stamp of the last local change the peer was told about
+	pub pushed: i64,
+	pub pushed_at: String,
+	pub synced_at: String,
+	/// Seconds since the last completed anti-entropy round
+	pub age: i64,
+	pub pending: bool,
+	pub status: String,
+}
+
+pub struct Peers {
+	db: Connection
+}
+
+impl Peers {
+	pub fn new() -> Result<Peers,StorageFailure> {
+		Ok(Peers {
+			db: try!(ServiceDatabase::new())
+		})
+	}
+
+	pub fn init() -> bool {
+		match ServiceDatabase::new() {
+			Ok(db) => schema::migrate(&db, schema::SERVICES).is_ok(),
+			Err(_) => false
+		}
+	}
+
+	/// Start tracking a peer, or refresh its address if already known
+	pub fn track(&self, springname: &str, address: &str) -> Result<(),StorageFailure> {
+		try!(self.execute("INSERT OR IGNORE INTO `sync_peers` (springname,address) VALUES (?,?)",
+						&[Value::String( springname.to_string() ), Value::String( address.to_string() )]));
+		self.execute("UPDATE `sync_peers` SET address = ? WHERE springname = ?",
+						&[Value::String( address.to_string() ), Value::String( springname.to_string() )])
+	}
+
+	/// The peer has changes for us; sync when the replicator next wakes
+	pub fn mark_pending(&self, springname: &str) -> Result<(),StorageFailure> {
+		self.execute("UPDATE `sync_peers` SET pending = 1 WHERE springname = ?",
+						&[Value::String( springname.to_string() )])
+	}
+
+	pub fn pushed(&self, springname: &str, stamp: i64) -> Result<(),StorageFailure> {
+		self.execute("UPDATE `sync_peers` SET pushed = ?, status = 'ok' WHERE springname = ?",
+						&[Value::Integer(stamp), Value::String( springname.to_string() )])
+	}
+
+	pub fn synced(&self, springname: &str, status: &str) -> Result<(),StorageFailure> {
+		self.execute("UPDATE `sync_peers` SET synced = strftime('%s','now'), pending = 0, status = ? WHERE springname = ?",
+						&[Value::String( status.to_string() ), Value::String( springname.to_string() )])
+	}
+
+	pub fn failed(&self, springname: &str, status: &str) -> Result<(),StorageFailure> {
+		self.execute("UPDATE `sync_peers` SET status = ? WHERE springname = ?",
+						&[Value::String( format!("error: {}", status) ), Value::String( springname.to_string() )])
+	}
+
+	pub fn listing(&self) -> Result<Vec<PeerStatus>,StorageFailure> {
+		self.query("", &[])
+	}
+
+	pub fn with_name(&self, springname: &str) -> Result<Option<PeerStatus>,StorageFailure> {
+		let mut v = try!(self.query("WHERE springname = ?", &[Value::String( springname.to_string() )]));
+		Ok(v.pop())
+	}
+
+	fn execute(&self, query: &str, values: &[Value]) -> Result<(),StorageFailure> {
+		let mut statement = try!(self.db.prepare(query));
+		for (i, value) in values.iter().enumerate() {
+			try!(statement.bind(i+1, value));
+		}
+		try!(step(&mut statement));
+		Ok(())
+	}
+
+	fn query(&self, condition: &str, values: &[Value]) -> Result<Vec<PeerStatus>,StorageFailure> {
+		let mut statement = try!(self.db.prepare(format!("SELECT springname, address, pushed,
+									CASE pushed WHEN 0 THEN 'never' ELSE datetime(pushed/1000,'unixepoch') END,
+									CASE synced WHEN 0 THEN 'never' ELSE datetime(synced,'unixepoch') END,
+									strftime('%s','now') - synced, pending, status
+									FROM `sync_peers` {} ORDER BY springname", condition)));
+
+		for (i, value) in values.iter().enumerate() {
+			try!(statement.bind(i+1, value));
+		}
+
+		self.peers_from_statement(&mut statement)
+	}
+
+	fn peers_from_statement(&self, statement: &mut Statement) -> Result<Vec<PeerStatus>,StorageFailure> {
+		let mut v = Vec::new();
+		while let State::Row = try!(step(statement)) {
+			v.push(PeerStatus {
+				springname: try!(statement.read::<String>(0)),
+				address: try!(statement.read::<String>(1)),
+				pushed: try!(statement.read::<i64>(2)),
+				pushed_at: try!(statement.read::<String>(3)),
+				synced_at: try!(statement.read::<String>(4)),
+				age: try!(statement.read::<i64>(5)),
+				pending: try!(statement.read::<i64>(6)) != 0,
+				status: try!(statement.read::<String>(7)),
+			});
+		}
+		Ok(v)
+	}
+}
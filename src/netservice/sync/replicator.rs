@@ -0,0 +1,261 @@
+use std::thread;
+use std::time::Duration;
+
+use rustc_serialize::json::Json;
+
+use ::netspace::*;
+use ::ledger::{self,LedgerEntry};
+use ::snapshot::{self,Change,NodeRecord};
+use ::netservice::service_request;
+use ::config::SharedConfig;
+use ::lifecycle::{self,Work};
+
+use super::{entry_from_json,page_from_json};
+use super::peers::{Peers,PeerStatus};
+
+/// How long the replicator waits for a change before checking on
+/// peers anyway; local changes and notifies wake it sooner
+const TICK_SECS : u64 = 5;
+
+/// How long between full anti-entropy rounds with each peer
+const SYNC_INTERVAL_SECS : i64 = 60;
+
+pub struct Replicator<'a> {
+	nio: &'a NetspaceIo,
+	peers: &'a Peers,
+	springname: String,
+	geosub: String,
+}
+
+impl<'a> Replicator<'a> {
+	pub fn start(cfg: &SharedConfig) -> Result<Success,Failure> {
+		let shared = cfg.clone();
+
+		thread::spawn(move|| {
+			let opened = NetspaceIo::open(database_path(shared.current().live_test));
+
+			let nio = match opened {
+				Ok(nio) => nio,
+				Err(e) => {
+					log_error!("sync", "Replication failed to open the netspace ({:?})", e);
+					return
+				}
+			};
+
+			let peers = match Peers::new() {
+				Ok(p) => p,
+				Err(e) => {
+					log_error!("sync", "Replication failed to open peer status ({:?})", e);
+					return
+				}
+			};
+
+			// Held until the loop ends so shutdown waits for the netspace to close
+			let _service = Work::hold();
+
+			log_info!("system", "Replication Service Online");
+			let mut seen = ledger::seen();
+			while !lifecycle::stopping() {
+				// Replicate as whatever the configuration says now
+				let config = shared.current();
+				let replicator = Replicator {
+					nio: &nio,
+					peers: &peers,
+					springname: config.springname(),
+					geosub: config.geosub(),
+				};
+
+				replicator.tick();
+				seen = ledger::wait(seen, Duration::new(TICK_SECS, 0));
+			}
+		});
+
+		Ok(Success::Ok)
+	}
+
+	fn tick(&self) {
+		for peer in self.nio.gtn_geosub_root_nodes(&self.geosub) {
+			if lifecycle::stopping() { return }
+			if peer.springname() == self.springname { continue }
+
+			let status = match self.peers.track(peer.springname(), &peer.address())
+								.and_then(|_| self.peers.with_name(peer.springname())) {
+				Ok(Some(s)) => s,
+				_ => continue
+			};
+
+			self.push(&peer, &status);
+
+			if status.pending || status.age >= SYNC_INTERVAL_SECS {
+				self.sync(&peer);
+			}
+		}
+	}
+
+	/// Tell a peer we have changes it has not heard about
+	fn push(&self, peer: &Node, status: &PeerStatus) {
+		let latest = match self.nio.ledger_local_since(status.pushed, &self.springname) {
+			Ok(ref v) if !v.is_empty() => v[v.len()-1].stamp,
+			_ => return
+		};
+
+		let r = match request(peer, &self.geosub, "notify") {
+			Ok(_) => self.peers.pushed(peer.springname(), latest),
+			Err(e) => self.peers.failed(peer.springname(), &e)
+		};
+
+		if let Err(e) = r { log_error!("sync", "Replication status update failed ({:?})", e) }
+	}
+
+	pub fn sync(&self, peer: &Node) {
+		let r = match self.anti_entropy(peer) {
+			Ok(n) => {
+				if n > 0 { log_info!("sync", "Applied {} change(s) from {}", n, peer.springname()) }
+				self.peers.synced(peer.springname(), "ok")
+			},
+			Err(e) => {
+				log_warn!("sync", "Failed with {}: {}", peer.springname(), e);
+				self.peers.failed(peer.springname(), &e)
+			}
+		};
+
+		if let Err(e) = r { log_error!("sync", "Replication status update failed ({:?})", e) }
+	}
+
+	/// Every ledger entry a peer has, fetched a page at a time
+	fn digest(&self, peer: &Node) -> Result<Vec<LedgerEntry>,String> {
+		let mut entries = Vec::new();
+		let mut after : Option<String> = None;
+
+		loop {
+			let path = match after {
+				Some(ref a) => format!("digest?after={}", a),
+				None => "digest".to_string()
+			};
+
+			let page = try!(Json::from_str(&try!(request(peer, &self.geosub, &path)))
+								.map_err(|e| format!("Malformed digest ({})", e)));
+
+			let (mut v, next) = match page_from_json(&page) {
+				Some(p) => p,
+				None => return Err("Malformed digest".to_string())
+			};
+
+			entries.append(&mut v);
+
+			// A cursor that does not move on would have us asking forever
+			match next {
+				Some(n) if after.as_ref().map_or(true, |a| n > *a) => after = Some(n),
+				Some(_) => return Err("Malformed digest cursor".to_string()),
+				None => return Ok(entries)
+			}
+		}
+	}
+
+	/// Compare ledgers with a peer and pull every node it has a newer copy of
+	fn anti_entropy(&self, peer: &Node) -> Result<usize,String> {
+		let remote = try!(self.digest(peer));
+
+		self.nio.set_actor(&format!("sync:{}", peer.springname()));
+
+		let mut applied = 0;
+		for entry in remote {
+			let local = try!(self.nio.ledger_entry(&entry.springname, &self.springname)
+								.map_err(|e| format!("{:?}", e)));
+
+			match local {
+				Some(ref l) if !entry.supersedes(l) => continue,
+				_ => { }
+			}
+
+			let data = try!(Json::from_str(&try!(request(peer, &self.geosub, &format!("node/{}", entry.springname))))
+								.map_err(|e| format!("Malformed node ({})", e)));
+
+			let entry = match data.find("entry").and_then(entry_from_json) {
+				Some(e) => e,
+				None => return Err(format!("Malformed node {}", entry.springname))
+			};
+
+			let record = data.find("node").and_then(NodeRecord::from_json);
+
+			try!(apply(self.nio, &entry, record));
+			applied += 1;
+		}
+
+		Ok(applied)
+	}
+}
+
+/// Bring a node in line with a newer ledger entry from a peer
+pub fn apply(nio: &NetspaceIo, entry: &LedgerEntry, record: Option<NodeRecord>) -> Result<(),String> {
+	let current = nio.gsn_node_by_springname(&entry.springname).ok().map(|n| NodeRecord::from_node(&n));
+
+	let change = match (current, entry.deleted, record) {
+		(Some(c), true, _) => Some(Change::RemoveNode(c)),
+		(None, false, Some(r)) => Some(Change::AddNode(r)),
+		(Some(c), false, Some(r)) => match c == r {
+			true => None,
+			false => Some(Change::UpdateNode(c, r))
+		},
+		(None, true, _) => None,
+		(_, false, None) => return Err(format!("No record sent for {}", entry.springname))
+	};
+
+	// The change and its stamp stand or fall together, or a kept change
+	// with a stale stamp would be reapplied or lose to an older write
+	let transaction = try!(nio.transaction().map_err(|e| format!("Unable to start transaction ({:?})", e)));
+
+	if let Some(c) = change {
+		try!(snapshot::apply(&vec![c], nio));
+	}
+
+	// Overwrite the local stamp left by applying the change
+	try!(nio.ledger_set(entry).map_err(|e| format!("{:?}", e)));
+	transaction.commit().map_err(|e| format!("{:?}", e))
+}
+
+fn request(peer: &Node, geosub: &str, path: &str) -> Result<String,String> {
+	service_request(peer, &format!("spring://{}.{}.uk/sync/{}", peer.springname(), geosub, path))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use ::netspace::*;
+	use ::ledger::LedgerEntry;
+	use ::snapshot::NodeRecord;
+
+	fn record(spring: &str, address: &str) -> NodeRecord {
+		NodeRecord::from_node(&Node::from_str(&format!("spring:{},host:bar,address:{},role:org,service:http,state:enabled", spring, address)).unwrap())
+	}
+
+	#[test]
+	fn ts_replicator_apply_register_p() {
+		let nsio = NetspaceIo::new(":memory:");
+		let entry = LedgerEntry::new("foo", 1000, "peer", false);
+
+		apply(&nsio, &entry, Some(record("foo", "192.168.1.2"))).unwrap();
+
+		let n = nsio.gsn_node_by_springname("foo").unwrap();
+		assert_eq!(n.state(), NodeState::Enabled);
+		assert_eq!(nsio.ledger_entry("foo", "self").unwrap(), Some(entry));
+	}
+
+	#[test]
+	fn ts_replicator_apply_update_unregister_p() {
+		let nsio = NetspaceIo::new(":memory:");
+		apply(&nsio, &LedgerEntry::new("foo", 1000, "peer", false), Some(record("foo", "192.168.1.2"))).unwrap();
+		apply(&nsio, &LedgerEntry::new("foo", 2000, "peer", false), Some(record("foo", "192.168.1.3"))).unwrap();
+		assert_eq!(nsio.gsn_node_by_springname("foo").unwrap().address(), "192.168.1.3");
+
+		apply(&nsio, &LedgerEntry::new("foo", 3000, "peer", true), None).unwrap();
+		assert!(nsio.gsn_node_by_springname("foo").is_err());
+		assert!(nsio.ledger_entry("foo", "self").unwrap().unwrap().deleted);
+	}
+
+	#[test]
+	fn ts_replicator_apply_missing_record_f() {
+		let nsio = NetspaceIo::new(":memory:");
+		assert!(apply(&nsio, &LedgerEntry::new("foo", 1000, "peer", false), None).is_err());
+	}
+}
@@ -1,15 +1,26 @@
-
 extern crate sqlite;
 
+use std::cell::{Cell,RefCell};
+use std::sync::{Arc,Mutex};
+use std::fmt;
+use std::thread;
+use std::time::Duration;
 
 pub use spring_dvs::enums::{Failure,Success};
 pub use spring_dvs::node::{Node,NodeRole,NodeService,NodeState,ParseFailure};
 pub use spring_dvs::spaces::{Netspace,NetspaceFailure};
 pub use config::{NodeConfig, Config};
 
+use std::net::IpAddr;
 
+use schema;
+use ledger;
+use ledger::LedgerEntry;
+use geotop::GeotopRoot;
+use throttle::{self,Counters,ThrottleLimits};
+use audit::{node_record,token_record};
 
-use self::sqlite::{State,Statement};
+use self::sqlite::{State,Statement,Value};
 
 /*
  * Fix:
@@ -17,18 +28,141 @@ use self::sqlite::{State,Statement};
  * of nodes from Database results is very sketchy and will eventually 
  * lead to ruin!
  */
-  
+
+/// How long sqlite waits on a lock held by another thread
+/// before reporting the database as busy
+const BUSY_TIMEOUT_MS : u32 = 2000;
+
+/// How many times a busy or locked statement is retried
+const STEP_RETRIES : u64 = 3;
+
+/// The netspace database of a running primary
+pub const NETSPACE_DATABASE : &'static str = "/var/lib/springdvs/gsn.db";
+
+/// The database used instead when live testing is enabled
+pub const LIVE_TEST_DATABASE : &'static str = "live-testing.db";
+
+/// Path of the netspace database for the given live testing setting
+pub fn database_path(live_test: bool) -> &'static str {
+	match live_test {
+		false => NETSPACE_DATABASE,
+		true => LIVE_TEST_DATABASE
+	}
+}
+
+const SQLITE_BUSY : isize = 5;
+const SQLITE_LOCKED : isize = 6;
+
+/// Failure in the underlying storage, as opposed to the
+/// netspace level failures of `NetspaceFailure`
+#[derive(Debug,Clone,PartialEq)]
+pub enum StorageFailure {
+	Busy,
+	Locked,
+	Query(String),
+}
+
+impl StorageFailure {
+	pub fn is_transient(&self) -> bool {
+		match *self {
+			StorageFailure::Busy | StorageFailure::Locked => true,
+			_ => false
+		}
+	}
+	
+	pub fn to_netspace_failure(&self) -> NetspaceFailure {
+		NetspaceFailure::DatabaseError
+	}
+}
+
+impl From<sqlite::Error> for StorageFailure {
+	fn from(e: sqlite::Error) -> StorageFailure {
+		match e.code {
+			Some(SQLITE_BUSY) => StorageFailure::Busy,
+			Some(SQLITE_LOCKED) => StorageFailure::Locked,
+			_ => StorageFailure::Query(match e.message {
+				Some(m) => m,
+				None => format!("sqlite error code {:?}", e.code)
+			})
+		}
+	}
+}
+
+/// Step a statement, backing off and retrying while another
+/// connection holds the database
+pub fn step(statement: &mut Statement) -> Result<State,StorageFailure> {
+	let mut attempt = 0;
+	loop {
+		match statement.next() {
+			Ok(state) => return Ok(state),
+			Err(e) => {
+				let failure = StorageFailure::from(e);
+				if !failure.is_transient() || attempt >= STEP_RETRIES {
+					return Err(failure)
+				}
+				attempt += 1;
+				thread::sleep(Duration::from_millis(100 * attempt));
+			}
+		}
+	}
+}
+
+/// Open a connection with the busy timeout used across the primary
+pub fn open_database(database: &str) -> Result<sqlite::Connection,StorageFailure> {
+	let db = try!(sqlite::open(database));
+	try!(db.execute(format!("PRAGMA busy_timeout = {}", BUSY_TIMEOUT_MS)));
+	Ok(db)
+}
 
 pub struct NetspaceIo {
 	db: sqlite::Connection,
+	actor: RefCell<String>,
+	/// Open transactions, innermost last
+	depth: Cell<usize>,
+	/// A ledger stamp is waiting on the outermost commit
+	stamped: Cell<bool>,
+	throttle: Arc<Mutex<Counters>>,
 }
 
 
 impl NetspaceIo {
 	
-	pub fn new(database: &str) -> NetspaceIo {
+	/// Open a netspace database, bringing its schema up to date
+	pub fn open(database: &str) -> Result<NetspaceIo,StorageFailure> {
+		let db = try!(open_database(database));
+		
+		match schema::migrate(&db, schema::NETSPACE) {
+			Ok(_) => { },
+			Err(e) => {
+				log_error!("database", "Netspace schema migration failed on {} ({:?})", database, e);
+				return Err(StorageFailure::from(e))
+			}
+		}
+		
+		Ok(NetspaceIo::with_connection(db, database))
+	}
+	
+	/// Open a netspace database whose schema another connection
+	/// has already brought up to date
+	pub fn attach(database: &str) -> Result<NetspaceIo,StorageFailure> {
+		Ok(NetspaceIo::with_connection(try!(open_database(database)), database))
+	}
+	
+	fn with_connection(db: sqlite::Connection, database: &str) -> NetspaceIo {
 		NetspaceIo {
-			db : sqlite::open(database).unwrap()
+			db : db,
+			actor: RefCell::new(String::from("system")),
+			depth: Cell::new(0),
+			stamped: Cell::new(false),
+			throttle: throttle::counters(database),
+		}
+	}
+	
+	/// As `open`, for databases that are always there such as `:memory:`
+	pub fn new(database: &str) -> NetspaceIo {
+		match NetspaceIo::open(database) {
+			Ok(nio) => nio,
+			Err(e) => panic!("[Error] Failed to open netspace database {} ({:?})", database, e)
 		}
 	}
 	
@@ -36,61 +170,282 @@ impl NetspaceIo {
 		&self.db
 	}
 	
-	fn fill_node(&self, statement: &sqlite::Statement) -> Result<Node,NetspaceFailure> {
-		let spring = statement.read::<String>(1).unwrap();
-		let host = statement.read::<String>(2).unwrap();
-		let addr = statement.read::<String>(3).unwrap();
-		let service = NodeService::from_i64(statement.read::<i64>(4).unwrap());
-		let state = NodeState::from_i64(statement.read::<i64>(5).unwrap());
-		let role =  NodeRole::from_i64(statement.read::<i64>(6).unwrap());
-		let key = statement.read::<String>(7).unwrap();
+	/// Rate limiting counters, shared by every connection to the database
+	pub fn throttle_counters(&self) -> &Mutex<Counters> {
+		&self.throttle
+	}
+	
+	/// Set who is responsible for the changes made from here on;
+	/// recorded against every entry in the audit log
+	pub fn set_actor(&self, actor: &str) {
+		*self.actor.borrow_mut() = actor.to_string();
+	}
+	
+	pub fn actor(&self) -> String {
+		self.actor.borrow().clone()
+	}
+	
+	/// Start a transaction; everything done through this netspace
+	/// until it is committed is undone if it is dropped. These are
+	/// savepoints so one can be opened inside another.
+	pub fn transaction(&self) -> Result<Transaction,StorageFailure> {
+		try!(self.db.execute("SAVEPOINT `netspace`"));
+		self.depth.set(self.depth.get() + 1);
+		Ok(Transaction {
+			nio: self,
+			open: true,
+		})
+	}
+	
+	
+	/*
+	 * The `Netspace` listings report a broken database as empty;
+	 * these say so instead, for callers that must not mistake one
+	 * for the other.
+	 */
+	
+	pub fn try_gsn_nodes(&self) -> Result<Vec<Node>,StorageFailure> {
+		self.try_nodes("SELECT * FROM geosub_netspace", &[])
+	}
+	
+	pub fn try_gsn_nodes_by_address(&self, address: &str) -> Result<Vec<Node>,StorageFailure> {
+		self.try_nodes("SELECT * FROM geosub_netspace WHERE address = ?",
+					&[Value::String( String::from(address) )])
+	}
+	
+	pub fn try_gsn_nodes_by_type(&self, types: NodeRole) -> Result<Vec<Node>,StorageFailure> {
+		self.try_nodes("SELECT * FROM geosub_netspace WHERE types & ?",
+					&[Value::Integer( types as i64 )])
+	}
+	
+	pub fn try_gtn_geosub_root_nodes(&self, gsn: &str) -> Result<Vec<Node>,StorageFailure> {
+		self.try_nodes("SELECT * FROM `geotop_netspace`
+					WHERE geosub = ?
+					ORDER BY priority ASC",
+					&[Value::String( String::from(gsn) )])
+	}
+	
+	pub fn try_gsn_check_token(&self, token: &str) -> Result<bool,StorageFailure> {
+		let mut statement = try!(self.prepare("SELECT * FROM geosub_tokens WHERE token = ?",
+									&[Value::String( String::from(token) )]));
+		Ok(try!(step(&mut statement)) == State::Row)
+	}
+	
+	pub fn try_gsn_tokens(&self) -> Result<Vec<(String,String)>,StorageFailure> {
+		self.try_tokens("SELECT token,spring FROM `geosub_tokens`", &[])
+	}
+	
+	pub fn try_gsn_metaspace(&self) -> Result<Vec<MetaspaceEntry>,StorageFailure> {
+		let mut statement = try!(self.prepare("SELECT settlement,postcode,county,geosub FROM `geosub_metaspace` ORDER BY id", &[]));
+		
+		let mut v: Vec<MetaspaceEntry> = Vec::new();
+		while let State::Row = try!(step(&mut statement)) {
+			v.push(MetaspaceEntry {
+				settlement: try!(statement.read::<String>(0)),
+				postcode: try!(statement.read::<String>(1)),
+				county: try!(statement.read::<String>(2)),
+				geosub: try!(statement.read::<String>(3)),
+			});
+		}
+		Ok(v)
+	}
+	
+	/*
+	 * The `Netspace` token writes have nowhere to report a failure;
+	 * these do, as the node mutators do.
+	 */
+	
+	pub fn try_gsn_add_token(&self, token: &str, springname: &str) -> Result<Success,NetspaceFailure> {
+		self.audited(|| {
+			try!(self.execute("INSERT INTO `geosub_tokens` (spring,token) VALUES (?,?)",
+								&[
+									Value::String( springname.to_string() ),
+									Value::String( token.to_string() ),
+								]));
+			self.audit_record("token_add", springname, "", &token_record(token))
+		})
+	}
+	
+	pub fn try_gsn_remove_token(&self, token: &str) -> Result<Success,NetspaceFailure> {
+		self.audited(|| {
+			let owners = try!(self.try_tokens("SELECT token,spring FROM `geosub_tokens` WHERE token = ?",
+								&[Value::String( token.to_string() )]));
+			
+			try!(self.execute("DELETE FROM `geosub_tokens` WHERE token = ?",
+								&[Value::String( token.to_string() )]));
+			
+			for (token, spring) in owners {
+				try!(self.audit_record("token_remove", &spring, &token_record(&token), ""));
+			}
+			Ok(())
+		})
+	}
+	
+	pub fn try_gsn_remove_token_by_springname(&self, springname: &str) -> Result<Success,NetspaceFailure> {
+		self.audited(|| {
+			let owned = try!(self.try_tokens("SELECT token,spring FROM `geosub_tokens` WHERE spring=?",
+								&[Value::String( springname.to_string() )]));
+			
+			try!(self.execute("DELETE FROM `geosub_tokens` WHERE spring = ?",
+								&[Value::String( springname.to_string() )]));
+			
+			for (token, _) in owned {
+				try!(self.audit_record("token_remove", springname, &token_record(&token), ""));
+			}
+			Ok(())
+		})
+	}
+	
+	/// Make a change and the audit entries recording it in one
+	/// transaction, so neither is ever kept without the other
+	pub fn audited<F>(&self, change: F) -> Result<Success,NetspaceFailure>
+		where F: FnOnce() -> Result<(),StorageFailure>
+	{
+		let result = self.transaction().and_then(|t| {
+			try!(change());
+			t.commit()
+		});
+		
+		match result {
+			Ok(_) => Ok(Success::Ok),
+			Err(e) => {
+				self.report(&e);
+				Err(e.to_netspace_failure())
+			}
+		}
+	}
+	
+	/// A ledger stamp was written; peers are told once it is committed
+	pub fn stamped(&self) {
+		match self.depth.get() {
+			0 => ledger::notify(),
+			_ => self.stamped.set(true)
+		}
+	}
+	
+	fn closed(&self, committed: bool) {
+		self.depth.set(self.depth.get() - 1);
+		if self.depth.get() > 0 { return }
+		
+		if self.stamped.get() && committed { ledger::notify() }
+		self.stamped.set(false);
+	}
+	
+	fn report(&self, failure: &StorageFailure) {
+		log_error!("netspace", "Netspace storage failure: {:?}", failure);
+	}
+	
+	fn prepare(&self, query: &str, values: &[Value]) -> Result<Statement,StorageFailure> {
+		let mut statement = try!(self.db.prepare(query));
+		
+		for (i, value) in values.iter().enumerate() {
+			try!(statement.bind(i+1, value));
+		}
+		
+		Ok(statement)
+	}
+	
+	fn execute(&self, query: &str, values: &[Value]) -> Result<(),StorageFailure> {
+		let mut statement = try!(self.prepare(query, values));
+		try!(step(&mut statement));
+		Ok(())
+	}
+	
+	fn try_nodes(&self, query: &str, values: &[Value]) -> Result<Vec<Node>,StorageFailure> {
+		self.prepare(query, values)
+			.and_then(|mut statement| self.vector_from_statement(&mut statement))
+	}
+	
+	fn nodes(&self, query: &str, values: &[Value]) -> Vec<Node> {
+		match self.try_nodes(query, values) {
+			Ok(v) => v,
+			Err(e) => {
+				self.report(&e);
+				Vec::new()
+			}
+		}
+	}
+	
+	fn node(&self, query: &str, values: &[Value]) -> Result<Node,NetspaceFailure> {
+		let result = self.prepare(query, values)
+						.and_then(|mut statement| self.node_from_statement(&mut statement));
+		
+		match result {
+			Ok(r) => r,
+			Err(e) => {
+				self.report(&e);
+				Err(e.to_netspace_failure())
+			}
+		}
+	}
+	
+	fn try_tokens(&self, query: &str, values: &[Value]) -> Result<Vec<(String,String)>,StorageFailure> {
+		self.prepare(query, values).and_then(|mut statement| {
+			let mut v : Vec<(String,String)> = Vec::new();
+			while let State::Row = try!(step(&mut statement)) {
+				let token = try!(statement.read::<String>(0));
+				let spring = try!(statement.read::<String>(1));
+				v.push((token,spring));
+			}
+			Ok(v)
+		})
+	}
+	
+	fn tokens(&self, query: &str, values: &[Value]) -> Vec<(String,String)> {
+		match self.try_tokens(query, values) {
+			Ok(v) => v,
+			Err(e) => {
+				self.report(&e);
+				Vec::new()
+			}
+		}
+	}
+	
+	fn fill_node(&self, statement: &sqlite::Statement) -> Result<Node,StorageFailure> {
+		let spring = try!(statement.read::<String>(1));
+		let host = try!(statement.read::<String>(2));
+		let addr = try!(statement.read::<String>(3));
+		let service = NodeService::from_i64(try!(statement.read::<i64>(4)));
+		let state = NodeState::from_i64(try!(statement.read::<i64>(5)));
+		let role =  NodeRole::from_i64(try!(statement.read::<i64>(6)));
+		let key = try!(statement.read::<String>(7));
 		
 		Ok(Node::new(&spring, &host, &addr, service, state, role, &key))
 	}
 	
-	fn vector_from_statement(&self, statement: &mut Statement) -> Vec<Node> {
+	fn vector_from_statement(&self, statement: &mut Statement) -> Result<Vec<Node>,StorageFailure> {
 		
 		let mut v: Vec<Node> = Vec::new();
 		
-		while let State::Row = statement.next().unwrap() {
-			match self.fill_node(&statement) {
-				Ok(node) => v.push(node),
-				_ => {}
-			}; 		   
+		while let State::Row = try!(step(statement)) {
+			v.push(try!(self.fill_node(&statement)));
 		}
 		
-		v
+		Ok(v)
 	}
 	
-	fn node_from_statement(&self, statement: &mut Statement) -> Result<Node,NetspaceFailure> {
-
-		match statement.next() {
-			Ok(state) => match state {
-				
-							State::Row => self.fill_node(&statement),
-			 				_ => Err(NetspaceFailure::NodeNotFound)
-			 				
-						},
-
-			_ => Err(NetspaceFailure::DatabaseError)
+	fn node_from_statement(&self, statement: &mut Statement) -> Result<Result<Node,NetspaceFailure>,StorageFailure> {
 
-		}
+		Ok(match try!(step(statement)) {
+			State::Row => Ok(try!(self.fill_node(&statement))),
+			_ => Err(NetspaceFailure::NodeNotFound)
+		})
 		
 	}
 	
 	#[allow(dead_code)]
 	fn debug_print_rows(&self, statement: &mut Statement) {
 		
-		while let State::Row = statement.next().unwrap() {
+		while let Ok(State::Row) = statement.next() {
 			
-			println!("id = {}", statement.read::<i64>(0).unwrap());
-			println!("spring = {}", statement.read::<String>(1).unwrap());
-			println!("host = {}", statement.read::<String>(2).unwrap());
-			println!("address = {}", statement.read::<String>(3).unwrap());
-			println!("service = {}", statement.read::<i64>(4).unwrap());
-			println!("status = {}", statement.read::<i64>(5).unwrap());
-			println!("types = {}", statement.read::<i64>(6).unwrap());
-			println!("key = {}", statement.read::<i64>(7).unwrap());
+			println!("id = {:?}", statement.read::<i64>(0));
+			println!("spring = {:?}", statement.read::<String>(1));
+			println!("host = {:?}", statement.read::<String>(2));
+			println!("address = {:?}", statement.read::<String>(3));
+			println!("service = {:?}", statement.read::<i64>(4));
+			println!("status = {:?}", statement.read::<i64>(5));
+			println!("types = {:?}", statement.read::<i64>(6));
+			println!("key = {:?}", statement.read::<String>(7));
 			    			
 		}
 		
@@ -101,70 +456,155 @@ impl NetspaceIo {
 	}
 }
 
+/// What the primary's handlers need from a netspace on top of
+/// `Netspace`; `Svr` holds one so handlers stay off the backend
+pub trait PrimaryNetspace : Netspace {
+	/// The same netspace as a plain `Netspace`
+	fn netspace(&self) -> &Netspace;
+	
+	fn try_gsn_nodes(&self) -> Result<Vec<Node>,StorageFailure>;
+	fn try_gsn_nodes_by_type(&self, types: NodeRole) -> Result<Vec<Node>,StorageFailure>;
+	fn try_gsn_check_token(&self, token: &str) -> Result<bool,StorageFailure>;
+	
+	fn transaction(&self) -> Result<Transaction,StorageFailure>;
+	
+	fn pending_add(&self, springname: &str, address: &str) -> Result<(),StorageFailure>;
+	fn pending_is(&self, springname: &str) -> bool;
+	
+	fn gtn_roots(&self, geosub: Option<&str>) -> Result<Vec<GeotopRoot>,StorageFailure>;
+	fn gtn_root_announce(&self, root: &GeotopRoot) -> Result<Success,NetspaceFailure>;
+	
+	fn ledger_entry(&self, springname: &str, local: &str) -> Result<Option<LedgerEntry>,StorageFailure>;
+	fn ledger_entries(&self, local: &str) -> Result<Vec<LedgerEntry>,StorageFailure>;
+	
+	fn throttle_hit(&self, address: &str, counter: &str, limit: i64) -> Result<bool,StorageFailure>;
+	fn throttle_failure(&self, address: &str, limits: &ThrottleLimits) -> Result<bool,StorageFailure>;
+	fn throttle_banned(&self, address: &str) -> Result<bool,StorageFailure>;
+	
+	fn acl_permits(&self, command: &str, module: Option<&str>, addr: &IpAddr, default: bool) -> Result<bool,StorageFailure>;
+}
+
+impl PrimaryNetspace for NetspaceIo {
+	fn netspace(&self) -> &Netspace {
+		self
+	}
+	
+	fn try_gsn_nodes(&self) -> Result<Vec<Node>,StorageFailure> {
+		NetspaceIo::try_gsn_nodes(self)
+	}
+	
+	fn try_gsn_nodes_by_type(&self, types: NodeRole) -> Result<Vec<Node>,StorageFailure> {
+		NetspaceIo::try_gsn_nodes_by_type(self, types)
+	}
+	
+	fn try_gsn_check_token(&self, token: &str) -> Result<bool,StorageFailure> {
+		NetspaceIo::try_gsn_check_token(self, token)
+	}
+	
+	fn transaction(&self) -> Result<Transaction,StorageFailure> {
+		NetspaceIo::transaction(self)
+	}
+	
+	fn pending_add(&self, springname: &str, address: &str) -> Result<(),StorageFailure> {
+		NetspaceIo::pending_add(self, springname, address)
+	}
+	
+	fn pending_is(&self, springname: &str) -> bool {
+		NetspaceIo::pending_is(self, springname)
+	}
+	
+	fn gtn_roots(&self, geosub: Option<&str>) -> Result<Vec<GeotopRoot>,StorageFailure> {
+		NetspaceIo::gtn_roots(self, geosub)
+	}
+	
+	fn gtn_root_announce(&self, root: &GeotopRoot) -> Result<Success,NetspaceFailure> {
+		NetspaceIo::gtn_root_announce(self, root)
+	}
+	
+	fn ledger_entry(&self, springname: &str, local: &str) -> Result<Option<LedgerEntry>,StorageFailure> {
+		NetspaceIo::ledger_entry(self, springname, local)
+	}
+	
+	fn ledger_entries(&self, local: &str) -> Result<Vec<LedgerEntry>,StorageFailure> {
+		NetspaceIo::ledger_entries(self, local)
+	}
+	
+	fn throttle_hit(&self, address: &str, counter: &str, limit: i64) -> Result<bool,StorageFailure> {
+		NetspaceIo::throttle_hit(self, address, counter, limit)
+	}
+	
+	fn throttle_failure(&self, address: &str, limits: &ThrottleLimits) -> Result<bool,StorageFailure> {
+		NetspaceIo::throttle_failure(self, address, limits)
+	}
+	
+	fn throttle_banned(&self, address: &str) -> Result<bool,StorageFailure> {
+		NetspaceIo::throttle_banned(self, address)
+	}
+	
+	fn acl_permits(&self, command: &str, module: Option<&str>, addr: &IpAddr, default: bool) -> Result<bool,StorageFailure> {
+		NetspaceIo::acl_permits(self, command, module, addr, default)
+	}
+}
+
+pub struct Transaction<'a> {
+	nio: &'a NetspaceIo,
+	open: bool,
+}
+
+impl<'a> Transaction<'a> {
+	pub fn commit(mut self) -> Result<(),StorageFailure> {
+		try!(self.nio.db.execute("RELEASE `netspace`"));
+		self.open = false;
+		self.nio.closed(true);
+		Ok(())
+	}
+	
+	pub fn rollback(mut self) -> Result<(),StorageFailure> {
+		self.open = false;
+		self.nio.closed(false);
+		try!(self.nio.db.execute("ROLLBACK TO `netspace`; RELEASE `netspace`"));
+		Ok(())
+	}
+}
+
+impl<'a> Drop for Transaction<'a> {
+	fn drop(&mut self) {
+		if self.open {
+			self.nio.closed(false);
+			let _ = self.nio.db.execute("ROLLBACK TO `netspace`; RELEASE `netspace`");
+		}
+	}
+}
+
 impl Netspace for NetspaceIo {
 
 	fn gsn_nodes(&self) -> Vec<Node> {
-		let mut statement = self.db.prepare("
-	    	SELECT * FROM geosub_netspace
-			").unwrap();
-			
-			self.vector_from_statement(&mut statement)
+		self.try_gsn_nodes().unwrap_or_else(|e| { self.report(&e); Vec::new() })
 	}
 	
 	fn gsn_nodes_by_address(&self, address: &str) -> Vec<Node> {
-		
-		let mut statement = self.db.prepare("
-    	SELECT * FROM geosub_netspace WHERE address = ?
-		").unwrap();
-
-		statement.bind(1, &sqlite::Value::String( String::from(address) ) ).unwrap();
-		
-		self.vector_from_statement(&mut statement)
-		
+		self.try_gsn_nodes_by_address(address).unwrap_or_else(|e| { self.report(&e); Vec::new() })
 	}
 
 	
 	fn gsn_nodes_by_type(&self, types: NodeRole) -> Vec<Node> {
-		
-		let mut statement = self.db.prepare("
-    	SELECT * FROM geosub_netspace WHERE types & ?
-		").unwrap();
-
-		statement.bind(1, &sqlite::Value::Integer( types as i64 ) ).unwrap();
-		
-		self.vector_from_statement(&mut statement)
+		self.nodes("SELECT * FROM geosub_netspace WHERE types & ?",
+					&[Value::Integer( types as i64 )])
 	}
 
 	fn gsn_nodes_by_state(&self, state: NodeState) -> Vec<Node> {
-		
-		let mut statement = self.db.prepare("
-    	SELECT * FROM geosub_netspace WHERE status = ?
-		").unwrap();
-
-		statement.bind(1, &sqlite::Value::Integer( state as i64 ) ).unwrap();
-		
-		self.vector_from_statement(&mut statement)
+		self.nodes("SELECT * FROM geosub_netspace WHERE status = ?",
+					&[Value::Integer( state as i64 )])
 	}
 	
 	fn gsn_node_by_springname(&self, name: &str) -> Result<Node, NetspaceFailure> {
-		
-		let mut statement = self.db.prepare("
-    	SELECT * FROM geosub_netspace WHERE springname = ?
-		").unwrap();
-
-		statement.bind(1, &sqlite::Value::String( String::from(name) ) ).unwrap();
-		self.node_from_statement(&mut statement)
+		self.node("SELECT * FROM geosub_netspace WHERE springname = ?",
+					&[Value::String( String::from(name) )])
 	}
 	
 	fn gsn_node_by_hostname(&self, name: &str) -> Result<Node,NetspaceFailure> {
-		
-		let mut statement = self.db.prepare("
-    	SELECT * FROM geosub_netspace WHERE hostname = ?
-		").unwrap();
-
-		statement.bind(1, &sqlite::Value::String( String::from(name) ) ).unwrap();
-		self.node_from_statement(&mut statement)
-
+		self.node("SELECT * FROM geosub_netspace WHERE hostname = ?",
+					&[Value::String( String::from(name) )])
 	}
 	
 	fn gtn_root_nodes(&self) -> Vec<Node> {
@@ -174,307 +614,286 @@ impl Netspace for NetspaceIo {
 	}
 	fn gtn_geosubs(&self) -> Vec<String> {
 		
-		let mut statement = self.db.prepare(
-			"SELECT DISTINCT `geosub` FROM 
-			`geotop_netspace`
-			").unwrap();
-		
-		let mut v: Vec<String> = Vec::new();
-		
-		while let State::Row = statement.next().unwrap() {
-			v.push(statement.read::<String>(0).unwrap()); 		   
+		let result = self.prepare("SELECT DISTINCT `geosub` FROM `geotop_netspace`", &[])
+			.and_then(|mut statement| {
+				let mut v: Vec<String> = Vec::new();
+				while let State::Row = try!(step(&mut statement)) {
+					v.push(try!(statement.read::<String>(0)));
+				}
+				Ok(v)
+			});
+		
+		match result {
+			Ok(v) => v,
+			Err(e) => {
+				self.report(&e);
+				Vec::new()
+			}
 		}
-		
-		v
 	}
 	
 	
 	fn gsn_node_register(&self, node: &Node) -> Result<Success,NetspaceFailure> {
 		
-		if self.gsn_node_by_springname(node.springname()).is_ok() {
-			return Err(NetspaceFailure::DuplicateNode)
+		match self.gsn_node_by_springname(node.springname()) {
+			Ok(_) => return Err(NetspaceFailure::DuplicateNode),
+			Err(NetspaceFailure::NodeNotFound) => { },
+			Err(e) => return Err(e)
 		}
 		
-		let mut statement = self.db.prepare(
-						"INSERT INTO 
-						`geosub_netspace` 
-						(springname,hostname,address,service,status,types,key) 
-						VALUES (?,?,?,?,?,?,?)").unwrap();
-		statement.bind(1, &sqlite::Value::String( String::from(node.springname()) ) ).unwrap();
-		statement.bind(2, &sqlite::Value::String( String::from(node.hostfield()) ) ).unwrap();
-		statement.bind(3, &sqlite::Value::String( String::from(node.address()) )).unwrap();
-		statement.bind(4, &sqlite::Value::Integer( node.service() as i64 ) ).unwrap();
-		
 		// Regardless of what is set -- the node should be disabled when it is registered
-		statement.bind(5, &sqlite::Value::Integer( NodeState::Disabled as i64 ) ).unwrap();
-		statement.bind(6, &sqlite::Value::Integer( node.role() as i64 ) ).unwrap();
-		statement.bind(7, &sqlite::Value::String( String::from(node.key() ) ) ).unwrap();
-		match statement.next() {
-			Ok(_) => Ok(Success::Ok),
-			Err(_) => Err(NetspaceFailure::NodeNotFound)   
-		}
+		let mut registered = node.clone();
+		registered.update_state(NodeState::Disabled);
 		
+		self.audited(|| {
+			try!(self.execute("INSERT INTO 
+						`geosub_netspace` 
+						(springname,hostname,address,service,status,types,key) 
+						VALUES (?,?,?,?,?,?,?)",
+						&[
+							Value::String( String::from(node.springname()) ),
+							Value::String( String::from(node.hostfield()) ),
+							Value::String( String::from(node.address()) ),
+							Value::Integer( node.service() as i64 ),
+							Value::Integer( NodeState::Disabled as i64 ),
+							Value::Integer( node.role() as i64 ),
+							Value::String( String::from(node.key() ) ),
+						]));
+			try!(self.ledger_touch(node.springname(), false));
+			self.audit_record("register", node.springname(), "", &node_record(&registered))
+		})
 	}
 
 	fn gsn_node_unregister(&self, node: &Node) -> Result<Success,NetspaceFailure> {
-		if self.gsn_node_by_springname(node.springname()).is_err() {
-			return Err(NetspaceFailure::NodeNotFound)
-		}
-		
-		let mut statement = self.db.prepare(
-						"DELETE FROM 
-						`geosub_netspace` WHERE 
-						springname = ?").unwrap();
-		statement.bind(1, &sqlite::Value::String( String::from(node.springname()) ) ).unwrap();
+		let old = try!(self.gsn_node_by_springname(node.springname()));
 		
-		match statement.next() {
-			Ok(_) => Ok(Success::Ok),
-			Err(_) => Err(NetspaceFailure::DatabaseError)   
-		}		
+		self.audited(|| {
+			try!(self.execute("DELETE FROM `geosub_netspace` WHERE springname = ?",
+						&[Value::String( String::from(node.springname()) )]));
+			try!(self.ledger_touch(node.springname(), true));
+			self.audit_record("unregister", node.springname(), &node_record(&old), "")
+		})
 	}
 
 	fn gsn_node_update_state(&self, node: &Node) -> Result<Success,NetspaceFailure> {
-	
-		if self.gsn_node_by_springname(node.springname()).is_err() {
-			return Err(NetspaceFailure::NodeNotFound)
-		}
-		
-		let mut statement = self.db.prepare(
-						"UPDATE  
-						`geosub_netspace`
-						SET status = ?
-						WHERE springname = ?").unwrap();
-		
-		statement.bind(1, &sqlite::Value::Integer( node.state() as i64 ) ).unwrap();
-		statement.bind(2, &sqlite::Value::String( String::from(node.springname()) ) ).unwrap();
-		
-		match statement.next() {
-			Ok(_) => Ok(Success::Ok),
-			Err(_) => Err(NetspaceFailure::DatabaseError)   
-		}
+		let old = try!(self.gsn_node_by_springname(node.springname()));
+		
+		self.audited(|| {
+			try!(self.execute("UPDATE `geosub_netspace` SET status = ? WHERE springname = ?",
+						&[
+							Value::Integer( node.state() as i64 ),
+							Value::String( String::from(node.springname()) ),
+						]));
+			try!(self.ledger_touch(node.springname(), false));
+			self.audit_record("update_state", node.springname(), &format!("{}", old.state()), &format!("{}", node.state()))
+		})
 	}
 	
 	fn gsn_node_update_role(&self, node: &Node) -> Result<Success,NetspaceFailure> {
-	
-		if self.gsn_node_by_springname(node.springname()).is_err() {
-			return Err(NetspaceFailure::NodeNotFound)
-		}
-		
-		let mut statement = self.db.prepare(
-						"UPDATE  
-						`geosub_netspace`
-						SET types = ?
-						WHERE springname = ?").unwrap();
+		let old = try!(self.gsn_node_by_springname(node.springname()));
 		
-		statement.bind(1, &sqlite::Value::Integer( node.role() as i64 ) ).unwrap();
-		statement.bind(2, &sqlite::Value::String( String::from(node.springname()) ) ).unwrap();
-		
-		match statement.next() {
-			Ok(_) => Ok(Success::Ok),
-			Err(_) => Err(NetspaceFailure::DatabaseError)   
-		}
+		self.audited(|| {
+			try!(self.execute("UPDATE `geosub_netspace` SET types = ? WHERE springname = ?",
+						&[
+							Value::Integer( node.role() as i64 ),
+							Value::String( String::from(node.springname()) ),
+						]));
+			try!(self.ledger_touch(node.springname(), false));
+			self.audit_record("update_role", node.springname(), &format!("{}", old.role()), &format!("{}", node.role()))
+		})
 	}
 
 	fn gsn_node_update_service(&self, node: &Node) -> Result<Success,NetspaceFailure> {
-	
-		if self.gsn_node_by_springname(node.springname()).is_err() {
-			return Err(NetspaceFailure::NodeNotFound)
-		}
-		
-		let mut statement = self.db.prepare(
-						"UPDATE  
-						`geosub_netspace`
-						SET service = ?
-						WHERE springname = ?").unwrap();
-		
-		statement.bind(1, &sqlite::Value::Integer( node.service() as i64 ) ).unwrap();
-		statement.bind(2, &sqlite::Value::String( String::from(node.springname()) ) ).unwrap();
-		
-		match statement.next() {
-			Ok(_) => Ok(Success::Ok),
-			Err(_) => Err(NetspaceFailure::NodeNotFound)   
-		}
+		let old = try!(self.gsn_node_by_springname(node.springname()));
+		
+		self.audited(|| {
+			try!(self.execute("UPDATE `geosub_netspace` SET service = ? WHERE springname = ?",
+						&[
+							Value::Integer( node.service() as i64 ),
+							Value::String( String::from(node.springname()) ),
+						]));
+			try!(self.ledger_touch(node.springname(), false));
+			self.audit_record("update_service", node.springname(), &format!("{}", old.service()), &format!("{}", node.service()))
+		})
 	}
 	
 	fn gsn_node_update_hostname(&self, node: &Node) ->  Result<Success,NetspaceFailure> {
-		if self.gsn_node_by_springname(node.springname()).is_err() {
-			return Err(NetspaceFailure::NodeNotFound)
-		}
-		let mut statement = self.db.prepare(
-						"UPDATE  
-						`geosub_netspace`
-						SET hostname = ?
-						WHERE springname = ?").unwrap();
-		
-		statement.bind(1, &sqlite::Value::String( node.hostname().to_string() ) ).unwrap();
-		statement.bind(2, &sqlite::Value::String( String::from(node.springname()) ) ).unwrap();
-		
-		match statement.next() {
-			Ok(_) => Ok(Success::Ok),
-			Err(_) => Err(NetspaceFailure::NodeNotFound)   
-		}
+		let old = try!(self.gsn_node_by_springname(node.springname()));
+		
+		self.audited(|| {
+			try!(self.execute("UPDATE `geosub_netspace` SET hostname = ? WHERE springname = ?",
+						&[
+							Value::String( node.hostname().to_string() ),
+							Value::String( String::from(node.springname()) ),
+						]));
+			try!(self.ledger_touch(node.springname(), false));
+			self.audit_record("update_hostname", node.springname(), &old.hostname().to_string(), &node.hostname().to_string())
+		})
 	}
 	
 	fn gsn_node_update_address(&self, node: &Node) ->  Result<Success,NetspaceFailure> {
-		if self.gsn_node_by_springname(node.springname()).is_err() {
-			return Err(NetspaceFailure::NodeNotFound)
-		}
-		let mut statement = self.db.prepare(
-						"UPDATE  
-						`geosub_netspace`
-						SET address = ?
-						WHERE springname = ?").unwrap();
-		
-		statement.bind(1, &sqlite::Value::String( node.address().to_string() ) ).unwrap();
-		statement.bind(2, &sqlite::Value::String( String::from(node.springname()) ) ).unwrap();
-		
-		match statement.next() {
-			Ok(_) => Ok(Success::Ok),
-			Err(_) => Err(NetspaceFailure::NodeNotFound)   
-		}
+		let old = try!(self.gsn_node_by_springname(node.springname()));
+		
+		self.audited(|| {
+			try!(self.execute("UPDATE `geosub_netspace` SET address = ? WHERE springname = ?",
+						&[
+							Value::String( node.address().to_string() ),
+							Value::String( String::from(node.springname()) ),
+						]));
+			try!(self.ledger_touch(node.springname(), false));
+			self.audit_record("update_address", node.springname(), &old.address().to_string(), &node.address().to_string())
+		})
 	}
 	
 	fn gtn_geosub_root_nodes(&self, gsn: &str) -> Vec<Node> {
-		let mut statement = self.db.prepare("
-	    	SELECT * FROM `geotop_netspace`
-	    	WHERE geosub = ?
-	    	ORDER BY priority ASC
-			").unwrap();
-		
-		statement.bind(1, &sqlite::Value::String( String::from(gsn) ) ).unwrap();
-			
-		self.vector_from_statement(&mut statement)		
+		self.nodes("SELECT * FROM `geotop_netspace`
+					WHERE geosub = ?
+					ORDER BY priority ASC",
+					&[Value::String( String::from(gsn) )])
 	}
 	
 	
 	fn gtn_geosub_node_by_springname(&self, name: &str, gsn: &str) -> Result<Node,NetspaceFailure> {
-		let mut statement = self.db.prepare("
-    	SELECT * FROM geotop_netspace 
-    	WHERE springname = ?
-    	AND geosub = ?
-		").unwrap();
-
-		statement.bind(1, &sqlite::Value::String( String::from(name) ) ).unwrap();
-		statement.bind(2, &sqlite::Value::String( String::from(gsn)  ) ).unwrap();
-		
-		self.node_from_statement(&mut statement)
+		self.node("SELECT * FROM geotop_netspace 
+					WHERE springname = ?
+					AND geosub = ?",
+					&[
+						Value::String( String::from(name) ),
+						Value::String( String::from(gsn) ),
+					])
 	}
 
 	fn gtn_geosub_register_node(&self, node: &Node, gsn: &str) -> Result<Success,NetspaceFailure> {
 		
-		if self.gtn_geosub_node_by_springname(node.springname(), &gsn).is_ok() {
-			return Err(NetspaceFailure::DuplicateNode)
+		match self.gtn_geosub_node_by_springname(node.springname(), &gsn) {
+			Ok(_) => return Err(NetspaceFailure::DuplicateNode),
+			Err(NetspaceFailure::NodeNotFound) => { },
+			Err(e) => return Err(e)
 		}
 		
-		let mut statement = self.db.prepare(
-						"INSERT INTO 
+		self.audited(|| {
+			try!(self.execute("INSERT INTO 
 						`geotop_netspace` 
-						(springname,hostname,address,service,priority, geosub,key) 
-						VALUES (?,?,?,?,?,?,?)").unwrap();
-		statement.bind(1, &sqlite::Value::String( String::from(node.springname()) ) ).unwrap();
-		statement.bind(2, &sqlite::Value::String( String::from(node.hostname()) ) ).unwrap();
-		statement.bind(3, &sqlite::Value::String( String::from(node.address()) ) ).unwrap();
-		statement.bind(4, &sqlite::Value::Integer( node.service() as i64 ) ).unwrap();
-		statement.bind(5, &sqlite::Value::Integer( 1 as i64)).unwrap();
-		statement.bind(6, &sqlite::Value::String( String::from(gsn) )).unwrap();
-		statement.bind(7, &sqlite::Value::String( String::from(node.key()) )).unwrap();
-			
-		match statement.next() {
-			Ok(_) => Ok(Success::Ok),
-			Err(_) => Err(NetspaceFailure::NodeNotFound)   
-		}	
+						(springname,hostname,address,service,priority,geosub,key) 
+						VALUES (?,?,?,?,?,?,?)",
+						&[
+							Value::String( String::from(node.springname()) ),
+							Value::String( String::from(node.hostname()) ),
+							Value::String( String::from(node.address()) ),
+							Value::Integer( node.service() as i64 ),
+							Value::Integer( 1 as i64 ),
+							Value::String( String::from(gsn) ),
+							Value::String( String::from(node.key()) ),
+						]));
+			self.audit_record("geotop_register", node.springname(), "", &format!("geosub:{},{}", gsn, node_record(node)))
+		})
 	}
 
 	fn gtn_geosub_unregister_node(&self, node: &Node, gsn: &str) -> Result<Success,NetspaceFailure> {
+		let old = try!(self.gtn_geosub_node_by_springname(node.springname(), &gsn));
 
-		if self.gtn_geosub_node_by_springname(node.springname(), &gsn).is_err() {
-			return Err(NetspaceFailure::NodeNotFound)
-		}
-
-		let mut statement = self.db.prepare(
-						"DELETE FROM `geotop_netspace` 
+		self.audited(|| {
+			try!(self.execute("DELETE FROM `geotop_netspace` 
 						WHERE springname = ?
-						AND geosub = ?").unwrap();
-
-		statement.bind(1, &sqlite::Value::String( String::from(node.springname()) ) ).unwrap();
-		statement.bind(2, &sqlite::Value::String( String::from(gsn) ) ).unwrap();
-
-		match statement.next() {
-			Ok(_) => Ok(Success::Ok),
-			Err(_) => Err(NetspaceFailure::NodeNotFound)   
-		}				
+						AND geosub = ?",
+						&[
+							Value::String( String::from(node.springname()) ),
+							Value::String( String::from(gsn) ),
+						]));
+			self.audit_record("geotop_unregister", node.springname(), &format!("geosub:{},{}", gsn, node_record(&old)), "")
+		})
 	}
 	
 	fn gsn_check_token(&self, token: &str) -> bool {
-		let mut statement = self.db().prepare("
-	    	SELECT * FROM geosub_tokens WHERE token = ?
-			").unwrap();
-	
-		statement.bind(1, &sqlite::Value::String( String::from( String::from(token) ) ) ).unwrap();
-			
-		match statement.next() {
-			Ok(State::Row) => true,
-			_ => false
-		}
-			
+		self.try_gsn_check_token(token).unwrap_or_else(|e| { self.report(&e); false })
 	}
 	
 	fn gsn_add_token(&self, token: &str, springname: &str) {
-		let mut statement = self.db.prepare(
-						"INSERT INTO 
-						`geosub_tokens` 
-						(spring,token) 
-						VALUES (?,?)").unwrap();
-		
-		statement.bind(1, &sqlite::Value::String( springname.to_string() )).unwrap();
-		statement.bind(2, &sqlite::Value::String( token.to_string() )).unwrap();
-		
-		let _ = statement.next();
+		let _ = self.try_gsn_add_token(token, springname);
 	}
 	
 	fn gsn_remove_token(&self, token: &str) {
-		let mut statement = self.db.prepare(
-						"DELETE FROM `geosub_tokens` 
-						WHERE token = ?").unwrap();
-		statement.bind(1, &sqlite::Value::String( token.to_string() )).unwrap();
-		let _ = statement.next();
+		let _ = self.try_gsn_remove_token(token);
 	}
 	
 	fn gsn_tokens(&self) -> Vec<(String,String)> {
-		let mut statement = self.db().prepare("
-	    	SELECT token,spring FROM `geosub_tokens`").unwrap();
-		
-		let mut v : Vec<(String,String)> = Vec::new();
-		while let State::Row = statement.next().unwrap() {
-			let token = statement.read::<String>(0).unwrap();
-			let spring = statement.read::<String>(1).unwrap();
-			v.push((token,spring));
-		}
-		
-		v
+		self.tokens("SELECT token,spring FROM `geosub_tokens`", &[])
 	}
 	
 	fn gsn_token_by_springname(&self, springname: &str) -> Vec<(String,String)> {
-		let mut statement = self.db().prepare("
-	    	SELECT token,spring FROM `geosub_tokens` WHERE spring=?").unwrap();
-		
-		statement.bind(1, &sqlite::Value::String( springname.to_string() )).unwrap();
-		let mut v : Vec<(String,String)> = Vec::new();
-		while let State::Row = statement.next().unwrap() {
-			let token = statement.read::<String>(0).unwrap();
-			let spring = statement.read::<String>(1).unwrap();
-			v.push((token,spring));
-		}
-		
-		v
+		self.tokens("SELECT token,spring FROM `geosub_tokens` WHERE spring=?",
+					&[Value::String( springname.to_string() )])
 	}
 	
 	fn gsn_remove_token_by_springname(&self, springname: &str) {
-	let mut statement = self.db.prepare(
-						"DELETE FROM `geosub_tokens` 
-						WHERE spring = ?").unwrap();
-		statement.bind(1, &sqlite::Value::String( springname.to_string() )).unwrap();
-		let _ = statement.next();		
+		let _ = self.try_gsn_remove_token_by_springname(springname);
+	}
+}
+
+/// A settlement to geosub mapping in the metaspace
+#[derive(Debug,Clone,PartialEq)]
+pub struct MetaspaceEntry {
+	pub settlement: String,
+	pub postcode: String,
+	pub county: String,
+	pub geosub: String,
+}
+
+impl MetaspaceEntry {
+	pub fn new(settlement: &str, postcode: &str, county: &str, geosub: &str) -> MetaspaceEntry {
+		MetaspaceEntry {
+			settlement: settlement.to_string(),
+			postcode: postcode.to_string(),
+			county: county.to_string(),
+			geosub: geosub.to_string(),
+		}
+	}
+}
+
+impl fmt::Display for MetaspaceEntry {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{},{},{},{}", self.settlement, self.postcode, self.county, self.geosub)
+	}
+}
+
+/// The metaspace is not part of the `Netspace` interface
+/// so it gets its own trait for backends that keep one
+pub trait Metaspace {
+	fn gsn_metaspace(&self) -> Vec<MetaspaceEntry>;
+	fn gsn_metaspace_add(&self, entry: &MetaspaceEntry) -> Result<Success,NetspaceFailure>;
+	fn gsn_metaspace_remove(&self, entry: &MetaspaceEntry) -> Result<Success,NetspaceFailure>;
+}
+
+impl Metaspace for NetspaceIo {
+	fn gsn_metaspace(&self) -> Vec<MetaspaceEntry> {
+		self.try_gsn_metaspace().unwrap_or_else(|e| { self.report(&e); Vec::new() })
+	}
+	
+	fn gsn_metaspace_add(&self, entry: &MetaspaceEntry) -> Result<Success,NetspaceFailure> {
+		self.audited(|| {
+			try!(self.execute("INSERT INTO `geosub_metaspace` (settlement,postcode,county,geosub) VALUES (?,?,?,?)",
+								&[
+									Value::String( entry.settlement.clone() ),
+									Value::String( entry.postcode.clone() ),
+									Value::String( entry.county.clone() ),
+									Value::String( entry.geosub.clone() ),
+								]));
+			self.audit_record("metaspace_add", &entry.geosub, "", &format!("{}", entry))
+		})
+	}
+	
+	fn gsn_metaspace_remove(&self, entry: &MetaspaceEntry) -> Result<Success,NetspaceFailure> {
+		self.audited(|| {
+			try!(self.execute("DELETE FROM `geosub_metaspace`
+								WHERE settlement = ? AND postcode = ? AND county = ? AND geosub = ?",
+								&[
+									Value::String( entry.settlement.clone() ),
+									Value::String( entry.postcode.clone() ),
+									Value::String( entry.county.clone() ),
+									Value::String( entry.geosub.clone() ),
+								]));
+			self.audit_record("metaspace_remove", &entry.geosub, &format!("{}", entry), "")
+		})
 	}
 }
 
@@ -513,15 +932,18 @@ pub fn netspace_add_self(ns: &Netspace, cfg: &Config) {
 	let s : String = format!("spring:{},host:{},address:{},service:dvsp,role:hub,state:enabled",cfg.springname(), cfg.hostname(), cfg.address());
 	let n = Node::from_str(&s).unwrap();
 	match ns.gsn_node_register(&n) {
-		Ok(_) => println!("[Node] Added self to netspace"),
-		Err(_) => println!("[Node] Ok - Already in netspace")
+		Ok(_) => log_info!("node", "Added self to netspace"),
+		Err(_) => log_info!("node", "Ok - Already in netspace")
 	}
 	
-	ns.gsn_node_update_state(&n).unwrap();
+	match ns.gsn_node_update_state(&n) {
+		Ok(_) => { },
+		Err(e) => log_error!("node", "Failed to enable self in netspace ({:?})", e)
+	}
 	
 	match ns.gtn_geosub_register_node(&n, &cfg.geosub()) {
-		Ok(_) => println!("[Node] Added self to geospace"),
-		Err(_) => println!("[Node] Ok - Already in geospace")
+		Ok(_) => log_info!("node", "Added self to geospace"),
+		Err(_) => log_info!("node", "Ok - Already in geospace")
 	}
 }
 
@@ -538,33 +960,6 @@ mod tests {
 	#[allow(dead_code)]
 	fn setup_netspace(db: &sqlite::Connection) {
 		db.execute("
-		CREATE TABLE `geosub_netspace` (
-			`id`			INTEGER PRIMARY KEY AUTOINCREMENT,
-			`springname`	TEXT UNIQUE,
-			`hostname`		TEXT,
-			`address`		TEXT,
-			`service`		INTEGER,
-			`status`		INTEGER,
-			`types`			INTEGER,
-			`key` 			TEXT
-		);
-		
-		CREATE TABLE `geotop_netspace` (
-			`id`			INTEGER PRIMARY KEY AUTOINCREMENT,
-			`springname`	TEXT,
-			`hostname`		TEXT,
-			`address`		TEXT,
-			`service`		INTEGER,
-			`priority`		INTEGER,
-			`geosub`		TEXT,
-			`key`			TEXT
-		);
-		CREATE TABLE `geosub_tokens` (
-			`id`	INTEGER PRIMARY KEY AUTOINCREMENT,
-			`token`	TEXT,
-			`spring` TEXT
-		);
-
 		INSERT INTO `geosub_netspace` (id,springname,hostname,address,service,status,types,key) VALUES (1,'esusx','greenman.zu','192.168.1.1',1,1,1,'PUBLIC KEY');
 		INSERT INTO `geosub_netspace` (id,springname,hostname,address,service,status,types,key) VALUES (2,'cci','dvsnode.greenman.zu','192.168.1.2',2,1,2,'PUBLIC KEY');
 		INSERT INTO `geotop_netspace` (id,springname,hostname,address,service,priority,geosub,key) VALUES (1,'springa', 'greenman', '192.168.1.2', 1, 2, 'esusx','PUBLIC KEY');
@@ -582,6 +977,19 @@ mod tests {
 		assert_eq!(2, v.len());
 	}
 
+	#[test]
+	fn ts_netspaceio_storage_failure_f() {
+
+		let nsio = NetspaceIo::new(":memory:");
+		nsio.db().execute("DROP TABLE `geosub_netspace`").unwrap();
+		
+		assert_eq!(0, nsio.gsn_nodes().len());
+		assert_eq!(Err(NetspaceFailure::DatabaseError), nsio.gsn_node_by_springname("esusx").map(|_| ()));
+		
+		let r = nsio.gsn_node_register(&Node::from_str("spring,host,192.172.1.1").unwrap());
+		assert_eq!(NetspaceFailure::DatabaseError, r.unwrap_err());
+	}
+
 	#[test]
 	fn ts_netspaceio_gsn_nodes_by_address_p() {
 
@@ -966,4 +1374,45 @@ mod tests {
 		assert_eq!(nsio.gtn_geosubs()[0], "esusx");
 	}
 	
-}
\ No newline at end of file
+	#[test]
+	fn ts_netspaceio_transaction_p() {
+		let nsio = NetspaceIo::new(":memory:");
+		setup_netspace(nsio.db());
+		
+		let t = nsio.transaction().unwrap();
+		nsio.db().execute("DELETE FROM `geosub_netspace`").unwrap();
+		assert_eq!(nsio.gsn_nodes().len(), 0);
+		t.commit().unwrap();
+		
+		assert_eq!(nsio.gsn_nodes().len(), 0);
+	}
+	
+	#[test]
+	fn ts_netspaceio_transaction_f() {
+		let nsio = NetspaceIo::new(":memory:");
+		setup_netspace(nsio.db());
+		let before = nsio.gsn_nodes().len();
+		
+		{
+			let _t = nsio.transaction().unwrap();
+			nsio.db().execute("DELETE FROM `geosub_netspace`").unwrap();
+		}
+		assert_eq!(nsio.gsn_nodes().len(), before);
+		
+		let t = nsio.transaction().unwrap();
+		nsio.db().execute("DELETE FROM `geosub_netspace`").unwrap();
+		t.rollback().unwrap();
+		assert_eq!(nsio.gsn_nodes().len(), before);
+	}
+	
+	#[test]
+	fn ts_netspaceio_token_write_f() {
+		let nsio = NetspaceIo::new(":memory:");
+		nsio.db().execute("DROP TABLE `geosub_tokens`").unwrap();
+		
+		assert!(nsio.try_gsn_add_token("3858f62230ac3c915f300c664312c63f", "foo").is_err());
+		assert!(nsio.try_gsn_remove_token("3858f62230ac3c915f300c664312c63f").is_err());
+		assert!(nsio.try_gsn_remove_token_by_springname("foo").is_err());
+	}
+	
+}
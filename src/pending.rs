@@ -0,0 +1,161 @@
+extern crate sqlite;
+
+use self::sqlite::{State,Value};
+
+use netspace::{NetspaceIo,Netspace,NetspaceFailure,StorageFailure,Success,Node,NodeState,step};
+
+/*
+ * Moderated registration
+ *
+ * With `registration=moderated` in node.conf a newly registered
+ * node is held in the netspace with an unspecified state and a row
+ * here until an operator approves it (it becomes disabled, like
+ * any fresh registration) or rejects it (it is unregistered).
+ * Pending nodes are left out of `info network`, service
+ * multicasts and replication, and cannot update themselves.
+ * When the table cannot be read a node is taken to be pending.
+ */
+
+#[derive(Debug,Clone,PartialEq)]
+pub struct PendingRegistration {
+	pub springname: String,
+	pub address: String,
+	pub requested: String,
+}
+
+impl NetspaceIo {
+
+	pub fn pending_add(&self, springname: &str, address: &str) -> Result<(),StorageFailure> {
+		let mut statement = try!(self.db().prepare("INSERT OR REPLACE INTO `geosub_pending`
+								(springname,address,requested) VALUES (?,?,strftime('%s','now'))"));
+		try!(statement.bind(1, &Value::String( springname.to_string() )));
+		try!(statement.bind(2, &Value::String( address.to_string() )));
+		try!(step(&mut statement));
+		Ok(())
+	}
+
+	pub fn pending_list(&self) -> Result<Vec<PendingRegistration>,StorageFailure> {
+		let mut statement = try!(self.db().prepare("SELECT springname,address,datetime(requested,'unixepoch')
+								FROM `geosub_pending` ORDER BY requested ASC"));
+
+		let mut v = Vec::new();
+		while let State::Row = try!(step(&mut statement)) {
+			v.push(PendingRegistration {
+				springname: try!(statement.read::<String>(0)),
+				address: try!(statement.read::<String>(1)),
+				requested: try!(statement.read::<String>(2)),
+			});
+		}
+
+		Ok(v)
+	}
+
+	pub fn pending_check(&self, springname: &str) -> Result<bool,StorageFailure> {
+		let mut statement = try!(self.db().prepare("SELECT 1 FROM `geosub_pending` WHERE springname = ?"));
+		try!(statement.bind(1, &Value::String( springname.to_string() )));
+		Ok(try!(step(&mut statement)) == State::Row)
+	}
+
+	pub fn pending_is(&self, springname: &str) -> bool {
+		match self.pending_check(springname) {
+			Ok(p) => p,
+			Err(e) => {
+				log_error!("netspace", "Unable to check {} for pending approval ({:?})", springname, e);
+				true
+			}
+		}
+	}
+
+	/// Let a pending node into the netspace as a normal, disabled registration
+	pub fn pending_approve(&self, springname: &str) -> Result<Success,NetspaceFailure> {
+		let mut node = try!(self.pending_node(springname));
+		node.update_state(NodeState::Disabled);
+
+		let t = try!(self.transaction().map_err(|e| e.to_netspace_failure()));
+		try!(self.gsn_node_update_state(&node));
+		try!(self.pending_remove(springname));
+		try!(self.audit_record("approve", springname, "pending", "").map_err(|e| e.to_netspace_failure()));
+		try!(t.commit().map_err(|e| e.to_netspace_failure()));
+		Ok(Success::Ok)
+	}
+
+	pub fn pending_reject(&self, springname: &str) -> Result<Success,NetspaceFailure> {
+		let node = try!(self.pending_node(springname));
+
+		let t = try!(self.transaction().map_err(|e| e.to_netspace_failure()));
+		try!(self.gsn_node_unregister(&node));
+		try!(self.pending_remove(springname));
+		try!(self.audit_record("reject", springname, "pending", "").map_err(|e| e.to_netspace_failure()));
+		try!(t.commit().map_err(|e| e.to_netspace_failure()));
+		Ok(Success::Ok)
+	}
+
+	fn pending_node(&self, springname: &str) -> Result<Node,NetspaceFailure> {
+		match self.pending_check(springname) {
+			Ok(true) => self.gsn_node_by_springname(springname),
+			Ok(false) => Err(NetspaceFailure::NodeNotFound),
+			Err(e) => Err(e.to_netspace_failure())
+		}
+	}
+
+	fn pending_remove(&self, springname: &str) -> Result<(),NetspaceFailure> {
+		let removed = self.db().prepare("DELETE FROM `geosub_pending` WHERE springname = ?")
+			.map_err(StorageFailure::from)
+			.and_then(|mut statement| {
+				try!(statement.bind(1, &Value::String( springname.to_string() )));
+				try!(step(&mut statement));
+				Ok(())
+			});
+
+		removed.map_err(|e| e.to_netspace_failure())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use netspace::*;
+
+	fn pending_node(nsio: &NetspaceIo) {
+		let mut node = Node::from_str("spring:foo,host:bar,address:192.168.1.2,role:org,service:http").unwrap();
+		nsio.gsn_node_register(&node).unwrap();
+		node.update_state(NodeState::Unspecified);
+		nsio.gsn_node_update_state(&node).unwrap();
+		nsio.pending_add("foo", "192.168.1.2").unwrap();
+	}
+
+	#[test]
+	fn ts_pending_approve_p() {
+		let nsio = NetspaceIo::new(":memory:");
+		pending_node(&nsio);
+		assert!(nsio.pending_is("foo"));
+
+		nsio.pending_approve("foo").unwrap();
+		assert!(!nsio.pending_is("foo"));
+		assert_eq!(nsio.gsn_node_by_springname("foo").unwrap().state(), NodeState::Disabled);
+	}
+
+	#[test]
+	fn ts_pending_reject_p() {
+		let nsio = NetspaceIo::new(":memory:");
+		pending_node(&nsio);
+
+		nsio.pending_reject("foo").unwrap();
+		assert!(!nsio.pending_is("foo"));
+		assert!(nsio.gsn_node_by_springname("foo").is_err());
+	}
+
+	#[test]
+	fn ts_pending_approve_f() {
+		let nsio = NetspaceIo::new(":memory:");
+		nsio.gsn_node_register(&Node::from_str("spring:foo,host:bar,address:192.168.1.2").unwrap()).unwrap();
+		assert!(nsio.pending_approve("foo").is_err());
+	}
+
+	#[test]
+	fn ts_pending_fail_closed_f() {
+		let nsio = NetspaceIo::new(":memory:");
+		nsio.db().execute("DROP TABLE `geosub_pending`").unwrap();
+		assert!(nsio.pending_is("foo"));
+	}
+}
@@ -1,4 +1,7 @@
 pub use std::net::{SocketAddr};
+use std::net::IpAddr;
+use std::str::FromStr;
+use std::time::Instant;
 
 extern crate spring_dvs;
 
@@ -11,26 +14,77 @@ pub use spring_dvs::uri::Uri;
 use chain::Chain;
 use resolution::{resolve_uri,ResolutionResult,ResolutionFailure};
 
-pub use netspace::{NetspaceIo};
+pub use netspace::{NetspaceIo,PrimaryNetspace,StorageFailure};
 pub use config::{NodeConfig,Config};
 use requests::multicast_request;
 use netservice;
+use throttle::ThrottleLimits;
+use metrics;
 
 
 
 
+/// What a response will be carried back over
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub enum Transport {
+	Udp,
+	Tcp,
+	Local,
+}
+
+impl Transport {
+	/// Largest response in bytes the transport will deliver whole
+	pub fn max_response(&self) -> usize {
+		match *self {
+			// Keep datagrams under a typical MTU so they are never
+			// fragmented; anything bigger is referred to the stream
+			Transport::Udp => 1400,
+			Transport::Tcp => 4096,
+			Transport::Local => usize::max_value(),
+		}
+	}
+}
+
+/// The property an update changes
+#[derive(Debug,Clone,Copy,PartialEq)]
+enum Property {
+	State,
+	Service,
+	Role,
+	Hostname,
+	Address,
+}
+
+impl Property {
+	fn name(&self) -> &'static str {
+		match *self {
+			Property::State => "state",
+			Property::Service => "service",
+			Property::Role => "role",
+			Property::Hostname => "hostname",
+			Property::Address => "address",
+		}
+	}
+}
+
 pub struct Svr<'s> {
 	pub sock: SocketAddr,
 	pub config: Box<NodeConfig>,
-	pub nio: &'s Netspace,
+	pub nio: &'s PrimaryNetspace,
+	pub transport: Transport,
 }
 
 impl<'s> Svr<'s> {
-	pub fn new(sock: SocketAddr, config: Box<NodeConfig>, nio: &'s Netspace) -> Svr<'s> {
+	pub fn new(sock: SocketAddr, config: Box<NodeConfig>, nio: &'s PrimaryNetspace) -> Svr<'s> {
 
-		Svr{ sock:sock, config:config, nio:nio }
+		Svr{ sock:sock, config:config, nio:nio, transport: Transport::Udp }
 		
 	}
+	
+	pub fn over(mut self, transport: Transport) -> Svr<'s> {
+		self.transport = transport;
+		self
+	}
 }
 
 #[allow(dead_code)]
@@ -56,6 +110,50 @@ pub fn response(code: Response) -> Message {
 	}
 }
 
+/// Service text of a network error response telling the client the
+/// reply was too large for a datagram and to ask again over the stream
+pub const TRUNCATED : &'static str = "truncated";
+
+/// Service text of a network error response refusing a source address
+/// that is over its rate limit or banned
+pub const THROTTLED : &'static str = "throttled";
+
+/// Service text of an unsupported action response refusing a command
+/// the access control rules do not allow the caller
+pub const DENIED : &'static str = "denied";
+
+fn response_notice(code: Response, text: &str) -> Message {
+	let mut m = generate_response_service_text(text);
+	
+	if let MessageContent::Response(ref mut r) = m.content {
+		r.code = code;
+	}
+	
+	m
+}
+
+pub fn response_truncated() -> Message {
+	response_notice(Response::NetworkError, TRUNCATED)
+}
+
+pub fn response_throttled() -> Message {
+	response_notice(Response::NetworkError, THROTTLED)
+}
+
+pub fn response_denied() -> Message {
+	response_notice(Response::UnsupportedAction, DENIED)
+}
+
+pub fn is_truncated(msg: &Message) -> bool {
+	match msg.content {
+		MessageContent::Response(ref r) => match r.content {
+			ResponseContent::ServiceText(ref t) => r.code == Response::NetworkError && t.content == TRUNCATED,
+			_ => false
+		},
+		_ => false
+	}
+}
+
 pub struct Protocol;
 
 
@@ -73,6 +171,61 @@ impl Protocol {
 
 	/// Run the action through the system
 	pub fn process(msg: &Message, svr: Svr, chain: Box<Chain>) -> ProtocolResult {
+		let start = Instant::now();
+		let command = Protocol::command_name(&msg.cmd).unwrap_or("other");
+		
+		let result = Protocol::dispatch(msg, svr, chain);
+		
+		metrics::count("dvsp_requests_total", &[("cmd", command)]);
+		metrics::observe("dvsp_request_seconds", &[("cmd", command)], metrics::since(start));
+		
+		match result {
+			ProtocolResult::Message(ref m) => Protocol::record_response(m),
+			ProtocolResult::Messages(ref v) => for m in v { Protocol::record_response(m) },
+			ProtocolResult::Bytes(_) => { }
+		}
+		
+		result
+	}
+	
+	/// Name a command goes by in access control rules and metrics
+	fn command_name(cmd: &CmdType) -> Option<&'static str> {
+		Some(match *cmd {
+			CmdType::Register => "register",
+			CmdType::Unregister => "unregister",
+			CmdType::Info => "info",
+			CmdType::Update => "update",
+			CmdType::Resolve => "resolve",
+			CmdType::Service => "service",
+			_ => return None
+		})
+	}
+	
+	/// Count a response by code, or by notice for the ones that share a code
+	fn record_response(msg: &Message) {
+		let r = match msg.content {
+			MessageContent::Response(ref r) => r,
+			_ => return
+		};
+		
+		let code = match r.content {
+			ResponseContent::ServiceText(ref t) if t.content == TRUNCATED || t.content == THROTTLED || t.content == DENIED =>
+				t.content.clone(),
+			_ => format!("{:?}", r.code)
+		};
+		
+		metrics::count("dvsp_responses_total", &[("code", code.as_str())]);
+	}
+	
+	fn dispatch(msg: &Message, svr: Svr, chain: Box<Chain>) -> ProtocolResult {
+		
+		if !Protocol::admitted(msg, &svr) {
+			return ProtocolResult::Message(response_throttled())
+		}
+		
+		if !Protocol::permitted(msg, &svr) {
+			return ProtocolResult::Message(response_denied())
+		}
 		
 		match msg.cmd {
 			CmdType::Register =>
@@ -99,17 +252,56 @@ impl Protocol {
 		let addr = ipaddr_str(svr.sock.ip());
 		let n : Node = Node::from_registration(reg, &addr);
 		
-		if svr.nio.gsn_check_token(&reg.token) == false {
-			return response(Response::NetspaceError)
-		}
-		match svr.nio.gsn_node_register(&n) {
-			Ok(_) => {
-				println!("[Netspace] Registered: {}", n.to_node_double().unwrap());
-				response(Response::Ok)
+		match svr.nio.try_gsn_check_token(&reg.token) {
+			Ok(true) => { },
+			Ok(false) => {
+				let limits = ThrottleLimits::from_config(svr.config.as_ref());
+				if limits.enabled { let _ = svr.nio.throttle_failure(&addr, &limits); }
+				return response(Response::NetspaceError)
 			},
-			Err(NetspaceFailure::DuplicateNode) =>  response(Response::NetspaceDuplication),
-			_ => response(Response::NetspaceError)
+			Err(e) => return Protocol::storage_response(e)
+		}
+		
+		let moderated = Protocol::moderated(svr);
+		
+		// A held node is registered, hidden and queued as one, so it is
+		// never seen live first nor left behind without its queue entry
+		let transaction = match svr.nio.transaction() {
+			Ok(t) => t,
+			Err(e) => return Protocol::storage_response(e)
+		};
+		
+		if let Err(e) = svr.nio.gsn_node_register(&n) {
+			return Protocol::netspace_response(e)
 		}
+		
+		if moderated {
+			// Held back until an operator approves it; the node can
+			// poll its state and will see unspecified until then
+			let mut pending = n.clone();
+			pending.update_state(NodeState::Unspecified);
+			
+			let held = match svr.nio.gsn_node_update_state(&pending) {
+				Ok(_) => svr.nio.pending_add(n.springname(), &addr).map_err(|e| e.to_netspace_failure()),
+				Err(e) => Err(e)
+			};
+			
+			// Dropping the transaction takes the registration back out
+			if let Err(e) = held {
+				return Protocol::netspace_response(e)
+			}
+		}
+		
+		if let Err(e) = transaction.commit() {
+			return Protocol::storage_response(e)
+		}
+		
+		log_info!("netspace", "Registered: {}", n.to_node_double().unwrap());
+		if moderated {
+			log_info!("netspace", "Pending approval: {}", n.springname());
+		}
+		
+		response(Response::Ok)
 	}
 	
 	fn unregister_action(msg: &Message, svr: &Svr) -> Message {
@@ -123,11 +315,10 @@ impl Protocol {
 
 		match svr.nio.gsn_node_unregister(&n) {
 			Ok(_) => {
-				println!("[Netspace] Unregistered: {}", n.springname());
+				log_info!("netspace", "Unregistered: {}", n.springname());
 				response(Response::Ok)
 			},
-			_ => response(Response::NetspaceDuplication)
-			
+			Err(e) => Protocol::netspace_response(e)
 		}
 	}
 	
@@ -154,8 +345,14 @@ impl Protocol {
 	
 	fn info_action_network(svr: &Svr) -> Message {
 		
+		let nodes = match svr.nio.try_gsn_nodes() {
+			Ok(v) => v,
+			Err(e) => return Protocol::storage_response(e)
+		};
+		
 		let mut v : Vec<NodeQuadFmt> = Vec::new();
-		for n in svr.nio.gsn_nodes() {
+		for n in nodes {
+			if svr.nio.pending_is(n.springname()) { continue }
 			v.push( match n.to_node_quad() {
 					Some(n) => n,
 					None => continue
@@ -163,6 +360,9 @@ impl Protocol {
 			)
 		}
 
+		// Always the whole listing: a datagram that is too large is
+		// referred to the stream as it is sent, and the stream carries
+		// any size. Callers wanting less ask the network service endpoint
 		response_content (
 			Response::Ok,
 			ResponseContent::Network( ContentNetwork{ network: v } )
@@ -172,26 +372,74 @@ impl Protocol {
 	fn update_action(msg: &Message, svr: &Svr) -> Message {
 		let np : &ContentNodeProperty = msg_update!(msg.content);
 		
-		let mut n : Node = match svr.nio.gsn_node_by_springname(&np.spring) {
+		let n : Node = match svr.nio.gsn_node_by_springname(&np.spring) {
 			Ok(n) => n,
 			_ => return response(Response::NetspaceError)
 		};
 		
 		valid_src!(n, svr);
 		
-		let state = match np.property {
-			NodeProperty::State(Some(s)) => s,
+		if svr.nio.pending_is(n.springname()) {
+			return response(Response::UnsupportedAction)
+		}
+		
+		let mut host = n.hostfield().to_string();
+		let mut address = n.address().to_string();
+		let mut service = n.service();
+		let mut state = n.state();
+		let mut role = n.role();
+		
+		let (property, value) = match np.property {
+			NodeProperty::State(Some(s)) => {
+				state = s;
+				(Property::State, format!("{}", s))
+			},
+			NodeProperty::Service(Some(s)) => {
+				service = s;
+				(Property::Service, format!("{}", s))
+			},
+			NodeProperty::Role(Some(r)) => {
+				if !Protocol::policy_allows("update_role", svr) {
+					return response(Response::UnsupportedAction)
+				}
+				role = r;
+				(Property::Role, format!("{}", r))
+			},
+			NodeProperty::Hostname(Some(ref h)) => {
+				match svr.nio.gsn_node_by_hostname(h) {
+					Ok(ref other) if other.springname() != n.springname() => return response(Response::NetspaceDuplication),
+					_ => { }
+				}
+				host = h.clone();
+				(Property::Hostname, h.clone())
+			},
+			NodeProperty::Address(Some(ref a)) => {
+				if IpAddr::from_str(a).is_err() {
+					return response(Response::MalformedContent)
+				}
+				address = a.clone();
+				(Property::Address, a.clone())
+			},
 			_ => return response(Response::UnsupportedAction),
 		};
 		
-		n.update_state(state);
-		match svr.nio.gsn_node_update_state(&n) {
+		let updated = Node::new(n.springname(), &host, &address, service, state, role, n.key());
+		
+		let result = match property {
+			Property::State => svr.nio.gsn_node_update_state(&updated),
+			Property::Service => svr.nio.gsn_node_update_service(&updated),
+			Property::Role => svr.nio.gsn_node_update_role(&updated),
+			Property::Hostname => svr.nio.gsn_node_update_hostname(&updated),
+			Property::Address => svr.nio.gsn_node_update_address(&updated),
+		};
+		
+		match result {
 			Ok(_) => {
-				println!("[Netspace] Update: {} state -> {}", n.springname(), state);
+				log_info!("netspace", "Update: {} {} -> {}", n.springname(), property.name(), value);
 				response(Response::Ok)
 			},
-			_ => response(Response::NetspaceError),
-		}		
+			Err(e) => Protocol::netspace_response(e),
+		}
 	}
 	
 	fn service_action(msg: &Message, svr: &Svr) -> ProtocolResult {
@@ -203,6 +451,15 @@ impl Protocol {
 				Some("cert") => {
 					return ProtocolResult::Message(netservice::cert::request(&curi.uri, svr))
 				},
+				Some("sync") => {
+					return ProtocolResult::Message(netservice::sync::request(&curi.uri, svr))
+				},
+				Some("geotop") => {
+					return ProtocolResult::Message(netservice::geotop::request(&curi.uri, svr))
+				},
+				Some("network") => {
+					return ProtocolResult::Message(netservice::network::request(&curi.uri, svr))
+				},
 				_ => return ProtocolResult::Message(response(Response::UnsupportedService)) 
 			}
 			
@@ -212,8 +469,11 @@ impl Protocol {
 			return ProtocolResult::Message(response(Response::NetworkError))
 		}
 		
-		let mut nodes = svr.nio.gsn_nodes_by_type(NodeRole::Org);
-		nodes.retain(|ref n| n.state() == NodeState::Enabled);
+		let mut nodes = match svr.nio.try_gsn_nodes_by_type(NodeRole::Org) {
+			Ok(v) => v,
+			Err(e) => return ProtocolResult::Message(Protocol::storage_response(e))
+		};
+		nodes.retain(|ref n| n.state() == NodeState::Enabled && !svr.nio.pending_is(n.springname()));
 		
 		let mut uri = curi.uri.clone();
 		ProtocolResult::Bytes(multicast_request(&nodes, &mut uri))
@@ -224,7 +484,7 @@ impl Protocol {
 		
 		let cr = msg_resolve!( msg.content );
 		let uri : Uri = cr.uri.clone();
-		match resolve_uri(&uri.to_string(), svr.nio, svr.config.as_ref(), chain) {
+		match resolve_uri(&uri.to_string(), svr.nio.netspace(), svr.config.as_ref(), chain) {
 			ResolutionResult::Network(net) => {
 				response_content (
 					Response::Ok,
@@ -258,6 +518,90 @@ impl Protocol {
 		
 	}
 	
+	/// A database that could not be read is never passed off as an empty one
+	fn storage_response(failure: StorageFailure) -> Message {
+		log_error!("netspace", "Storage failure answering request ({:?})", failure);
+		response(Response::NetspaceError)
+	}
+	
+	/// Map a failed netspace operation onto the response sent to the
+	/// requester; storage failures are never passed through as-is
+	fn netspace_response(failure: NetspaceFailure) -> Message {
+		match failure {
+			NetspaceFailure::DuplicateNode => response(Response::NetspaceDuplication),
+			_ => response(Response::NetspaceError)
+		}
+	}
+	
+	/// Rate limits and bans per source address; local requests and the
+	/// roots of our own geosub are never held back
+	fn admitted(msg: &Message, svr: &Svr) -> bool {
+		let limits = ThrottleLimits::from_config(svr.config.as_ref());
+		if !limits.enabled || svr.transport == Transport::Local { return true }
+		
+		let addr = ipaddr_str(svr.sock.ip());
+		
+		let geosub = svr.config.geosub();
+		match svr.nio.gtn_roots(Some(geosub.as_str())) {
+			Ok(roots) => if roots.iter().any(|r| r.address == addr) { return true },
+			Err(_) => { }
+		}
+		
+		if svr.nio.throttle_banned(&addr).unwrap_or(false) {
+			return false
+		}
+		
+		match limits.for_cmd(&msg.cmd) {
+			Some((counter, limit)) => svr.nio.throttle_hit(&addr, counter, limit).unwrap_or(true),
+			None => true
+		}
+	}
+	
+	/// Access control rules; local requests always pass, and a rule
+	/// table that cannot be read denies rather than opening up
+	fn permitted(msg: &Message, svr: &Svr) -> bool {
+		if svr.transport == Transport::Local { return true }
+		
+		let command = match Protocol::command_name(&msg.cmd) {
+			Some(c) => c,
+			None => return true
+		};
+		
+		let module = match msg.cmd {
+			CmdType::Service => {
+				let curi = msg_service!(msg.content);
+				match curi.uri.route().starts_with(&[svr.config.springname()]) {
+					true => curi.uri.res_index(0).map(|m| m.to_string()),
+					false => None
+				}
+			},
+			_ => None
+		};
+		
+		let default = match svr.config.setting("acl_default") {
+			Some(ref v) => v != "deny",
+			None => true
+		};
+		
+		svr.nio.acl_permits(command, module.as_ref().map(|m| m.as_str()), &svr.sock.ip(), default).unwrap_or(false)
+	}
+	
+	/// `registration=moderated` in node.conf holds new nodes for approval
+	fn moderated(svr: &Svr) -> bool {
+		match svr.config.setting("registration") {
+			Some(ref v) => v == "moderated",
+			None => false
+		}
+	}
+	
+	/// Policy switches are `policy_<action>=allow` in node.conf; anything else denies
+	fn policy_allows(action: &str, svr: &Svr) -> bool {
+		match svr.config.setting(&format!("policy_{}", action)) {
+			Some(ref v) => v == "allow",
+			None => false
+		}
+	}
+	
 	fn source_valid(n: &Node, svr: &Svr) -> Result<Success,Response> {
 		match svr.nio.gsn_node_by_springname(n.springname()) {
 			Ok(n) =>  match n.address() == ipaddr_str(svr.sock.ip()) {
@@ -310,6 +654,8 @@ mod tests {
 	use super::*;
 	use ::chain::mocks::MockChain;
 	use ::config::mocks::MockConfig;
+	use ::rustc_serialize::json::Json;
+	use ::acl::{AclRule,AclSubject};
 	
 	macro_rules! assert_match {
 		($e: expr, $p: pat) => (
@@ -362,38 +708,13 @@ mod tests {
 	fn new_netspace() -> NetspaceIo {
 		let ns = NetspaceIo::new(":memory:");
 		ns.db().execute("
-		CREATE TABLE `geosub_netspace` (
-			`id`			INTEGER PRIMARY KEY AUTOINCREMENT,
-			`springname`	TEXT UNIQUE,
-			`hostname`		TEXT,
-			`address`		TEXT,
-			`service`		INTEGER,
-			`status`		INTEGER,
-			`types`			INTEGER,
-			`key`			TEXT
-		);
-		
-		CREATE TABLE `geotop_netspace` (
-			`id`			INTEGER PRIMARY KEY AUTOINCREMENT,
-			`springname`	TEXT,
-			`hostname`		TEXT,
-			`address`		TEXT,
-			`service`		INTEGER,
-			`priority`		INTEGER,
-			`geosub`		TEXT,
-			`key`			TEXT
-		);
-		CREATE TABLE `geosub_tokens` (
-			`id`	INTEGER PRIMARY KEY AUTOINCREMENT,
-			`token`	TEXT
-		);
 		INSERT INTO `geosub_tokens` (token) VALUES ('3858f62230ac3c915f300c664312c63f');
 		").unwrap();
 		
 		ns
 	}
 	
-	fn new_svr(ns: &Netspace) -> Svr {
+	fn new_svr(ns: &NetspaceIo) -> Svr {
 		Svr::new(SocketAddr::new(IpAddr::V4(Ipv4Addr::from_str("192.168.1.2").unwrap()),55400), Box::new(MockConfig::dflt()) , ns)
 	}
 	
@@ -452,6 +773,58 @@ mod tests {
 		assert_eq!( n.service(), NodeService::Http);
 	}
 
+	#[test]
+	fn ts_protocol_register_moderated_pass() {
+		let ns = new_netspace();
+		let svr = Svr::new(SocketAddr::new(IpAddr::V4(Ipv4Addr::from_str("192.168.1.2").unwrap()),55400),
+						Box::new(MockConfig::dflt().with_setting("registration", "moderated")), &ns);
+		
+		process_assert_ok!("register spring,host;org;http;3858f62230ac3c915f300c664312c63f\nPUBLIC KEY", svr);
+		
+		let n : Node = get_node("spring", &ns);
+		assert_eq!(n.state(), NodeState::Unspecified);
+		assert!(ns.pending_is("spring"));
+	}
+	
+	#[test]
+	fn ts_protocol_register_moderated_fail() {
+		let ns = new_netspace();
+		ns.db().execute("DROP TABLE `geosub_pending`").unwrap();
+		let svr = Svr::new(SocketAddr::new(IpAddr::V4(Ipv4Addr::from_str("192.168.1.2").unwrap()),55400),
+						Box::new(MockConfig::dflt().with_setting("registration", "moderated")), &ns);
+		
+		// Nothing to hold it in, so it is not let in at all
+		Protocol::process(&new_msg("register spring,host;org;http;3858f62230ac3c915f300c664312c63f\nPUBLIC KEY"), svr, Box::new(MockChain::new("")));
+		assert!(ns.gsn_node_by_springname("spring").is_err());
+	}
+	
+	#[test]
+	fn ts_protocol_info_network_pending_pass() {
+		let ns = new_netspace();
+		let svr = new_svr(&ns);
+		
+		add_node(&ns);
+		add_node_with_name("bar", &ns);
+		ns.pending_add("bar", "192.168.1.2").unwrap();
+		
+		let m = process_assert_ok!("info network", svr);
+		let cn = msg_response_network!(m.content);
+		
+		assert_eq!(cn.network.len(), 1);
+		assert_eq!(cn.network[0].spring , "foo");
+	}
+	
+	#[test]
+	fn ts_protocol_update_state_fail_pending() {
+		let ns = new_netspace();
+		let svr = new_svr(&ns);
+		
+		add_node(&ns);
+		ns.pending_add("foo", "192.168.1.2").unwrap();
+		
+		process_assert_response!("update foo state enabled", svr, Response::UnsupportedAction);
+	}
+	
 	#[test]
 	fn ts_protocol_register_fail_duplicate() {
 		let ns = new_netspace();
@@ -645,6 +1018,18 @@ mod tests {
 		process_assert_response!("info node void hostname", svr, Response::NetspaceError);		
 	}
 	
+	#[test]
+	fn ts_protocol_storage_failure_fail() {
+		let ns = new_netspace();
+		add_node(&ns);
+		ns.db().execute("DROP TABLE `geosub_netspace`; DROP TABLE `geosub_tokens`;").unwrap();
+		
+		let svr = new_svr(&ns);
+		process_assert_response!("info network", svr, Response::NetspaceError);
+		let svr = new_svr(&ns);
+		process_assert_response!("register spring,host;org;http;3858f62230ac3c915f300c664312c63f\nPUBLIC KEY", svr, Response::NetspaceError);
+	}
+	
 	#[test]
 	fn ts_protocol_info_network_pass() {
 		let ns = new_netspace();
@@ -663,6 +1048,177 @@ mod tests {
 		assert_eq!(cn.network[1].spring , "croc");
 	}
 	
+	#[test]
+	fn ts_protocol_info_network_whole_pass() {
+		let ns = new_netspace();
+		let svr = new_svr(&ns);
+
+		for i in 0..200 {
+			add_node_with_name(&format!("node{}", i), &ns);
+		}
+		
+		let m = process_assert_ok!("info network", svr);
+		assert_eq!(msg_response_network!(m.content).network.len(), 200);
+	}
+	
+	#[test]
+	fn ts_protocol_info_network_stream_whole_pass() {
+		let ns = new_netspace();
+		let svr = new_svr(&ns).over(Transport::Tcp);
+
+		for i in 0..200 {
+			add_node_with_name(&format!("node{}", i), &ns);
+		}
+		
+		let m = process_assert_ok!("info network", svr);
+		assert_eq!(msg_response_network!(m.content).network.len(), 200);
+	}
+	
+	fn service_text(m: &Message) -> String {
+		match msg_response!(m.content).content {
+			ResponseContent::ServiceText(ref t) => t.content.clone(),
+			_ => panic!("Expected service text")
+		}
+	}
+	
+	fn service_json(m: &Message) -> Json {
+		Json::from_str(&service_text(m)).unwrap()
+	}
+	
+	#[test]
+	fn ts_protocol_service_network_filter_pass() {
+		let ns = new_netspace();
+		let svr = new_svr(&ns);
+
+		add_node(&ns);
+		add_node_with_name("croc", &ns);
+		
+		let m = process_assert_ok!("service spring://foohub.esusx.uk/network?role=org", svr);
+		let j = service_json(&m);
+		
+		let nodes = j.find("nodes").unwrap().as_array().unwrap();
+		assert_eq!(nodes.len(), 1);
+		assert_eq!(nodes[0].find("spring").unwrap().as_string(), Some("croc"));
+		assert!(j.find("next").unwrap().is_null());
+	}
+	
+	#[test]
+	fn ts_protocol_service_network_page_pass() {
+		let ns = new_netspace();
+		
+		for name in &["alpha", "bravo", "charlie"] {
+			add_node_with_name(name, &ns);
+		}
+		
+		let svr = new_svr(&ns);
+		let m = process_assert_ok!("service spring://foohub.esusx.uk/network?limit=2", svr);
+		let j = service_json(&m);
+		assert_eq!(j.find("nodes").unwrap().as_array().unwrap().len(), 2);
+		assert_eq!(j.find("next").unwrap().as_string(), Some("bravo"));
+		
+		let svr = new_svr(&ns);
+		let m = process_assert_ok!("service spring://foohub.esusx.uk/network?limit=2&after=bravo", svr);
+		let j = service_json(&m);
+		let nodes = j.find("nodes").unwrap().as_array().unwrap();
+		assert_eq!(nodes.len(), 1);
+		assert_eq!(nodes[0].find("spring").unwrap().as_string(), Some("charlie"));
+		assert!(j.find("next").unwrap().is_null());
+	}
+	
+	#[test]
+	fn ts_protocol_service_network_fail() {
+		let ns = new_netspace();
+		let svr = new_svr(&ns);
+		
+		process_assert_response!("service spring://foohub.esusx.uk/network?role=void", svr, Response::MalformedContent);
+	}
+	
+	fn throttled_svr<'a>(ns: &'a NetspaceIo, key: &str, value: &str) -> Svr<'a> {
+		Svr::new(SocketAddr::new(IpAddr::V4(Ipv4Addr::from_str("192.168.1.2").unwrap()),55400),
+				Box::new(MockConfig::dflt().with_setting(key, value)), ns)
+	}
+	
+	#[test]
+	fn ts_protocol_throttle_rate_fail() {
+		let ns = new_netspace();
+		
+		for _ in 0..2 {
+			let svr = throttled_svr(&ns, "throttle_info", "2");
+			process_assert_ok!("info network", svr);
+		}
+		
+		let svr = throttled_svr(&ns, "throttle_info", "2");
+		let m = process_assert_response!("info network", svr, Response::NetworkError);
+		assert_eq!(service_text(&m), THROTTLED);
+	}
+	
+	#[test]
+	fn ts_protocol_throttle_token_ban_fail() {
+		let ns = new_netspace();
+		
+		for _ in 0..2 {
+			let svr = throttled_svr(&ns, "throttle_token_failures", "2");
+			process_assert_response!("register spring,host;org;http;00000000000000000000000000000000\nPUBLIC KEY", svr, Response::NetspaceError);
+		}
+		
+		assert!(ns.throttle_banned("192.168.1.2").unwrap());
+		
+		let svr = throttled_svr(&ns, "throttle_token_failures", "2");
+		process_assert_response!("register spring,host;org;http;3858f62230ac3c915f300c664312c63f\nPUBLIC KEY", svr, Response::NetworkError);
+	}
+	
+	#[test]
+	fn ts_protocol_throttle_local_pass() {
+		let ns = new_netspace();
+		
+		for _ in 0..3 {
+			let svr = throttled_svr(&ns, "throttle_info", "1").over(Transport::Local);
+			process_assert_ok!("info network", svr);
+		}
+	}
+	
+	#[test]
+	fn ts_protocol_acl_cidr_fail() {
+		let ns = new_netspace();
+		let svr = new_svr(&ns);
+		
+		ns.acl_add(&AclRule::new(false, "info", None, AclSubject::cidr("192.168.0.0/16").unwrap())).unwrap();
+		
+		let m = process_assert_response!("info network", svr, Response::UnsupportedAction);
+		assert_eq!(service_text(&m), DENIED);
+	}
+	
+	#[test]
+	fn ts_protocol_acl_module_role_pass() {
+		let ns = new_netspace();
+		add_node(&ns);
+		
+		ns.acl_add(&AclRule::new(true, "service", Some("network"), AclSubject::Role(NodeRole::Org))).unwrap();
+		ns.acl_add(&AclRule::new(false, "service", Some("network"), AclSubject::Any)).unwrap();
+		
+		let svr = new_svr(&ns);
+		process_assert_response!("service spring://foohub.esusx.uk/network", svr, Response::UnsupportedAction);
+		
+		change_node_role("foo", NodeRole::Org, &ns);
+		let svr = new_svr(&ns);
+		process_assert_ok!("service spring://foohub.esusx.uk/network", svr);
+	}
+	
+	#[test]
+	fn ts_protocol_truncated_pass() {
+		let m = response_truncated();
+		assert_eq!(msg_response!(m.content).code, Response::NetworkError);
+		
+		let m = try_panic!(Message::from_bytes(m.to_bytes().as_slice()));
+		assert!(is_truncated(&m));
+	}
+	
+	#[test]
+	fn ts_protocol_truncated_fail() {
+		assert!(!is_truncated(&response(Response::NetworkError)));
+		assert!(!is_truncated(&generate_response_service_text(TRUNCATED)));
+	}
+	
 	#[test]
 	fn ts_protocol_update_state_unspecified_pass() {
 		let ns = new_netspace();
@@ -758,6 +1314,84 @@ mod tests {
 			
 	}
 	
+	#[test]
+	fn ts_protocol_update_hostname_pass() {
+		let ns = new_netspace();
+		let svr = new_svr(&ns);
+
+		add_node(&ns);
+		
+		process_assert_ok!("update foo hostname barfoo", svr);
+
+		let n = get_node("foo", &ns);
+		assert_eq!(n.hostname(), "barfoo");
+	}
+	
+	#[test]
+	fn ts_protocol_update_hostname_fail_duplicate() {
+		let ns = new_netspace();
+		let svr = new_svr(&ns);
+
+		add_node(&ns);
+		try_panic!(ns.gsn_node_register(&Node::from_str("spring:bar,host:barfoo,address:192.168.1.3").unwrap()));
+		
+		process_assert_response!("update foo hostname barfoo", svr, Response::NetspaceDuplication);
+	}
+	
+	#[test]
+	fn ts_protocol_update_address_pass() {
+		let ns = new_netspace();
+		let svr = new_svr(&ns);
+
+		add_node(&ns);
+		
+		process_assert_ok!("update foo address 192.168.1.20", svr);
+
+		let n = get_node("foo", &ns);
+		assert_eq!(n.address(), "192.168.1.20");
+	}
+	
+	#[test]
+	fn ts_protocol_update_service_pass() {
+		let ns = new_netspace();
+		let svr = new_svr(&ns);
+
+		add_node(&ns);
+		
+		process_assert_ok!("update foo service dvsp", svr);
+
+		let n = get_node("foo", &ns);
+		assert_eq!(n.service(), NodeService::Dvsp);
+	}
+	
+	#[test]
+	fn ts_protocol_update_role_policy_pass() {
+		let ns = new_netspace();
+		let svr = Svr::new(SocketAddr::new(IpAddr::V4(Ipv4Addr::from_str("192.168.1.2").unwrap()),55400),
+						Box::new(MockConfig::dflt().with_setting("policy_update_role", "allow")), &ns);
+
+		add_node(&ns);
+		
+		process_assert_ok!("update foo role org", svr);
+
+		let n = get_node("foo", &ns);
+		assert_eq!(n.role(), NodeRole::Org);
+	}
+	
+	#[test]
+	fn ts_protocol_update_role_policy_fail() {
+		let ns = new_netspace();
+		let svr = Svr::new(SocketAddr::new(IpAddr::V4(Ipv4Addr::from_str("192.168.1.2").unwrap()),55400),
+						Box::new(MockConfig::dflt().with_setting("policy_update_role", "deny")), &ns);
+
+		add_node(&ns);
+		
+		process_assert_response!("update foo role org", svr, Response::UnsupportedAction);
+
+		let n = get_node("foo", &ns);
+		assert_eq!(n.role(), NodeRole::Hub);
+	}
+	
 	#[test]
 	fn ts_protocol_resolve_pass_local_node() {
 		let ns = new_netspace();
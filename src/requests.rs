@@ -1,5 +1,6 @@
 use std::thread;
 use std::sync::mpsc::channel;
+use std::time::Instant;
 
 use spring_dvs::protocol::{Bytes,CmdType,ProtocolObject,Message,MessageContent,ResponseContent};
 use spring_dvs::node::Node;
@@ -8,12 +9,14 @@ use spring_dvs::uri::Uri;
 use spring_dvs::http;
 
 use service::Tcp;
+use lifecycle::Work;
+use metrics;
 
 pub fn multicast_request(nodes: &Vec<Node>, uri: &mut Uri) -> Bytes {
 
 	let mut v : Vec<Message> = Vec::new();
 	let dbg_uri = uri.to_string();
-	println!("[Service] Processing {}", dbg_uri);
+	log_info!("service", "Processing {}", dbg_uri);
 	let (tx,rx) = channel();
 	
 	
@@ -32,8 +35,12 @@ pub fn multicast_request(nodes: &Vec<Node>, uri: &mut Uri) -> Bytes {
 			Err(_) => continue
 		};
 
+		// Part of the request that spawned it, so shutdown waits on it too
+		let work = Work::hold();
+		
 		thread::spawn(move|| {
-				
+			let _work = work;
+			let start = Instant::now();
 				
 			let inbound = match node.service() {
 				NodeService::Dvsp =>
@@ -46,6 +53,11 @@ pub fn multicast_request(nodes: &Vec<Node>, uri: &mut Uri) -> Bytes {
 					},
 				_ => Err(Failure::InvalidArgument),
 			};
+			
+			let outcome = match inbound { Ok(_) => "ok", Err(_) => "failed" };
+			metrics::count("dvsp_multicast_requests_total", &[("node", node.springname()), ("result", outcome)]);
+			metrics::observe("dvsp_multicast_seconds", &[("node", node.springname())], metrics::since(start));
+			
 			tx.send((i,inbound)).unwrap();		
 		});
 	}
@@ -80,6 +80,8 @@ pub fn resolve(uri: &str, nio: &Netspace, config: &NodeConfig) -> ResolutionResu
 
 pub fn resolve_uri(suri: &str, nio: &Netspace, config: &NodeConfig, chain: Box<Chain>) -> ResolutionResult {
 	
+	log_debug!("resolution", "Resolving {}", suri);
+	
 	let mut uri : Uri = match Uri::new(suri) {
 		Err(_) => return ResolutionResult::Err(ResolutionFailure::InvalidUri),
 		Ok(u) => u
@@ -177,10 +179,11 @@ pub fn resolve_uri(suri: &str, nio: &Netspace, config: &NodeConfig, chain: Box<C
 		for node in nodes {
 			match chain.as_ref().request(&out_bytes, &node) {
 				Ok(b) => return ResolutionResult::Chain(b),
-				_ => continue
+				_ => log_warn!("resolution", "Chain through {} failed", node.springname())
 			}
 		}
 		
+		log_warn!("resolution", "No root answered for {}", suri);
 		ResolutionResult::Err(ResolutionFailure::UnresponsiveChain)
 
 	} else {
@@ -256,33 +259,6 @@ mod tests {
 	fn new_netspace(cfg: &MockConfig) -> NetspaceIo {
 
 		let ns = NetspaceIo::new(":memory:");
-		ns.db().execute("
-		CREATE TABLE `geosub_netspace` (
-			`id`			INTEGER PRIMARY KEY AUTOINCREMENT,
-			`springname`	TEXT UNIQUE,
-			`hostname`		TEXT,
-			`address`		TEXT,
-			`service`		INTEGER,
-			`status`		INTEGER,
-			`types`			INTEGER,
-			`key`			TEXT
-		);
-		
-		CREATE TABLE `geotop_netspace` (
-			`id`			INTEGER PRIMARY KEY AUTOINCREMENT,
-			`springname`	TEXT,
-			`hostname`		TEXT,
-			`address`		TEXT,
-			`service`		INTEGER,
-			`priority`		INTEGER,
-			`geosub`		TEXT,
-			`key`			TEXT
-		);
-		CREATE TABLE `geosub_tokens` (
-			`id`	INTEGER PRIMARY KEY AUTOINCREMENT,
-			`token`	TEXT
-		);").unwrap();
-		
 		add_self(&ns, &cfg);
 		ns
 	}
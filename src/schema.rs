@@ -0,0 +1,323 @@
+extern crate sqlite;
+
+use self::sqlite::{Connection,State,Value};
+
+/*
+ * Versioned schema for the primary's databases
+ *
+ * Every database opened through `NetspaceIo::new` or
+ * `ServiceDatabase::new` is brought up to the latest version
+ * here, so this is the only place a table definition should
+ * live -- tests and the live testing environment use it too.
+ *
+ * Migrations are applied in order and recorded in `schema_version`.
+ * Never edit a released migration; append a new one instead.
+ */
+
+pub struct Migration {
+	pub version: i64,
+	pub description: &'static str,
+	pub apply: fn(&Connection) -> Result<(),sqlite::Error>,
+}
+
+pub static NETSPACE: &'static [Migration] = &[
+	Migration { version: 1, description: "base netspace tables", apply: netspace_base },
+	Migration { version: 2, description: "node keys and token springnames", apply: netspace_keys },
+	Migration { version: 3, description: "netspace audit log", apply: netspace_audit },
+	Migration { version: 4, description: "replication ledger", apply: netspace_ledger },
+	Migration { version: 5, description: "pending registrations", apply: netspace_pending },
+	Migration { version: 6, description: "rate limiting", apply: netspace_throttle },
+	Migration { version: 7, description: "access control rules", apply: netspace_acl },
+	Migration { version: 8, description: "rate limiting counters kept in memory", apply: netspace_throttle_memory },
+];
+
+pub static SERVICES: &'static [Migration] = &[
+	Migration { version: 1, description: "certificate keyring", apply: services_keyring },
+	Migration { version: 2, description: "replication peers", apply: services_sync_peers },
+];
+
+/// Bring a database up to the latest version in `migrations`,
+/// returning the version it is now at
+pub fn migrate(db: &Connection, migrations: &[Migration]) -> Result<i64,sqlite::Error> {
+	try!(db.execute("CREATE TABLE IF NOT EXISTS `schema_version` (
+			`version`		INTEGER PRIMARY KEY,
+			`description`	TEXT,
+			`applied`		INTEGER
+		);"));
+
+	let mut current = try!(version(db));
+
+	for m in migrations {
+		if m.version <= current { continue }
+
+		try!(db.execute("BEGIN TRANSACTION"));
+
+		match (m.apply)(db).and_then(|_| record(db, m)) {
+			Ok(_) => try!(db.execute("COMMIT")),
+			Err(e) => {
+				let _ = db.execute("ROLLBACK");
+				return Err(e)
+			}
+		}
+
+		log_info!("database", "Applied migration {} ({})", m.version, m.description);
+		current = m.version;
+	}
+
+	Ok(current)
+}
+
+pub fn version(db: &Connection) -> Result<i64,sqlite::Error> {
+	let mut statement = try!(db.prepare("SELECT MAX(version) FROM `schema_version`"));
+
+	match try!(statement.next()) {
+		State::Row => Ok(try!(statement.read::<i64>(0))),
+		State::Done => Ok(0)
+	}
+}
+
+fn record(db: &Connection, m: &Migration) -> Result<(),sqlite::Error> {
+	let mut statement = try!(db.prepare("INSERT INTO `schema_version`
+						(version,description,applied)
+						VALUES (?,?,strftime('%s','now'))"));
+	try!(statement.bind(1, &Value::Integer(m.version)));
+	try!(statement.bind(2, &Value::String(m.description.to_string())));
+	try!(statement.next());
+	Ok(())
+}
+
+fn has_column(db: &Connection, table: &str, column: &str) -> Result<bool,sqlite::Error> {
+	let mut statement = try!(db.prepare(&format!("PRAGMA table_info(`{}`)", table)));
+
+	while let State::Row = try!(statement.next()) {
+		if try!(statement.read::<String>(1)) == column { return Ok(true) }
+	}
+
+	Ok(false)
+}
+
+fn add_column(db: &Connection, table: &str, column: &str, kind: &str) -> Result<(),sqlite::Error> {
+	if try!(has_column(db, table, column)) { return Ok(()) }
+	db.execute(&format!("ALTER TABLE `{}` ADD COLUMN `{}` {}", table, column, kind))
+}
+
+// Matches the original gsn.sql layout so existing databases are adopted as-is
+fn netspace_base(db: &Connection) -> Result<(),sqlite::Error> {
+	db.execute("
+		CREATE TABLE IF NOT EXISTS `geosub_netspace` (
+			`id`			INTEGER PRIMARY KEY AUTOINCREMENT,
+			`springname`	TEXT UNIQUE,
+			`hostname`		TEXT,
+			`address`		TEXT,
+			`service`		INTEGER,
+			`status`		INTEGER,
+			`types`			INTEGER
+		);
+		CREATE TABLE IF NOT EXISTS `geosub_metaspace` (
+			`id`			INTEGER PRIMARY KEY AUTOINCREMENT,
+			`settlement`	TEXT,
+			`postcode`		TEXT,
+			`county`		TEXT,
+			`geosub`		TEXT
+		);
+		CREATE TABLE IF NOT EXISTS `geotop_netspace` (
+			`id`			INTEGER PRIMARY KEY AUTOINCREMENT,
+			`springname`	TEXT,
+			`hostname`		TEXT,
+			`address`		TEXT,
+			`service`		INTEGER,
+			`priority`		INTEGER,
+			`geosub`		TEXT
+		);
+		CREATE TABLE IF NOT EXISTS `geosub_tokens` (
+			`id`			INTEGER PRIMARY KEY AUTOINCREMENT,
+			`token`			TEXT
+		);
+	")
+}
+
+// Rows from before the columns existed are read as strings, so never leave them NULL
+fn netspace_keys(db: &Connection) -> Result<(),sqlite::Error> {
+	try!(add_column(db, "geosub_netspace", "key", "TEXT DEFAULT ''"));
+	try!(add_column(db, "geotop_netspace", "key", "TEXT DEFAULT ''"));
+	try!(add_column(db, "geosub_tokens", "spring", "TEXT DEFAULT ''"));
+	db.execute("
+		UPDATE `geosub_netspace` SET `key` = '' WHERE `key` IS NULL;
+		UPDATE `geotop_netspace` SET `key` = '' WHERE `key` IS NULL;
+		UPDATE `geosub_tokens` SET `spring` = '' WHERE `spring` IS NULL;
+	")
+}
+
+fn netspace_audit(db: &Connection) -> Result<(),sqlite::Error> {
+	db.execute("
+		CREATE TABLE IF NOT EXISTS `netspace_audit` (
+			`id`			INTEGER PRIMARY KEY AUTOINCREMENT,
+			`timestamp`		INTEGER,
+			`actor`			TEXT,
+			`action`		TEXT,
+			`springname`	TEXT,
+			`before`		TEXT,
+			`after`			TEXT
+		);
+		CREATE INDEX IF NOT EXISTS `netspace_audit_springname` ON `netspace_audit` (`springname`);
+		CREATE INDEX IF NOT EXISTS `netspace_audit_timestamp` ON `netspace_audit` (`timestamp`);
+	")
+}
+
+// One row per springname, tombstones included, so deletions replicate too
+fn netspace_ledger(db: &Connection) -> Result<(),sqlite::Error> {
+	db.execute("
+		CREATE TABLE IF NOT EXISTS `netspace_ledger` (
+			`springname`	TEXT PRIMARY KEY,
+			`stamp`			INTEGER,
+			`origin`		TEXT,
+			`deleted`		INTEGER
+		);
+	")
+}
+
+fn netspace_pending(db: &Connection) -> Result<(),sqlite::Error> {
+	db.execute("
+		CREATE TABLE IF NOT EXISTS `geosub_pending` (
+			`springname`	TEXT PRIMARY KEY,
+			`address`		TEXT,
+			`requested`		INTEGER
+		);
+	")
+}
+
+fn netspace_throttle(db: &Connection) -> Result<(),sqlite::Error> {
+	db.execute("
+		CREATE TABLE IF NOT EXISTS `throttle_hits` (
+			`address`		TEXT,
+			`counter`		TEXT,
+			`window`		INTEGER,
+			`count`			INTEGER,
+			PRIMARY KEY(`address`,`counter`)
+		);
+		CREATE TABLE IF NOT EXISTS `throttle_bans` (
+			`address`		TEXT PRIMARY KEY,
+			`until`			INTEGER,
+			`reason`		TEXT
+		);
+	")
+}
+
+fn netspace_throttle_memory(db: &Connection) -> Result<(),sqlite::Error> {
+	db.execute("DROP TABLE IF EXISTS `throttle_hits`;")
+}
+
+fn netspace_acl(db: &Connection) -> Result<(),sqlite::Error> {
+	db.execute("
+		CREATE TABLE IF NOT EXISTS `acl_rules` (
+			`id`			INTEGER PRIMARY KEY AUTOINCREMENT,
+			`allow`			INTEGER,
+			`command`		TEXT,
+			`module`		TEXT,
+			`subject`		TEXT
+		);
+	")
+}
+
+fn services_keyring(db: &Connection) -> Result<(),sqlite::Error> {
+	db.execute("
+		CREATE TABLE IF NOT EXISTS `certificates`(
+			`keyid`			TEXT,
+			`name`			TEXT,
+			`email`			TEXT,
+			`sigs`			TEXT,
+			`key`			TEXT,
+			PRIMARY KEY(`keyid`)
+		);
+	")
+}
+
+fn services_sync_peers(db: &Connection) -> Result<(),sqlite::Error> {
+	db.execute("
+		CREATE TABLE IF NOT EXISTS `sync_peers`(
+			`springname`	TEXT,
+			`address`		TEXT,
+			`pushed`		INTEGER DEFAULT 0,
+			`synced`		INTEGER DEFAULT 0,
+			`pending`		INTEGER DEFAULT 0,
+			`status`		TEXT DEFAULT '',
+			PRIMARY KEY(`springname`)
+		);
+	")
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use super::sqlite;
+
+	#[test]
+	fn ts_schema_migrate_fresh_p() {
+		let db = sqlite::open(":memory:").unwrap();
+		let v = migrate(&db, NETSPACE).unwrap();
+
+		assert_eq!(v, NETSPACE.last().unwrap().version);
+		assert!(has_column(&db, "geosub_netspace", "key").unwrap());
+		assert!(has_column(&db, "geosub_tokens", "spring").unwrap());
+	}
+
+	#[test]
+	fn ts_schema_migrate_idempotent_p() {
+		let db = sqlite::open(":memory:").unwrap();
+		let first = migrate(&db, NETSPACE).unwrap();
+		let second = migrate(&db, NETSPACE).unwrap();
+		assert_eq!(first, second);
+	}
+
+	#[test]
+	fn ts_schema_migrate_adopt_legacy_p() {
+		let db = sqlite::open(":memory:").unwrap();
+		db.execute("
+			CREATE TABLE `geosub_netspace` (
+				`id`	INTEGER PRIMARY KEY AUTOINCREMENT,
+				`springname`	TEXT UNIQUE,
+				`hostname`	TEXT,
+				`address`	TEXT,
+				`service`	INTEGER,
+				`status`	INTEGER,
+				`types`	INTEGER,
+				`key`	TEXT
+			);").unwrap();
+
+		assert!(migrate(&db, NETSPACE).is_ok());
+		assert!(has_column(&db, "geotop_netspace", "key").unwrap());
+	}
+
+	#[test]
+	fn ts_schema_migrate_backfill_p() {
+		let db = sqlite::open(":memory:").unwrap();
+		db.execute("
+			CREATE TABLE `geosub_tokens` (
+				`id`	INTEGER PRIMARY KEY AUTOINCREMENT,
+				`token`	TEXT
+			);
+			INSERT INTO `geosub_tokens` (token) VALUES ('abc');
+			CREATE TABLE `geosub_netspace` (
+				`id`	INTEGER PRIMARY KEY AUTOINCREMENT,
+				`springname`	TEXT UNIQUE,
+				`hostname`	TEXT,
+				`address`	TEXT,
+				`service`	INTEGER,
+				`status`	INTEGER,
+				`types`	INTEGER,
+				`key`	TEXT
+			);
+			INSERT INTO `geosub_netspace` (springname,hostname,address,service,status,types) VALUES ('foo','bar','192.168.1.2',1,1,1);
+			").unwrap();
+
+		migrate(&db, NETSPACE).unwrap();
+
+		let mut statement = db.prepare("SELECT spring FROM `geosub_tokens`").unwrap();
+		assert_eq!(statement.next().unwrap(), State::Row);
+		assert_eq!(statement.read::<String>(0).unwrap(), "");
+
+		let mut statement = db.prepare("SELECT key FROM `geosub_netspace`").unwrap();
+		assert_eq!(statement.next().unwrap(), State::Row);
+		assert_eq!(statement.read::<String>(0).unwrap(), "");
+	}
+}
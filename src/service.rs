@@ -7,28 +7,74 @@ use std::str::FromStr;
 
 use std::fs::remove_file;
 use std::io::prelude::*;
+use std::io::ErrorKind;
 use std::os::unix::io::{AsRawFd, RawFd};
 use std::net::{UdpSocket,SocketAddr};
 use std::net::{TcpListener,TcpStream};
 
+use std::sync::{Arc,Mutex};
+use std::sync::atomic::AtomicUsize;
 use std::thread;
+use std::time::Duration;
 
 use spring_dvs::enums::{Response};
 use spring_dvs::protocol::{Bytes,ProtocolObject,Message};
 use spring_dvs::protocol::{Port};
 
 use spring_dvs::http::HttpWrapper;
-use self::unix_socket::UnixListener;
+use self::unix_socket::{UnixListener,UnixStream};
 
 
 use netspace::*;
 use management::management_handler;
+use management::remote::{self,remote_handler,Session};
 use protocol::ProtocolResult;
 
 use self::epoll::*;
 use self::epoll::util::*;
 
 use unit_test_env::*;
+use lifecycle::{self,Work};
+use config::SharedConfig;
+use metrics;
+use status::{self,Service};
+
+pub const MANAGEMENT_SOCKET : &'static str = "/var/run/springdvs/primary.sock";
+
+/// Where the Prometheus exporter listens unless `metrics_address` says otherwise
+pub const METRICS_ADDRESS : &'static str = "127.0.0.1:55400";
+
+/// Seconds a scrape has to send its request before it is dropped
+const METRICS_READ_SECS : u64 = 5;
+
+/// Milliseconds the UDP service waits on epoll before checking whether to stop
+const EPOLL_TIMEOUT_MS : i32 = 500;
+
+/// Seconds a request made over the stream waits on a quiet peer
+const STREAM_READ_SECS : u64 = 10;
+
+/// Largest reply a request made over the stream will take in
+const MAX_REPLY_BYTES : usize = 1 << 20;
+
+// Where the listeners placed by node.conf settings are bound, if they
+// were started, so shutdown can reach them
+process_global!(metrics_bound, Mutex<Option<SocketAddr>>, Mutex::new(None));
+process_global!(remote_bound, Mutex<Option<SocketAddr>>, Mutex::new(None));
+
+/// Note where a listener is bound
+fn bound(at: &Mutex<Option<SocketAddr>>, listener: &TcpListener) {
+	if let Ok(mut a) = at.lock() { *a = listener.local_addr().ok() }
+}
+
+/// Connect to a listener, if it was bound, so it notices the primary is stopping
+fn wake_listener(at: &Mutex<Option<SocketAddr>>) {
+	let address = match at.lock() {
+		Ok(a) => *a,
+		Err(_) => return
+	};
+
+	if let Some(a) = address { let _ = TcpStream::connect(a); }
+}
 
 macro_rules! pr_bytes {
 	($content:expr) => (
@@ -59,7 +105,7 @@ macro_rules! pr_bytes {
 
 
 
-use protocol::{Protocol,Svr,response};
+use protocol::{Protocol,Svr,Transport,response,response_truncated};
 use chain::ChainService;
 
 
@@ -83,7 +129,11 @@ fn content_len(bytes: &[u8]) -> Option<(usize,usize)> {
 		Err(_) => return None
 	};
 
-	let index = s.find(" ").unwrap();
+	// A reply still arriving may not have got past the length yet
+	let index = match s.find(" ") {
+		Some(i) => i,
+		None => return None
+	};
 	let (sl,_) = s.split_at(index);
 	
 	Some(match sl.parse() {
@@ -92,6 +142,38 @@ fn content_len(bytes: &[u8]) -> Option<(usize,usize)> {
 	})
 }
 
+/// Read a stream reply until the peer closes it or `expected`, given
+/// what has arrived so far, says how long it is and that much is in
+fn read_reply<R: Read, F: Fn(&[u8]) -> Option<usize>>(stream: &mut R, expected: F) -> Result<Vec<u8>,Failure> {
+	let mut reply = Vec::new();
+	let mut buf = [0;4096];
+
+	loop {
+		match expected(reply.as_slice()) {
+			Some(len) if reply.len() >= len => break,
+			_ => { }
+		}
+
+		let size = match stream.read(&mut buf) {
+			Ok(s) => s,
+			Err(ref e) if e.kind() == ErrorKind::Interrupted => continue,
+			// A quiet peer; whatever did arrive has to stand on its own
+			Err(_) if !reply.is_empty() => break,
+			Err(_) => return Err(Failure::InvalidArgument)
+		};
+
+		if size == 0 { break }
+
+		reply.extend_from_slice(&buf[0..size]);
+		if reply.len() > MAX_REPLY_BYTES { return Err(Failure::InvalidBytes) }
+	}
+
+	match reply.is_empty() {
+		true => Err(Failure::InvalidArgument),
+		false => Ok(reply)
+	}
+}
+
 /*
 
 */
@@ -99,9 +181,11 @@ fn content_len(bytes: &[u8]) -> Option<(usize,usize)> {
 pub struct Tcp;
 pub struct Dvsp;
 pub struct Management;
+pub struct MetricsHttp;
+pub struct RemoteManagement;
 
 impl Dvsp {
-	pub fn start(config: &Config) -> Result<Success,Failure> {
+	pub fn start(config: &SharedConfig) -> Result<Success,Failure> {
 		
 		let sa = SocketAddr::from_str(&format!("0.0.0.0:{}",Port::Dvsp)).unwrap();
 		let socket = match UdpSocket::bind(sa) {
@@ -119,7 +203,7 @@ impl Dvsp {
 		
 		match epoll::ctl(epfd, ctl_op::ADD, sfd, &mut event) {
 			Ok(()) => { },
-			Err(e) => println!("[Error] CtlError on add: {}", e)
+			Err(e) => log_error!("service", "CtlError on add: {}", e)
 		};
 		
 		let cfg_clone = config.clone();
@@ -131,7 +215,7 @@ impl Dvsp {
 	
 /*		match s.join() {
 			Ok(_) => { },
-			_ => println!("[Error] Error on UDP thread join"),
+			_ => log_error!("service", "Error on UDP thread join"),
 		}	
 */
 		Ok(Success::Ok)
@@ -141,7 +225,20 @@ impl Dvsp {
 	
 	
 	
-	fn epoll_wait(epfd: RawFd, socket: UdpSocket, config: Config) {
+	/// The datagram sent back for a result; anything too large to
+	/// send whole is replaced with a referral to the stream
+	fn outbound(pr: ProtocolResult, to: &SocketAddr) -> Bytes {
+		let outbound = pr_bytes!(pr);
+		
+		if outbound.len() > Transport::Udp.max_response() {
+			log_info!("service", "{} byte response to {} referred to stream", outbound.len(), to);
+			return response_truncated().to_bytes()
+		}
+		
+		outbound
+	}
+	
+	fn epoll_wait(epfd: RawFd, socket: UdpSocket, shared: SharedConfig) {
 	
 		let mut bytes = [0;4096];
 	
@@ -149,37 +246,54 @@ impl Dvsp {
 	  
 	    unsafe { events.set_len(100); }
 	    
-	    let nio = match config.live_test {
+	    let config = shared.current();
+	    let opened = match config.live_test {
 			false => {
-				NetspaceIo::new("/var/lib/springdvs/gsn.db") 
+				NetspaceIo::open(NETSPACE_DATABASE)
 			},
 			true => {
-				println!("[Alert] Testing enabled -- using testing database");
-				let nio = NetspaceIo::new("live-testing.db");
-				
-				setup_live_test_env(&nio, &config);
-				nio
-			
+				log_warn!("system", "Testing enabled -- using testing database");
+				NetspaceIo::open(LIVE_TEST_DATABASE).map(|nio| {
+					setup_live_test_env(&nio, &config);
+					nio
+				})
 			}
 		};
 	    
+	    let nio = match opened {
+	    	Ok(nio) => nio,
+	    	Err(e) => {
+	    		log_error!("service", "UDP service failed to open the netspace ({:?})", e);
+	    		return
+	    	}
+	    };
+	    
 	    netspace_add_self(&nio, &config);
 
-	    println!("[System] UDP Service Online");
-	    loop {
-		    match epoll::wait(epfd, &mut events[..], -1) {
+	    // Held until the loop ends and the database is closed
+	    let _service = Work::hold();
+
+	    log_info!("system", "UDP Service Online");
+	    status::set_online(Service::Udp, true);
+	    while !lifecycle::stopping() {
+		    match epoll::wait(epfd, &mut events[..], EPOLL_TIMEOUT_MS) {
 		
 		        Ok(num_events) => {
 		            
 		            
 		            for _ in 0..num_events {
+						let _work = match Work::begin() {
+							Some(w) => w,
+							None => break
+						};
 
 		       			let (sz, from) = match socket.recv_from(&mut bytes) {
 							Err(_) => return,
 							Ok(s) => s
 						};
 
-						let svr = Svr::new(from, Box::new(config.clone()), &nio);
+						nio.set_actor(&format!("{}", from));
+						let svr = Svr::new(from, Box::new(shared.current()), &nio);
 						let pr = match Message::from_bytes(&bytes[0..sz]) {
 							Ok(m) => Protocol::process(&m, svr, Box::new(ChainService{})),
 							Err(e) => {
@@ -187,13 +301,14 @@ impl Dvsp {
 								let mut v : Bytes = Vec::new();
 
 								v.extend_from_slice(&bytes[0..sz]);
-								println!("[Error] Parse Error: {:?}\nDump:\n{:?}", e, v);
+								log_warn!("protocol", "Parse Error from {}: {:?}", from, e);
+								log_debug!("protocol", "Dump: {:?}", v);
 								ProtocolResult::Message(response(Response::MalformedContent))
 							}
 
 						};
 						
-						let outbound = pr_bytes!(pr);
+						let outbound = Dvsp::outbound(pr, &from);
 
 		            	match socket.send_to(outbound.as_slice(), from) {
 		            		Err(_) => return,
@@ -203,9 +318,12 @@ impl Dvsp {
 		            }
 		        }
 
-		        Err(e) => println!("[Error] Error on epoll::wait(): {}", e)
+		        Err(e) => log_error!("service", "Error on epoll::wait(): {}", e)
 			}
 	    }
+	    
+	    log_info!("system", "UDP Service Offline");
+	    status::set_online(Service::Udp, false);
 	}
 
 }
@@ -213,27 +331,37 @@ impl Dvsp {
 
 impl Tcp {
 
-	pub fn start(cfg: &Config) -> Result<Success,Failure> {
+	pub fn start(cfg: &SharedConfig) -> Result<Success,Failure> {
 		
 		let listener = TcpListener::bind("0.0.0.0:55300").unwrap();
 
-		let config = cfg.clone();
+		let shared = cfg.clone();
 		
 
-		let s = thread::spawn(move|| {
+		thread::spawn(move|| {
 				
-			let nio = match config.live_test {
-				false => {
-					NetspaceIo::new("/var/lib/springdvs/gsn.db") 
-				},
-				true => {
-					NetspaceIo::new("live-testing.db")
+			let opened = NetspaceIo::open(database_path(shared.current().live_test));
+			
+			let nio = match opened {
+				Ok(nio) => nio,
+				Err(e) => {
+					log_error!("service", "TCP service failed to open the netspace ({:?})", e);
+					return
 				}
 			};
+			
+			let _service = Work::hold();
 		    
-			println!("[System] TCP Service Online");
+			log_info!("system", "TCP Service Online");
+			status::set_online(Service::Tcp, true);
 			for stream in listener.incoming() {
 				
+				// Accepted connections after a stop are left unanswered
+				let _work = match Work::begin() {
+					Some(w) => w,
+					None => break
+				};
+				
 				match stream {
 					Ok(mut stream) => {
 	
@@ -250,35 +378,42 @@ impl Tcp {
 						};
 
 						if size > 4 {
-							let out : Vec<u8> = Tcp::handle_request(&buf[0..size], &mut address, &config, &nio);
+							let out : Vec<u8> = Tcp::handle_request(&buf[0..size], &mut address, &shared.current(), &nio);
 	
-							stream.write(out.as_slice()).unwrap();
+							if let Err(e) = stream.write_all(out.as_slice()) {
+								log_warn!("service", "Reply to {} failed ({})", address, e);
+							}
 	
 						}
 	
 					},
 					Err(_) => { }
 				}
-			}	    
+			}
+			
+			log_info!("system", "TCP Service Offline");
+			status::set_online(Service::Tcp, false);
 		});
 		
-		match s.join() {
-			Ok(_) => { },
-			_ => println!("[Error] Error on TCP thread join"),
-		}	
 		Ok(Success::Ok)
 		
 	}
 	
+	/// Unblock the listener so it notices the primary is stopping
+	pub fn wake() {
+		let _ = TcpStream::connect("127.0.0.1:55300");
+	}
+	
 	pub fn handle_request(bytes: &[u8], address: &mut SocketAddr, config: &Config, nio: &NetspaceIo) -> Bytes {
 		let check = &bytes[0..4];
+		nio.set_actor(&format!("{}", address));
 		
 		if &check == &"POST".as_bytes() {
 			// Here sort it as an HTTP service layer
 			match HttpWrapper::deserialise_request(Vec::from(bytes), address) {
 				Ok(msg) => {
 					
-					let svr = Svr::new(address.clone(), Box::new(config.clone()), nio);
+					let svr = Svr::new(address.clone(), Box::new(config.clone()), nio).over(Transport::Tcp);
 					
 					let b = pr_bytes!(Protocol::process(&msg, svr, Box::new(ChainService{})));
 					return HttpWrapper::serialise_response_bytes(&b)
@@ -287,7 +422,7 @@ impl Tcp {
 			};
 		}
 
-		let svr = Svr::new(address.clone(), Box::new(config.clone()), nio);
+		let svr = Svr::new(address.clone(), Box::new(config.clone()), nio).over(Transport::Tcp);
 		// Here we handle a straight DVSP TCP stream
 		let m = match Message::from_bytes(bytes) {
 			Ok(m) => m,
@@ -315,91 +450,337 @@ impl Tcp {
 			Err(_) => return Err(Failure::InvalidArgument)
 		};
 
-		stream.write(serial.as_slice()).unwrap();
+		let _ = stream.set_read_timeout(Some(Duration::new(STREAM_READ_SECS, 0)));
 
-		let mut buf = [0;4096];
-		let size = match stream.read(&mut buf) {
-					Ok(s) => s,
-					Err(_) => 0
-		};
+		if let Err(e) = stream.write_all(serial.as_slice()) {
+			log_warn!("service", "Request to {} failed to send ({})", address, e);
+			return Err(Failure::InvalidArgument)
+		}
 
-		if size == 0 { return Err(Failure::InvalidArgument) }
-		
-		if service == NodeService::Http {
-			let (mut msgbuf, hdrend) = try!(HttpWrapper::deserialise_response(Vec::from(&buf[0..size])));
-
-			match content_len(msgbuf.as_slice()) {
-				Some((conlen,split)) => {
-					let metalen = hdrend + split;
-
-					if (metalen + conlen) > 4096 {
-						let diff = conlen - (4096-metalen);
-						let mut vbuf = Vec::new();
-						vbuf.resize(diff, 0);
-						match stream.read(&mut vbuf.as_mut_slice()) {
-							Ok(s) => s,
-							Err(_) =>  0
-						};
-						msgbuf.append(&mut vbuf);	
-					}
-				}
-				_ => { }
-			}
-			
-			let mstr = msgbuf.as_slice();
+		let reply = match service {
+			NodeService::Http => {
+				let raw = try!(read_reply(&mut stream, |b| {
+					HttpWrapper::deserialise_response(Vec::from(b)).ok()
+						.and_then(|(msgbuf, hdrend)| content_len(msgbuf.as_slice()).map(|(conlen,split)| hdrend + split + conlen))
+				}));
 
+				let (msgbuf, _) = try!(HttpWrapper::deserialise_response(raw));
+				msgbuf
+			},
+			_ => try!(read_reply(&mut stream, |b| content_len(b).map(|(conlen,split)| split + conlen)))
+		};
 
-			match Message::from_bytes(mstr) {
-				Ok(m) => Ok(m),
-				Err(e) => {
-					 println!("[Error] {:?}\nDumping:\n{}", e, str::from_utf8(mstr).unwrap());
-					 Err(Failure::InvalidBytes)
-				} 
+		match Message::from_bytes(reply.as_slice()) {
+			Ok(m) => Ok(m),
+			Err(e) => {
+				 log_warn!("service", "Unreadable response from {}: {:?}", address, e);
+				 log_debug!("service", "Dump: {}", String::from_utf8_lossy(reply.as_slice()));
+				 Err(Failure::InvalidBytes)
 			}
-
-			
-			
-		} else {
-			Ok(Message::from_bytes(&buf[0..size]).unwrap())
 		}
 	} 
 
 }
 
 impl Management {
-	pub fn start(cfg: &Config) -> Result<Success,Failure> {
-		let config = cfg.clone();
+	pub fn start(cfg: &SharedConfig) -> Result<Success,Failure> {
+		let shared = cfg.clone();
 		
-		let s = thread::spawn(move|| {
+		thread::spawn(move|| {
 
-			let _ = remove_file("/var/run/springdvs/primary.sock");
-			let listener = match UnixListener::bind("/var/run/springdvs/primary.sock") {
+			let _ = remove_file(MANAGEMENT_SOCKET);
+			let listener = match UnixListener::bind(MANAGEMENT_SOCKET) {
 				Ok(l) => l,
 				Err(e) => {
-					 println!("[Error] Management Service socket failed to bind ({})", e);
+					 log_error!("management", "Management Service socket failed to bind ({})", e);
 					 return
 				}
 			};
 
-			println!("[System] Management service online");
+			let _service = Work::hold();
+
+			log_info!("system", "Management service online");
+			status::set_online(Service::Management, true);
 			
 			for unix_stream in listener.incoming() {
-				let c = config.clone();
+				let work = match Work::begin() {
+					Some(w) => w,
+					None => break
+				};
+				
+				let c = shared.clone();
 				match unix_stream {
 					Ok(stream) => {
-						 thread::spawn(|| management_handler(stream, c));
+						 thread::spawn(move|| {
+							 let _work = work;
+							 management_handler(stream, c)
+						 });
 						  },
 					Err(_) => { break; }
 				}
 			}
 			
 			drop(listener);
+			Management::cleanup();
+			status::set_online(Service::Management, false);
 
 		});
 		
-		if cfg.toggle_offline == true {
-			let _ = s.join();
+		Ok(Success::Ok)
+	}
+	
+	/// Unblock the listener so it notices the primary is stopping
+	pub fn wake() {
+		let _ = UnixStream::connect(MANAGEMENT_SOCKET);
+	}
+	
+	pub fn cleanup() {
+		let _ = remove_file(MANAGEMENT_SOCKET);
+	}
+}
+
+impl MetricsHttp {
+	/// Serve the traffic metrics as Prometheus text on `GET /metrics`;
+	/// `metrics=off` in node.conf leaves the exporter down
+	pub fn start(cfg: &SharedConfig) -> Result<Success,Failure> {
+		let config = cfg.current();
+		
+		if let Some(ref v) = config.setting("metrics") {
+			if v == "off" {
+				log_info!("system", "Metrics exporter disabled");
+				return Ok(Success::Ok)
+			}
 		}
+		
+		let address = config.setting("metrics_address").unwrap_or(METRICS_ADDRESS.to_string());
+		
+		let listener = match TcpListener::bind(address.as_str()) {
+			Ok(l) => l,
+			Err(e) => {
+				log_error!("service", "Metrics exporter failed to bind {} ({})", address, e);
+				return Err(Failure::InvalidArgument)
+			}
+		};
+		bound(metrics_bound(), &listener);
+		
+		thread::spawn(move|| {
+			log_info!("system", "Metrics exporter online at {}", address);
+			status::set_online(Service::Metrics, true);
+			
+			for stream in listener.incoming() {
+				if lifecycle::stopping() { break }
+				
+				match stream {
+					Ok(mut stream) => {
+						// One scrape at a time, so never wait long on any of them
+						let _ = stream.set_read_timeout(Some(Duration::new(METRICS_READ_SECS, 0)));
+						let _ = stream.set_write_timeout(Some(Duration::new(METRICS_READ_SECS, 0)));
+						
+						let mut buf = [0;1024];
+						let size = match stream.read(&mut buf) {
+							Ok(s) => s,
+							Err(_) => continue
+						};
+						
+						let _ = stream.write_all(&MetricsHttp::handle_request(&buf[0..size]));
+					},
+					Err(_) => { }
+				}
+			}
+			
+			status::set_online(Service::Metrics, false);
+		});
+		
 		Ok(Success::Ok)
 	}
+	
+	/// Unblock the listener so it notices the primary is stopping
+	pub fn wake() {
+		wake_listener(metrics_bound());
+	}
+	
+	fn handle_request(bytes: &[u8]) -> Bytes {
+		let (status, body) = match bytes.starts_with(b"GET /metrics ") || bytes.starts_with(b"GET / ") {
+			true => ("200 OK", metrics::render()),
+			false => ("404 Not Found", "Not found\n".to_string())
+		};
+		
+		let mut out = format!("HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n", status, body.len()).into_bytes();
+		out.extend_from_slice(body.as_bytes());
+		out
+	}
+}
+
+impl RemoteManagement {
+	pub fn start(cfg: &SharedConfig) -> Result<Success,Failure> {
+		let config = cfg.current();
+		
+		if !remote::enabled(&config) {
+			return Ok(Success::Ok)
+		}
+		
+		let acceptor = match remote::acceptor(&config) {
+			Ok(a) => Arc::new(a),
+			Err(e) => {
+				log_error!("management", "Remote management not started: {}", e);
+				return Err(Failure::InvalidArgument)
+			}
+		};
+		
+		let address = config.setting("remote_man_address").unwrap_or(remote::REMOTE_ADDRESS.to_string());
+		
+		let listener = match TcpListener::bind(address.as_str()) {
+			Ok(l) => l,
+			Err(e) => {
+				log_error!("management", "Remote management failed to bind {} ({})", address, e);
+				return Err(Failure::InvalidArgument)
+			}
+		};
+		bound(remote_bound(), &listener);
+		
+		let shared = cfg.clone();
+		
+		thread::spawn(move|| {
+			// One connection serves every ban check; sessions open
+			// their own once the caller is authenticated
+			let gate = match NetspaceIo::open(database_path(shared.current().live_test)) {
+				Ok(nio) => nio,
+				Err(e) => {
+					log_error!("management", "Remote management not started; unable to open the netspace ({:?})", e);
+					return
+				}
+			};
+			
+			let open = Arc::new(AtomicUsize::new(0));
+			
+			log_info!("system", "Remote management online at {}", address);
+			status::set_online(Service::Remote, true);
+			
+			for stream in listener.incoming() {
+				let work = match Work::begin() {
+					Some(w) => w,
+					None => break
+				};
+				
+				let stream = match stream {
+					Ok(s) => s,
+					Err(_) => continue
+				};
+				
+				let ip = match stream.peer_addr() {
+					Ok(a) => format!("{}", a.ip()),
+					Err(_) => continue
+				};
+				
+				// Addresses banned for bad tokens are not even given a
+				// handshake, nor is anyone while bans cannot be checked
+				match gate.throttle_banned(&ip) {
+					Ok(false) => { },
+					Ok(true) => {
+						log_warn!("management", "Refused remote management from banned {}", ip);
+						continue
+					},
+					Err(e) => {
+						log_error!("management", "Refused remote management from {}; unable to check bans ({:?})", ip, e);
+						continue
+					}
+				}
+				
+				let session = match Session::take(&open) {
+					Some(s) => s,
+					None => {
+						log_warn!("management", "Refused remote management from {}; too many sessions", ip);
+						continue
+					}
+				};
+				
+				let acceptor = acceptor.clone();
+				let c = shared.clone();
+				thread::spawn(move|| {
+					let _work = work;
+					let _session = session;
+					remote_handler(stream, &acceptor, c)
+				});
+			}
+			
+			status::set_online(Service::Remote, false);
+		});
+		
+		Ok(Success::Ok)
+	}
+	
+	/// Unblock the listener so it notices the primary is stopping
+	pub fn wake() {
+		wake_listener(remote_bound());
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use config::mocks::MockConfig;
+	use protocol::is_truncated;
+
+	#[test]
+	fn ts_service_dvsp_oversized_truncated_p() {
+		let nio = NetspaceIo::new(":memory:");
+		for i in 0..200 {
+			nio.gsn_node_register(&Node::from_str(&format!("spring:node{},host:foobar,address:192.168.1.2,role:org,service:http", i)).unwrap()).unwrap();
+		}
+
+		let from = SocketAddr::from_str("192.168.1.2:55400").unwrap();
+		let svr = Svr::new(from, Box::new(MockConfig::dflt()), &nio);
+		let pr = Protocol::process(&Message::from_bytes(b"info network").unwrap(), svr, Box::new(ChainService{}));
+
+		let outbound = Dvsp::outbound(pr, &from);
+		assert!(outbound.len() <= Transport::Udp.max_response());
+		assert!(is_truncated(&Message::from_bytes(&outbound).unwrap()));
+	}
+
+	/// Hands a reply over a little at a time, as a busy stream does
+	struct Trickle {
+		bytes: Vec<u8>,
+		at: usize,
+	}
+
+	impl Read for Trickle {
+		fn read(&mut self, buf: &mut [u8]) -> ::std::io::Result<usize> {
+			let n = ::std::cmp::min(1000, ::std::cmp::min(buf.len(), self.bytes.len() - self.at));
+			buf[0..n].copy_from_slice(&self.bytes[self.at..self.at+n]);
+			self.at += n;
+			Ok(n)
+		}
+	}
+
+	#[test]
+	fn ts_service_read_reply_oversized_p() {
+		let nio = NetspaceIo::new(":memory:");
+		for i in 0..200 {
+			nio.gsn_node_register(&Node::from_str(&format!("spring:node{},host:foobar,address:192.168.1.2,role:org,service:http", i)).unwrap()).unwrap();
+		}
+
+		let from = SocketAddr::from_str("192.168.1.2:55400").unwrap();
+		let svr = Svr::new(from, Box::new(MockConfig::dflt()), &nio).over(Transport::Local);
+		let bytes = pr_bytes!(Protocol::process(&Message::from_bytes(b"info network").unwrap(), svr, Box::new(ChainService{})));
+		assert!(bytes.len() > 4096);
+
+		let mut stream = Trickle { bytes: bytes.clone(), at: 0 };
+		let reply = read_reply(&mut stream, |b| content_len(b).map(|(conlen,split)| split + conlen)).unwrap();
+		assert_eq!(reply, bytes);
+		assert!(Message::from_bytes(reply.as_slice()).is_ok());
+	}
+
+	#[test]
+	fn ts_service_read_reply_empty_f() {
+		let mut stream = Trickle { bytes: Vec::new(), at: 0 };
+		assert!(read_reply(&mut stream, |b| content_len(b).map(|(conlen,split)| split + conlen)).is_err());
+		assert_eq!(content_len(b"200 12"), None);
+	}
+
+	#[test]
+	fn ts_service_dvsp_outbound_whole_p() {
+		let from = SocketAddr::from_str("192.168.1.2:55400").unwrap();
+		let outbound = Dvsp::outbound(ProtocolResult::Message(response(Response::Ok)), &from);
+		assert!(!is_truncated(&Message::from_bytes(&outbound).unwrap()));
+	}
 }
@@ -0,0 +1,518 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use rustc_serialize::json::{Json, ToJson};
+
+use netspace::{Netspace,Metaspace,MetaspaceEntry,NetspaceIo,Node,NodeRole,NodeService,NodeState,Success,StorageFailure};
+
+/*
+ * Portable netspace snapshots
+ *
+ * A snapshot holds the geosub netspace, the geotop roots, the
+ * registration tokens and the metaspace of a primary as versioned
+ * JSON. Nodes, tokens and the metaspace go through the
+ * `Netspace` and `Metaspace` traits; geotop priorities are read
+ * from the primary's own tables, and a restore is applied in a
+ * single transaction so a failed import leaves nothing behind.
+ *
+ * Version 2 added geotop priorities; older snapshots restore
+ * their roots at priority 1.
+ */
+
+pub static SNAPSHOT_VERSION : i64 = 2;
+
+macro_rules! try_opt {
+	($e: expr) => (
+		match $e {
+			Some(s) => s,
+			None => return None,
+		}
+	)
+}
+
+#[derive(Debug,Clone,PartialEq)]
+pub struct NodeRecord {
+	pub springname: String,
+	pub hostname: String,
+	pub address: String,
+	pub service: NodeService,
+	pub state: NodeState,
+	pub role: NodeRole,
+	pub key: String,
+}
+
+impl NodeRecord {
+	pub fn from_node(node: &Node) -> NodeRecord {
+		NodeRecord {
+			springname: node.springname().to_string(),
+			hostname: node.hostfield(),
+			address: node.address().to_string(),
+			service: node.service(),
+			state: node.state(),
+			role: node.role(),
+			key: node.key().to_string(),
+		}
+	}
+
+	pub fn to_node(&self) -> Node {
+		Node::new(&self.springname, &self.hostname, &self.address, self.service, self.state, self.role, &self.key)
+	}
+
+	pub fn from_json(data: &Json) -> Option<NodeRecord> {
+		Some(NodeRecord {
+			springname: try_opt!(string_field(data, "springname")),
+			hostname: try_opt!(string_field(data, "hostname")),
+			address: try_opt!(string_field(data, "address")),
+			service: try_opt!(NodeService::from_str(&try_opt!(string_field(data, "service")))),
+			state: try_opt!(NodeState::from_str(&try_opt!(string_field(data, "state")))),
+			role: try_opt!(NodeRole::from_str(&try_opt!(string_field(data, "role")))),
+			key: try_opt!(string_field(data, "key")),
+		})
+	}
+}
+
+impl ToJson for NodeRecord {
+	fn to_json(&self) -> Json {
+		let mut d = BTreeMap::new();
+		d.insert("springname".to_string(), self.springname.to_json());
+		d.insert("hostname".to_string(), self.hostname.to_json());
+		d.insert("address".to_string(), self.address.to_json());
+		d.insert("service".to_string(), format!("{}", self.service).to_json());
+		d.insert("state".to_string(), format!("{}", self.state).to_json());
+		d.insert("role".to_string(), format!("{}", self.role).to_json());
+		d.insert("key".to_string(), self.key.to_json());
+		Json::Object(d)
+	}
+}
+
+impl fmt::Display for NodeRecord {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{} ({}, {}, {}, {}, {})", self.springname, self.hostname,
+				self.address, self.service, self.state, self.role)
+	}
+}
+
+#[derive(Debug,Clone,PartialEq)]
+pub struct Snapshot {
+	pub version: i64,
+	pub netspace: Vec<NodeRecord>,
+	pub geotop: Vec<(String,NodeRecord,i64)>,
+	pub tokens: Vec<(String,String)>,
+	pub metaspace: Vec<MetaspaceEntry>,
+}
+
+fn string_field(data: &Json, key: &str) -> Option<String> {
+	match data.find(key) {
+		Some(&Json::String(ref s)) => Some(s.clone()),
+		Some(&Json::Null) => Some(String::new()),
+		_ => None
+	}
+}
+
+impl Snapshot {
+
+	/// Capture the current state of a netspace; a read that fails fails
+	/// the capture rather than leave a section looking empty
+	pub fn take(nio: &NetspaceIo) -> Result<Snapshot,StorageFailure> {
+		let roots = try!(nio.gtn_roots(None));
+
+		let mut geosubs : Vec<String> = roots.iter().map(|r| r.geosub.clone()).collect();
+		geosubs.dedup();
+
+		let mut geotop = Vec::new();
+		for gsn in geosubs {
+			for node in try!(nio.try_gtn_geosub_root_nodes(&gsn)) {
+				let priority = roots.iter()
+								.find(|r| r.geosub == gsn && r.springname == node.springname())
+								.map(|r| r.priority)
+								.unwrap_or(1);
+				geotop.push((gsn.clone(), NodeRecord::from_node(&node), priority))
+			}
+		}
+
+		Ok(Snapshot {
+			version: SNAPSHOT_VERSION,
+			netspace: try!(nio.try_gsn_nodes()).iter().map(NodeRecord::from_node).collect(),
+			geotop: geotop,
+			tokens: try!(nio.try_gsn_tokens()),
+			metaspace: try!(nio.try_gsn_metaspace()),
+		})
+	}
+
+	pub fn to_string(&self) -> String {
+		format!("{}\n", self.to_json().pretty())
+	}
+
+	pub fn from_str(s: &str) -> Result<Snapshot,String> {
+		let data = match Json::from_str(s) {
+			Ok(d) => d,
+			Err(e) => return Err(format!("JSON parse error '{}'", e))
+		};
+
+		let version = match data.find("version").and_then(|v| v.as_i64()) {
+			Some(v) => v,
+			None => return Err("Snapshot has no version".to_string())
+		};
+
+		if version > SNAPSHOT_VERSION {
+			return Err(format!("Snapshot version {} is newer than supported version {}", version, SNAPSHOT_VERSION))
+		}
+
+		let mut snapshot = Snapshot { version: version, netspace: Vec::new(), geotop: Vec::new(), tokens: Vec::new(), metaspace: Vec::new() };
+
+		for (i, n) in Snapshot::section(&data, "geosub_netspace").iter().enumerate() {
+			match NodeRecord::from_json(n) {
+				Some(r) => snapshot.netspace.push(r),
+				None => return Err(format!("Malformed geosub_netspace entry at index {}", i))
+			}
+		}
+
+		for (i, n) in Snapshot::section(&data, "geotop_netspace").iter().enumerate() {
+			let priority = match n.find("priority") {
+				Some(p) => p.as_i64(),
+				None => Some(1)
+			};
+
+			match (string_field(n, "geosub"), NodeRecord::from_json(n), priority) {
+				(Some(g), Some(r), Some(p)) => snapshot.geotop.push((g, r, p)),
+				_ => return Err(format!("Malformed geotop_netspace entry at index {}", i))
+			}
+		}
+
+		for (i, t) in Snapshot::section(&data, "tokens").iter().enumerate() {
+			match (string_field(t, "token"), string_field(t, "spring")) {
+				(Some(token), Some(spring)) => snapshot.tokens.push((token, spring)),
+				_ => return Err(format!("Malformed token entry at index {}", i))
+			}
+		}
+
+		for (i, m) in Snapshot::section(&data, "metaspace").iter().enumerate() {
+			match (string_field(m, "settlement"), string_field(m, "postcode"), string_field(m, "county"), string_field(m, "geosub")) {
+				(Some(s), Some(p), Some(c), Some(g)) => snapshot.metaspace.push(MetaspaceEntry::new(&s, &p, &c, &g)),
+				_ => return Err(format!("Malformed metaspace entry at index {}", i))
+			}
+		}
+
+		Ok(snapshot)
+	}
+
+	fn section(data: &Json, key: &str) -> Vec<Json> {
+		match data.find(key).and_then(|s| s.as_array()) {
+			Some(a) => a.clone(),
+			None => Vec::new()
+		}
+	}
+}
+
+impl ToJson for Snapshot {
+	fn to_json(&self) -> Json {
+		let mut d = BTreeMap::new();
+		d.insert("version".to_string(), self.version.to_json());
+		d.insert("geosub_netspace".to_string(), Json::Array(self.netspace.iter().map(|n| n.to_json()).collect()));
+
+		d.insert("geotop_netspace".to_string(), Json::Array(self.geotop.iter().map(|&(ref g, ref n, p)| {
+			let mut j = n.to_json();
+			if let Json::Object(ref mut o) = j {
+				o.insert("geosub".to_string(), g.to_json());
+				o.insert("priority".to_string(), p.to_json());
+			}
+			j
+		}).collect()));
+
+		d.insert("tokens".to_string(), Json::Array(self.tokens.iter().map(|&(ref token, ref spring)| {
+			let mut o = BTreeMap::new();
+			o.insert("token".to_string(), token.to_json());
+			o.insert("spring".to_string(), spring.to_json());
+			Json::Object(o)
+		}).collect()));
+
+		d.insert("metaspace".to_string(), Json::Array(self.metaspace.iter().map(|m| {
+			let mut o = BTreeMap::new();
+			o.insert("settlement".to_string(), m.settlement.to_json());
+			o.insert("postcode".to_string(), m.postcode.to_json());
+			o.insert("county".to_string(), m.county.to_json());
+			o.insert("geosub".to_string(), m.geosub.to_json());
+			Json::Object(o)
+		}).collect()));
+
+		Json::Object(d)
+	}
+}
+
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub enum ImportMode {
+	/// Add and update entries from the snapshot, keep everything else
+	Merge,
+	/// Make the netspace match the snapshot exactly
+	Replace,
+}
+
+#[derive(Debug,Clone,PartialEq)]
+pub enum Change {
+	AddNode(NodeRecord),
+	UpdateNode(NodeRecord,NodeRecord),
+	RemoveNode(NodeRecord),
+	AddRoot(String,NodeRecord,i64),
+	RemoveRoot(String,NodeRecord),
+	AddToken(String,String),
+	RemoveToken(String,String),
+	AddMeta(MetaspaceEntry),
+	RemoveMeta(MetaspaceEntry),
+}
+
+impl fmt::Display for Change {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			Change::AddNode(ref n) => write!(f, "+ node {}", n),
+			Change::UpdateNode(ref o, ref n) => write!(f, "~ node {} -> {}", o, n),
+			Change::RemoveNode(ref n) => write!(f, "- node {}", n),
+			Change::AddRoot(ref g, ref n, p) => write!(f, "+ geotop {} {} priority {}", g, n, p),
+			Change::RemoveRoot(ref g, ref n) => write!(f, "- geotop {} {}", g, n),
+			Change::AddToken(ref t, ref s) => write!(f, "+ token {} {}", t, s),
+			Change::RemoveToken(ref t, ref s) => write!(f, "- token {} {}", t, s),
+			Change::AddMeta(ref m) => write!(f, "+ metaspace {}", m),
+			Change::RemoveMeta(ref m) => write!(f, "- metaspace {}", m),
+		}
+	}
+}
+
+/// Work out the changes needed to bring `current` in line with `target`
+pub fn diff(current: &Snapshot, target: &Snapshot, mode: ImportMode) -> Vec<Change> {
+	let mut v = Vec::new();
+
+	for n in &target.netspace {
+		match current.netspace.iter().find(|c| c.springname == n.springname) {
+			None => v.push(Change::AddNode(n.clone())),
+			Some(c) if c != n => v.push(Change::UpdateNode(c.clone(), n.clone())),
+			_ => { }
+		}
+	}
+
+	for &(ref g, ref n, p) in &target.geotop {
+		match current.geotop.iter().find(|&&(ref cg, ref c, _)| cg == g && c.springname == n.springname) {
+			None => v.push(Change::AddRoot(g.clone(), n.clone(), p)),
+			Some(&(_, ref c, cp)) if c != n || cp != p => {
+				v.push(Change::RemoveRoot(g.clone(), c.clone()));
+				v.push(Change::AddRoot(g.clone(), n.clone(), p));
+			},
+			_ => { }
+		}
+	}
+
+	for t in &target.tokens {
+		if !current.tokens.contains(t) { v.push(Change::AddToken(t.0.clone(), t.1.clone())) }
+	}
+
+	for m in &target.metaspace {
+		if !current.metaspace.contains(m) { v.push(Change::AddMeta(m.clone())) }
+	}
+
+	if mode == ImportMode::Merge { return v }
+
+	for c in &current.netspace {
+		if !target.netspace.iter().any(|n| n.springname == c.springname) {
+			v.push(Change::RemoveNode(c.clone()))
+		}
+	}
+
+	for &(ref cg, ref c, _) in &current.geotop {
+		if !target.geotop.iter().any(|&(ref g, ref n, _)| g == cg && n.springname == c.springname) {
+			v.push(Change::RemoveRoot(cg.clone(), c.clone()))
+		}
+	}
+
+	for t in &current.tokens {
+		if !target.tokens.contains(t) { v.push(Change::RemoveToken(t.0.clone(), t.1.clone())) }
+	}
+
+	for m in &current.metaspace {
+		if !target.metaspace.contains(m) { v.push(Change::RemoveMeta(m.clone())) }
+	}
+
+	v
+}
+
+/// Apply a set of changes in one transaction; the first failure
+/// rolls back everything applied before it
+pub fn apply(changes: &Vec<Change>, nio: &NetspaceIo) -> Result<usize,String> {
+	let ns : &Netspace = nio;
+	let ms : &Metaspace = nio;
+
+	let transaction = match nio.transaction() {
+		Ok(t) => t,
+		Err(e) => return Err(format!("Unable to start transaction ({:?})", e))
+	};
+
+	for (i, change) in changes.iter().enumerate() {
+		let r = match *change {
+			Change::AddNode(ref n) => {
+				// Registration always disables a node, so restore the state after
+				let node = n.to_node();
+				match ns.gsn_node_register(&node) {
+					Ok(_) if n.state != NodeState::Disabled => ns.gsn_node_update_state(&node),
+					r => r
+				}
+			},
+			Change::UpdateNode(ref o, ref n) => {
+				let node = n.to_node();
+				let mut r = Ok(Success::Ok);
+				if r.is_ok() && o.hostname != n.hostname { r = ns.gsn_node_update_hostname(&node) }
+				if r.is_ok() && o.address != n.address { r = ns.gsn_node_update_address(&node) }
+				if r.is_ok() && o.service != n.service { r = ns.gsn_node_update_service(&node) }
+				if r.is_ok() && o.state != n.state { r = ns.gsn_node_update_state(&node) }
+				if r.is_ok() && o.role != n.role { r = ns.gsn_node_update_role(&node) }
+				r
+			},
+			Change::RemoveNode(ref n) => ns.gsn_node_unregister(&n.to_node()),
+			Change::AddRoot(ref g, ref n, p) => {
+				match ns.gtn_geosub_register_node(&n.to_node(), g) {
+					Ok(_) if p != 1 => nio.gtn_geosub_update_priority(&n.springname, g, p),
+					r => r
+				}
+			},
+			Change::RemoveRoot(ref g, ref n) => ns.gtn_geosub_unregister_node(&n.to_node(), g),
+			Change::AddToken(ref t, ref s) => nio.try_gsn_add_token(t, s),
+			Change::RemoveToken(ref t, _) => nio.try_gsn_remove_token(t),
+			Change::AddMeta(ref m) => ms.gsn_metaspace_add(m),
+			Change::RemoveMeta(ref m) => ms.gsn_metaspace_remove(m),
+		};
+
+		match r {
+			Ok(_) => { },
+			Err(e) => {
+				let _ = transaction.rollback();
+				return Err(format!("Failed applying `{}` after {} change(s), nothing was applied ({:?})", change, i, e))
+			}
+		}
+	}
+
+	match transaction.commit() {
+		Ok(_) => Ok(changes.len()),
+		Err(e) => Err(format!("Unable to commit {} change(s) ({:?})", changes.len(), e))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use netspace::*;
+
+	fn populated() -> NetspaceIo {
+		let nsio = NetspaceIo::new(":memory:");
+		nsio.db().execute("
+		INSERT INTO `geosub_netspace` (springname,hostname,address,service,status,types,key) VALUES ('esusx','greenman.zu','192.168.1.1',1,1,1,'PUBLIC KEY');
+		INSERT INTO `geosub_netspace` (springname,hostname,address,service,status,types,key) VALUES ('cci','dvsnode.greenman.zu','192.168.1.2',2,1,2,'PUBLIC KEY');
+		INSERT INTO `geotop_netspace` (springname,hostname,address,service,priority,geosub,key) VALUES ('springa', 'greenman', '192.168.1.2', 1, 3, 'esusx','PUBLIC KEY');
+		INSERT INTO `geosub_tokens` (token,spring) VALUES ('3858f62230ac3c915f300c664312c63f','foo');
+		INSERT INTO `geosub_metaspace` (settlement,postcode,county,geosub) VALUES ('Lewes','BN7','East Sussex','esusx');
+		").unwrap();
+		nsio
+	}
+
+	#[test]
+	fn ts_snapshot_roundtrip_p() {
+		let nsio = populated();
+		let snap = Snapshot::take(&nsio).unwrap();
+		let parsed = Snapshot::from_str(&snap.to_string()).unwrap();
+
+		assert_eq!(snap, parsed);
+		assert_eq!(parsed.netspace.len(), 2);
+		assert_eq!(parsed.geotop[0].0, "esusx");
+		assert_eq!(parsed.geotop[0].2, 3);
+		assert_eq!(parsed.metaspace[0].postcode, "BN7");
+	}
+
+	#[test]
+	fn ts_snapshot_merge_into_empty_p() {
+		let source = populated();
+		let snap = Snapshot::take(&source).unwrap();
+
+		let target = NetspaceIo::new(":memory:");
+		let changes = diff(&Snapshot::take(&target).unwrap(), &snap, ImportMode::Merge);
+		assert_eq!(changes.len(), 5);
+		assert_eq!(apply(&changes, &target), Ok(5));
+
+		assert_eq!(Snapshot::take(&target).unwrap(), snap);
+		assert_eq!(diff(&Snapshot::take(&target).unwrap(), &snap, ImportMode::Replace).len(), 0);
+	}
+
+	#[test]
+	fn ts_snapshot_replace_removes_p() {
+		let nsio = populated();
+		let mut snap = Snapshot::take(&nsio).unwrap();
+		snap.netspace.retain(|n| n.springname != "cci");
+		snap.tokens.clear();
+
+		let changes = diff(&Snapshot::take(&nsio).unwrap(), &snap, ImportMode::Replace);
+		assert_eq!(changes.len(), 2);
+
+		assert!(diff(&Snapshot::take(&nsio).unwrap(), &snap, ImportMode::Merge).is_empty());
+	}
+
+	#[test]
+	fn ts_snapshot_priority_restored_p() {
+		let source = populated();
+		let snap = Snapshot::take(&source).unwrap();
+
+		let target = NetspaceIo::new(":memory:");
+		apply(&diff(&Snapshot::take(&target).unwrap(), &snap, ImportMode::Merge), &target).unwrap();
+		assert_eq!(target.gtn_roots(Some("esusx")).unwrap()[0].priority, 3);
+
+		// A priority change alone is still a change
+		let mut moved = snap.clone();
+		moved.geotop[0].2 = 5;
+		assert_eq!(diff(&Snapshot::take(&target).unwrap(), &moved, ImportMode::Merge).len(), 2);
+	}
+
+	#[test]
+	fn ts_snapshot_version1_priority_p() {
+		let old = Snapshot::take(&populated()).unwrap().to_string()
+					.replace("\"priority\": 3,", "")
+					.replace("\"version\": 2", "\"version\": 1");
+		let snap = Snapshot::from_str(&old).unwrap();
+		assert_eq!(snap.version, 1);
+		assert_eq!(snap.geotop[0].2, 1);
+	}
+
+	#[test]
+	fn ts_snapshot_apply_atomic_f() {
+		let nsio = populated();
+		let mut snap = Snapshot::take(&nsio).unwrap();
+		snap.netspace.retain(|n| n.springname != "cci");
+		snap.tokens.clear();
+
+		let mut changes = diff(&Snapshot::take(&nsio).unwrap(), &snap, ImportMode::Replace);
+		changes.push(Change::RemoveNode(NodeRecord::from_node(&Node::from_str("spring:void,host:void,address:192.168.1.9").unwrap())));
+
+		assert!(apply(&changes, &nsio).is_err());
+		assert_eq!(nsio.gsn_nodes().len(), 2);
+		assert_eq!(nsio.gsn_tokens().len(), 1);
+	}
+
+	#[test]
+	fn ts_snapshot_token_rollback_f() {
+		let nsio = populated();
+		nsio.db().execute("DROP TABLE `geosub_tokens`").unwrap();
+
+		let changes = vec![
+			Change::RemoveNode(NodeRecord::from_node(&nsio.gsn_node_by_springname("cci").unwrap())),
+			Change::AddToken("3858f62230ac3c915f300c664312c63f".to_string(), "esusx".to_string()),
+		];
+
+		assert!(apply(&changes, &nsio).is_err());
+		assert_eq!(nsio.gsn_nodes().len(), 2);
+	}
+
+	#[test]
+	fn ts_snapshot_take_f() {
+		let nsio = populated();
+		nsio.db().execute("DROP TABLE `geosub_netspace`").unwrap();
+		assert!(Snapshot::take(&nsio).is_err());
+	}
+
+	#[test]
+	fn ts_snapshot_version_f() {
+		assert!(Snapshot::from_str("{\"version\": 99}").is_err());
+		assert!(Snapshot::from_str("{}").is_err());
+	}
+}
@@ -0,0 +1,165 @@
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::prelude::*;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize,Ordering,ATOMIC_USIZE_INIT};
+use std::time::{SystemTime,UNIX_EPOCH};
+
+/*
+ * Runtime status
+ *
+ * What the running primary knows about itself for the `status`
+ * management zone: when it started, which of its services are
+ * online and the last few errors it logged.
+ */
+
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub enum Service {
+	Udp,
+	Tcp,
+	Management,
+	Metrics,
+	Remote,
+}
+
+impl Service {
+	pub fn all() -> Vec<Service> {
+		vec![Service::Udp, Service::Tcp, Service::Management, Service::Metrics, Service::Remote]
+	}
+
+	pub fn name(&self) -> &'static str {
+		match *self {
+			Service::Udp => "udp",
+			Service::Tcp => "tcp",
+			Service::Management => "management",
+			Service::Metrics => "metrics",
+			Service::Remote => "remote management",
+		}
+	}
+
+	fn bit(&self) -> usize {
+		match *self {
+			Service::Udp => 1,
+			Service::Tcp => 2,
+			Service::Management => 4,
+			Service::Metrics => 8,
+			Service::Remote => 16,
+		}
+	}
+}
+
+/// Errors kept for the status zone
+const RECENT_ERRORS : usize = 20;
+
+static STARTED : AtomicUsize = ATOMIC_USIZE_INIT;
+static ONLINE : AtomicUsize = ATOMIC_USIZE_INIT;
+
+#[derive(Debug,Clone,PartialEq)]
+pub struct LoggedError {
+	pub at: u64,
+	pub target: String,
+	pub msg: String,
+}
+
+fn now() -> u64 {
+	SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+pub fn mark_started() {
+	STARTED.store(now() as usize, Ordering::SeqCst);
+}
+
+/// Seconds since `mark_started`
+pub fn uptime() -> u64 {
+	match STARTED.load(Ordering::SeqCst) {
+		0 => 0,
+		s => now().saturating_sub(s as u64)
+	}
+}
+
+pub fn set_online(service: Service, online: bool) {
+	match online {
+		true => ONLINE.fetch_or(service.bit(), Ordering::SeqCst),
+		false => ONLINE.fetch_and(!service.bit(), Ordering::SeqCst),
+	};
+}
+
+pub fn is_online(service: Service) -> bool {
+	ONLINE.load(Ordering::SeqCst) & service.bit() != 0
+}
+
+/// The last `RECENT_ERRORS` errors, oldest first
+struct ErrorRing {
+	errors: VecDeque<LoggedError>,
+}
+
+impl ErrorRing {
+	fn new() -> ErrorRing {
+		ErrorRing { errors: VecDeque::new() }
+	}
+
+	fn push(&mut self, error: LoggedError) {
+		if self.errors.len() == RECENT_ERRORS { self.errors.pop_front(); }
+		self.errors.push_back(error);
+	}
+
+	fn recent(&self) -> Vec<LoggedError> {
+		self.errors.iter().rev().cloned().collect()
+	}
+}
+
+process_global!(errors, Mutex<ErrorRing>, Mutex::new(ErrorRing::new()));
+
+pub fn record_error(target: &str, msg: &str) {
+	if let Ok(mut e) = errors().lock() {
+		e.push(LoggedError { at: now(), target: target.to_string(), msg: msg.to_string() });
+	}
+}
+
+/// Most recent first
+pub fn recent_errors() -> Vec<LoggedError> {
+	match errors().lock() {
+		Ok(e) => e.recent(),
+		Err(_) => Vec::new()
+	}
+}
+
+/// Threads in the process as the kernel counts them
+pub fn thread_count() -> Option<usize> {
+	let mut s = String::new();
+	match File::open("/proc/self/status").and_then(|mut f| f.read_to_string(&mut s)) {
+		Ok(_) => { },
+		Err(_) => return None
+	}
+
+	s.lines()
+		.filter(|l| l.starts_with("Threads:"))
+		.filter_map(|l| l["Threads:".len()..].trim().parse().ok())
+		.next()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn ts_status_online_p() {
+		set_online(Service::Metrics, true);
+		assert!(is_online(Service::Metrics));
+		set_online(Service::Metrics, false);
+		assert!(!is_online(Service::Metrics));
+	}
+
+	#[test]
+	fn ts_status_recent_errors_p() {
+		let mut ring = ErrorRing::new();
+		for i in 0..(RECENT_ERRORS + 5) {
+			ring.push(LoggedError { at: 0, target: "status".to_string(), msg: format!("error {}", i) });
+		}
+
+		let e = ring.recent();
+		assert_eq!(e.len(), RECENT_ERRORS);
+		assert_eq!(e[0].msg, format!("error {}", RECENT_ERRORS + 4));
+		assert_eq!(e[RECENT_ERRORS - 1].msg, "error 5");
+	}
+}
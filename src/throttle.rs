@@ -0,0 +1,354 @@
+extern crate sqlite;
+
+use std::collections::HashMap;
+use std::sync::{Arc,Mutex};
+use std::time::{SystemTime,UNIX_EPOCH};
+
+use self::sqlite::{State,Value};
+
+use netspace::{NetspaceIo,NodeConfig,StorageFailure,step};
+use spring_dvs::protocol::CmdType;
+
+/*
+ * Per source address rate limiting
+ *
+ * Off unless node.conf has `throttle=on` or sets any of the
+ * budgets. Each command type has a budget of requests per address
+ * per minute, set with `throttle_<cmd>=<n>`. Failed registration
+ * tokens are counted separately and an address that runs up
+ * `throttle_token_failures` of them inside a ban period is refused
+ * everything for `throttle_ban_secs`.
+ *
+ * Counters are kept in memory, one set per database file so the
+ * UDP and TCP services share them, and expired windows are pruned
+ * as they go. Bans are rare and kept in the netspace database.
+ */
+
+/// Length of a rate limiting window in seconds
+const WINDOW_SECS : i64 = 60;
+
+/// Counter name failed registration tokens are kept under
+const TOKEN_FAILURE : &'static str = "token";
+
+const NOW : &'static str = "CAST(strftime('%s','now') AS INTEGER)";
+
+/// Settings that switch throttling on when present
+const BUDGETS : &'static [&'static str] = &[
+	"throttle_register", "throttle_unregister", "throttle_info", "throttle_update",
+	"throttle_service", "throttle_resolve", "throttle_token_failures", "throttle_ban_secs",
+];
+
+/// Request counts of every address, keyed on address and counter
+pub struct Counters {
+	hits: HashMap<(String,String),Hits>,
+	pruned: i64,
+}
+
+#[derive(Debug,Clone,Copy)]
+struct Hits {
+	window: i64,
+	length: i64,
+	count: i64,
+}
+
+impl Counters {
+	fn new() -> Counters {
+		Counters { hits: HashMap::new(), pruned: 0 }
+	}
+
+	// Fixed windows: the count restarts once `length` seconds have passed
+	fn count(&mut self, address: &str, counter: &str, length: i64, now: i64) -> i64 {
+		if now - self.pruned >= WINDOW_SECS {
+			self.hits.retain(|_, h| h.window + h.length > now);
+			self.pruned = now;
+		}
+
+		let h = self.hits.entry((address.to_string(), counter.to_string()))
+					.or_insert(Hits { window: now, length: length, count: 0 });
+
+		if h.window + h.length <= now {
+			*h = Hits { window: now, length: length, count: 0 };
+		}
+
+		h.count += 1;
+		h.count
+	}
+}
+
+process_global!(shared, Mutex<HashMap<String,Arc<Mutex<Counters>>>>, Mutex::new(HashMap::new()));
+
+/// Counters for a database; every in-memory database has its own
+pub fn counters(database: &str) -> Arc<Mutex<Counters>> {
+	if database == ":memory:" {
+		return Arc::new(Mutex::new(Counters::new()))
+	}
+
+	match shared().lock() {
+		Ok(mut m) => m.entry(database.to_string()).or_insert(Arc::new(Mutex::new(Counters::new()))).clone(),
+		Err(_) => Arc::new(Mutex::new(Counters::new()))
+	}
+}
+
+fn now() -> i64 {
+	match SystemTime::now().duration_since(UNIX_EPOCH) {
+		Ok(d) => d.as_secs() as i64,
+		Err(_) => 0
+	}
+}
+
+#[derive(Debug,Clone,PartialEq)]
+pub struct ThrottleLimits {
+	pub enabled: bool,
+	pub register: i64,
+	pub unregister: i64,
+	pub info: i64,
+	pub update: i64,
+	pub service: i64,
+	pub resolve: i64,
+	pub token_failures: i64,
+	pub ban_secs: i64,
+}
+
+impl ThrottleLimits {
+	pub fn dflt() -> ThrottleLimits {
+		ThrottleLimits {
+			enabled: false,
+			register: 10,
+			unregister: 10,
+			info: 120,
+			update: 30,
+			service: 30,
+			resolve: 60,
+			token_failures: 5,
+			ban_secs: 3600,
+		}
+	}
+
+	pub fn from_config(config: &NodeConfig) -> ThrottleLimits {
+		let d = ThrottleLimits::dflt();
+		let n = |key: &str, dflt: i64| config.setting(key).and_then(|v| v.parse::<i64>().ok()).unwrap_or(dflt);
+
+		ThrottleLimits {
+			enabled: match config.setting("throttle") {
+				Some(v) => v == "on",
+				None => BUDGETS.iter().any(|k| config.setting(k).is_some())
+			},
+			register: n("throttle_register", d.register),
+			unregister: n("throttle_unregister", d.unregister),
+			info: n("throttle_info", d.info),
+			update: n("throttle_update", d.update),
+			service: n("throttle_service", d.service),
+			resolve: n("throttle_resolve", d.resolve),
+			token_failures: n("throttle_token_failures", d.token_failures),
+			ban_secs: n("throttle_ban_secs", d.ban_secs),
+		}
+	}
+
+	/// Counter name and budget for a command, if it is limited at all
+	pub fn for_cmd(&self, cmd: &CmdType) -> Option<(&'static str, i64)> {
+		match *cmd {
+			CmdType::Register => Some(("register", self.register)),
+			CmdType::Unregister => Some(("unregister", self.unregister)),
+			CmdType::Info => Some(("info", self.info)),
+			CmdType::Update => Some(("update", self.update)),
+			CmdType::Service => Some(("service", self.service)),
+			CmdType::Resolve => Some(("resolve", self.resolve)),
+			_ => None
+		}
+	}
+
+	fn for_counter(&self, counter: &str) -> Option<i64> {
+		[CmdType::Register, CmdType::Unregister, CmdType::Info, CmdType::Update, CmdType::Service, CmdType::Resolve]
+			.iter()
+			.filter_map(|c| self.for_cmd(c))
+			.find(|&(name, _)| name == counter)
+			.map(|(_, limit)| limit)
+	}
+}
+
+#[derive(Debug,Clone,PartialEq)]
+pub struct Throttled {
+	pub address: String,
+	pub reason: String,
+	pub count: i64,
+	pub until: String,
+}
+
+impl NetspaceIo {
+
+	/// Count a request from `address`, false when it is over budget
+	pub fn throttle_hit(&self, address: &str, counter: &str, limit: i64) -> Result<bool,StorageFailure> {
+		Ok(try!(self.throttle_count(address, counter, WINDOW_SECS)) <= limit)
+	}
+
+	/// Count a failed token, banning the address once it has had too many
+	pub fn throttle_failure(&self, address: &str, limits: &ThrottleLimits) -> Result<bool,StorageFailure> {
+		let failures = try!(self.throttle_count(address, TOKEN_FAILURE, limits.ban_secs));
+
+		if failures < limits.token_failures { return Ok(false) }
+
+		try!(self.throttle_ban(address, limits.ban_secs, &format!("{} token failures", failures)));
+		Ok(true)
+	}
+
+	pub fn throttle_banned(&self, address: &str) -> Result<bool,StorageFailure> {
+		let mut statement = try!(self.db().prepare(format!("SELECT 1 FROM `throttle_bans`
+									WHERE address = ? AND until > {}", NOW)));
+		try!(statement.bind(1, &Value::String( address.to_string() )));
+
+		match try!(step(&mut statement)) {
+			State::Row => Ok(true),
+			State::Done => Ok(false)
+		}
+	}
+
+	pub fn throttle_ban(&self, address: &str, secs: i64, reason: &str) -> Result<(),StorageFailure> {
+		let mut statement = try!(self.db().prepare(format!("INSERT OR REPLACE INTO `throttle_bans`
+									(address,until,reason) VALUES (?,{}+?,?)", NOW)));
+		try!(statement.bind(1, &Value::String( address.to_string() )));
+		try!(statement.bind(2, &Value::Integer( secs )));
+		try!(statement.bind(3, &Value::String( reason.to_string() )));
+		try!(step(&mut statement));
+
+		log_warn!("throttle", "Banned {} for {}s: {}", address, secs, reason);
+		Ok(())
+	}
+
+	/// Lift a ban and forget every counter held against the address
+	pub fn throttle_clear(&self, address: &str) -> Result<(),StorageFailure> {
+		let mut statement = try!(self.db().prepare("DELETE FROM `throttle_bans` WHERE address = ?"));
+		try!(statement.bind(1, &Value::String( address.to_string() )));
+		try!(step(&mut statement));
+
+		if let Ok(mut c) = self.throttle_counters().lock() {
+			c.hits.retain(|k, _| k.0 != address);
+		}
+
+		Ok(())
+	}
+
+	/// Addresses that are banned or over budget in the current window
+	pub fn throttle_listing(&self, limits: &ThrottleLimits) -> Result<Vec<Throttled>,StorageFailure> {
+		let mut v = Vec::new();
+
+		let mut statement = try!(self.db().prepare(format!("SELECT address,reason,datetime(until,'unixepoch')
+									FROM `throttle_bans` WHERE until > {} ORDER BY until DESC", NOW)));
+		while let State::Row = try!(step(&mut statement)) {
+			v.push(Throttled {
+				address: try!(statement.read::<String>(0)),
+				reason: try!(statement.read::<String>(1)),
+				count: 0,
+				until: try!(statement.read::<String>(2)),
+			});
+		}
+
+		let now = now();
+		let mut over = Vec::new();
+		if let Ok(c) = self.throttle_counters().lock() {
+			for (&(ref address, ref counter), h) in &c.hits {
+				if counter == TOKEN_FAILURE || h.window + h.length <= now { continue }
+
+				match limits.for_counter(counter) {
+					Some(limit) if h.count > limit => over.push((address.clone(), counter.clone(), *h)),
+					_ => { }
+				}
+			}
+		}
+		over.sort_by(|a, b| a.0.cmp(&b.0));
+
+		for (address, counter, h) in over {
+			let mut statement = try!(self.db().prepare("SELECT datetime(?,'unixepoch')"));
+			try!(statement.bind(1, &Value::Integer( h.window + h.length )));
+			try!(step(&mut statement));
+
+			v.push(Throttled {
+				address: address,
+				reason: format!("{} rate", counter),
+				count: h.count,
+				until: try!(statement.read::<String>(0)),
+			});
+		}
+
+		Ok(v)
+	}
+
+	fn throttle_count(&self, address: &str, counter: &str, window: i64) -> Result<i64,StorageFailure> {
+		match self.throttle_counters().lock() {
+			Ok(mut c) => Ok(c.count(address, counter, window, now())),
+			Err(_) => Err(StorageFailure::Query("throttle counters poisoned".to_string()))
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use netspace::*;
+	use config::mocks::MockConfig;
+
+	#[test]
+	fn ts_throttle_hit_p() {
+		let nsio = NetspaceIo::new(":memory:");
+		assert!(nsio.throttle_hit("192.168.1.2", "register", 2).unwrap());
+		assert!(nsio.throttle_hit("192.168.1.2", "register", 2).unwrap());
+		assert!(!nsio.throttle_hit("192.168.1.2", "register", 2).unwrap());
+
+		// Budgets are per address and per counter
+		assert!(nsio.throttle_hit("192.168.1.3", "register", 2).unwrap());
+		assert!(nsio.throttle_hit("192.168.1.2", "info", 2).unwrap());
+	}
+
+	#[test]
+	fn ts_throttle_failure_ban_p() {
+		let nsio = NetspaceIo::new(":memory:");
+		let mut limits = ThrottleLimits::dflt();
+		limits.token_failures = 2;
+
+		assert!(!nsio.throttle_failure("192.168.1.2", &limits).unwrap());
+		assert!(!nsio.throttle_banned("192.168.1.2").unwrap());
+		assert!(nsio.throttle_failure("192.168.1.2", &limits).unwrap());
+		assert!(nsio.throttle_banned("192.168.1.2").unwrap());
+
+		let listed = nsio.throttle_listing(&limits).unwrap();
+		assert_eq!(listed.len(), 1);
+		assert_eq!(listed[0].address, "192.168.1.2");
+
+		nsio.throttle_clear("192.168.1.2").unwrap();
+		assert!(!nsio.throttle_banned("192.168.1.2").unwrap());
+	}
+
+	#[test]
+	fn ts_throttle_window_pruned_p() {
+		let mut c = Counters::new();
+		assert_eq!(c.count("192.168.1.2", "info", WINDOW_SECS, 1000), 1);
+		assert_eq!(c.count("192.168.1.2", "info", WINDOW_SECS, 1001), 2);
+
+		// A later window starts over and the expired one is dropped
+		assert_eq!(c.count("192.168.1.3", "info", WINDOW_SECS, 1000 + WINDOW_SECS), 1);
+		assert_eq!(c.hits.len(), 1);
+		assert_eq!(c.count("192.168.1.2", "info", WINDOW_SECS, 1000 + WINDOW_SECS), 1);
+	}
+
+	#[test]
+	fn ts_throttle_enabled_f() {
+		assert!(!ThrottleLimits::from_config(&MockConfig::dflt()).enabled);
+		assert!(!ThrottleLimits::from_config(&MockConfig::dflt().with_setting("throttle", "off")).enabled);
+		assert!(ThrottleLimits::from_config(&MockConfig::dflt().with_setting("throttle_info", "10")).enabled);
+		assert!(ThrottleLimits::from_config(&MockConfig::dflt().with_setting("throttle", "on")).enabled);
+	}
+
+	#[test]
+	fn ts_throttle_listing_rate_p() {
+		let nsio = NetspaceIo::new(":memory:");
+		let mut limits = ThrottleLimits::dflt();
+		limits.service = 1;
+
+		nsio.throttle_hit("192.168.1.2", "service", limits.service).unwrap();
+		assert!(nsio.throttle_listing(&limits).unwrap().is_empty());
+
+		nsio.throttle_hit("192.168.1.2", "service", limits.service).unwrap();
+		let listed = nsio.throttle_listing(&limits).unwrap();
+		assert_eq!(listed.len(), 1);
+		assert_eq!(listed[0].count, 2);
+	}
+}
@@ -7,48 +7,15 @@ use ::config::Config;
 
 pub fn setup_live_test_env(nio: &NetspaceIo, config: &Config) {
 	if config.live_test == false { return }
-	let _ = nio.db().execute("
-		CREATE TABLE \"geosub_netspace\" (
-			`id`			INTEGER PRIMARY KEY AUTOINCREMENT,
-			`springname`	TEXT UNIQUE,
-			`hostname`		TEXT,
-			`address`		TEXT,
-			`service`		INTEGER,
-			`status`		INTEGER,
-			`types`			INTEGER,
-			`key`			TEXT
-		);
-		CREATE TABLE \"geosub_metaspace\" (
-			`id`			INTEGER PRIMARY KEY AUTOINCREMENT,
-			`settlement`	TEXT,
-			`postcode`		TEXT,
-			`county`		TEXT,
-			`geosub`		TEXT
-		);
-		CREATE TABLE `geotop_netspace` (
-			`id`			INTEGER PRIMARY KEY AUTOINCREMENT,
-			`springname`	TEXT,
-			`hostname`		TEXT,
-			`address`		TEXT,
-			`service`		INTEGER,
-			`priority`		INTEGER,
-			`geosub`		TEXT,
-			`key`			TEXT
-		);
-		CREATE TABLE `geosub_tokens` (
-			`id`	INTEGER PRIMARY KEY AUTOINCREMENT,
-			`token`	TEXT,
-			`spring`	TEXT
-		);
-		");
-	
+	// Tables are created by the schema migrations when the
+	// testing database is opened
 	reset_live_test_env(nio, config);
 }
 
 // 3858f62230ac3c915f300c664312c63f
 pub fn reset_live_test_env(nio: &NetspaceIo, config: &Config) {
 	if config.live_test == false { return }
-	println!("[Database] Reset testing database");
+	log_info!("database", "Reset testing database");
 	nio.db().execute("DELETE FROM \"geosub_netspace\"").unwrap();
 	nio.db().execute("DELETE FROM \"geotop_netspace\"").unwrap();
 	nio.db().execute("DELETE FROM \"geosub_metaspace\"").unwrap();